@@ -0,0 +1,38 @@
+;; Test fixture for the wasm reducer. `reduce` answers with a copy of its
+;; input, so the host can pick the commands it gets back. An empty input makes
+;; it spin forever until it runs out of fuel.
+;;
+;; Rebuild with `wat2wasm echo.wat -o echo.wasm`.
+(module
+  (memory (export "memory") 1)
+
+  ;; bump allocator, reset once every buffer has been released
+  (global $heap (mut i32) (i32.const 0))
+  (global $live (mut i32) (i32.const 0))
+
+  (func $alloc (export "alloc") (param $len i32) (result i32)
+    (local $ptr i32)
+    (local $pages i32)
+    (local.set $ptr (global.get $heap))
+    (global.set $heap (i32.add (global.get $heap) (local.get $len)))
+    (global.set $live (i32.add (global.get $live) (i32.const 1)))
+    (local.set $pages
+      (i32.div_u (i32.add (global.get $heap) (i32.const 65535)) (i32.const 65536)))
+    (if (i32.gt_u (local.get $pages) (memory.size))
+      (then (drop (memory.grow (i32.sub (local.get $pages) (memory.size))))))
+    (local.get $ptr))
+
+  (func (export "dealloc") (param $ptr i32) (param $len i32)
+    (global.set $live (i32.sub (global.get $live) (i32.const 1)))
+    (if (i32.eqz (global.get $live))
+      (then (global.set $heap (i32.const 0)))))
+
+  (func (export "reduce") (param $ptr i32) (param $len i32) (result i64)
+    (local $out i32)
+    (if (i32.eqz (local.get $len))
+      (then (loop $spin (br $spin))))
+    (local.set $out (call $alloc (local.get $len)))
+    (memory.copy (local.get $out) (local.get $ptr) (local.get $len))
+    (i64.or
+      (i64.shl (i64.extend_i32_u (local.get $out)) (i64.const 32))
+      (i64.extend_i32_u (local.get $len)))))
@@ -1,12 +1,14 @@
 use clap;
 
 use gasket::runtime::Tether;
-use scrolls::{enrich, framework::*, reducers, sources, storage};
+use scrolls::{
+    crosscut::policies::RuntimePolicy, enrich, framework::*, reducers, sources, storage,
+};
 use serde::Deserialize;
-use std::{collections::VecDeque, time::Duration};
+use std::time::Duration;
 use tracing::{info, warn};
 
-use crate::console;
+use crate::{console, metrics};
 
 // use crate::console;
 
@@ -15,11 +17,13 @@ struct ConfigRoot {
     source: sources::Config,
     enrich: Option<enrich::Config>,
     reducer: reducers::Config,
-    storage: storage::Config,
+    storage: StorageConfigs,
     intersect: IntersectConfig,
     finalize: Option<FinalizeConfig>,
     chain: Option<ChainConfig>,
+    policy: Option<RuntimePolicy>,
     retries: Option<gasket::retries::Policy>,
+    metrics: Option<metrics::Config>,
 }
 impl ConfigRoot {
     pub fn new(explicit_file: &Option<std::path::PathBuf>) -> Result<Self, config::ConfigError> {
@@ -43,24 +47,64 @@ impl ConfigRoot {
     }
 }
 
+/// Accepts either a single `[storage]` table or a list of `[[storage]]`
+/// tables, each receiving the full output of the reducer
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum StorageConfigs {
+    One(storage::Config),
+    Many(Vec<storage::Config>),
+}
+impl From<StorageConfigs> for Vec<storage::Config> {
+    fn from(other: StorageConfigs) -> Self {
+        match other {
+            StorageConfigs::One(x) => vec![x],
+            StorageConfigs::Many(x) => x,
+        }
+    }
+}
+
 struct Runtime {
     source: Tether,
     enrich: Tether,
     reducer: Tether,
-    storage: Tether,
+    storage: Vec<Tether>,
+    finished: sources::Finished,
 }
 impl Runtime {
     fn all_tethers(&self) -> impl Iterator<Item = &Tether> {
-        vec![&self.source, &self.enrich, &self.reducer, &self.storage].into_iter()
+        vec![&self.source, &self.enrich, &self.reducer]
+            .into_iter()
+            .chain(self.storage.iter())
     }
 
+    /// Stages are chained in order, so a stage that ends while its upstream
+    /// is still alive means the pipeline can't make progress anymore. Every
+    /// storage stage hangs from the reducer.
     fn should_stop(&self) -> bool {
-        self.all_tethers().any(|tether| match tether.check_state() {
-            gasket::runtime::TetherState::Alive(x) => {
-                matches!(x, gasket::runtime::StagePhase::Ended)
-            }
-            _ => true,
-        })
+        let chained = [&self.source, &self.enrich, &self.reducer]
+            .windows(2)
+            .any(|pair| !is_ended(pair[0]) && is_ended(pair[1]));
+
+        let storage = !is_ended(&self.reducer) && self.storage.iter().any(is_ended);
+
+        chained || storage
+    }
+
+    /// When the source finishes (eg: after reaching the finalize config),
+    /// each downstream stage ends after draining its queue. A source that
+    /// fails also ends every stage, which isn't a clean finish.
+    fn is_drained(&self) -> Result<bool, Error> {
+        if !self.all_tethers().all(is_ended) {
+            return Ok(false);
+        }
+
+        match self.finished.is_set() {
+            true => Ok(true),
+            false => Err(Error::message(
+                "pipeline ended before the source finished, check the logs for errors",
+            )),
+        }
     }
 
     fn shutdown(&self) {
@@ -82,6 +126,15 @@ impl Runtime {
     }
 }
 
+fn is_ended(tether: &Tether) -> bool {
+    match tether.check_state() {
+        gasket::runtime::TetherState::Alive(x) => {
+            matches!(x, gasket::runtime::StagePhase::Ended)
+        }
+        _ => true,
+    }
+}
+
 fn define_gasket_policy(config: Option<&gasket::retries::Policy>) -> gasket::runtime::Policy {
     let default_policy = gasket::retries::Policy {
         max_retries: 20,
@@ -103,7 +156,7 @@ fn chain_stages<'a>(
     source: &'a mut dyn StageBootstrapper,
     enrich: &'a mut dyn StageBootstrapper,
     reducer: &'a mut dyn StageBootstrapper,
-    storage: &'a mut dyn StageBootstrapper,
+    storages: Vec<&'a mut dyn StageBootstrapper>,
 ) {
     let (to_next, from_prev) = gasket::messaging::tokio::mpsc_channel(100);
     source.connect_output(to_next);
@@ -113,25 +166,41 @@ fn chain_stages<'a>(
     enrich.connect_output(to_next);
     reducer.connect_input(from_prev);
 
-    let (to_next, from_prev) = gasket::messaging::tokio::mpsc_channel(100);
-    reducer.connect_output(to_next);
-    storage.connect_input(from_prev);
+    for storage in storages {
+        let (to_next, from_prev) = gasket::messaging::tokio::mpsc_channel(100);
+        reducer.connect_output(to_next);
+        storage.connect_input(from_prev);
+    }
 }
 
 fn bootstrap(
     mut source: sources::Bootstrapper,
     mut enrich: enrich::Bootstrapper,
     mut reducer: reducers::Bootstrapper,
-    mut storage: storage::Bootstrapper,
+    mut storages: Vec<storage::Bootstrapper>,
     policy: gasket::runtime::Policy,
 ) -> Result<Runtime, Error> {
-    chain_stages(&mut source, &mut enrich, &mut reducer, &mut storage);
+    chain_stages(
+        &mut source,
+        &mut enrich,
+        &mut reducer,
+        storages
+            .iter_mut()
+            .map(|x| x as &mut dyn StageBootstrapper)
+            .collect(),
+    );
+
+    let finished = source.finished();
 
     let runtime = Runtime {
         source: source.spawn(policy.clone()),
         enrich: enrich.spawn(policy.clone()),
         reducer: reducer.spawn(policy.clone()),
-        storage: storage.spawn(policy.clone()),
+        storage: storages
+            .into_iter()
+            .map(|x| x.spawn(policy.clone()))
+            .collect(),
+        finished,
     };
 
     Ok(runtime)
@@ -145,16 +214,35 @@ pub fn run(args: &Args) -> Result<(), Error> {
     let chain = config.chain.unwrap_or_default();
     let intersect = config.intersect;
     let finalize = config.finalize;
+    let policy = config.policy.unwrap_or_default();
     // let current_dir = std::env::current_dir().unwrap();
 
-    // TODO: load from persistence mechanism
-    let cursor = Cursor::new(VecDeque::new());
+    let storages: Vec<storage::Config> = config.storage.into();
+
+    let cursors = storages
+        .iter()
+        .map(|x| x.load_cursor())
+        .collect::<Result<Vec<_>, _>>()?;
+
+    // the source resumes from the storage that is furthest behind, the rest
+    // skip the events they already persisted
+    let cursor = cursors
+        .iter()
+        .min_by_key(|x| x.latest_known_point().map(|p| p.slot_or_default()))
+        .cloned()
+        .ok_or_else(|| Error::config("at least one storage is required"))?;
+
+    match cursor.latest_known_point() {
+        Some(point) => info!(?point, "resuming from persisted cursor"),
+        None => info!("no persisted cursor, using intersect config"),
+    };
 
     let ctx = Context {
         chain,
         intersect,
         finalize,
         cursor,
+        policy,
     };
 
     let source = config.source.bootstrapper(&ctx)?;
@@ -164,15 +252,34 @@ pub fn run(args: &Args) -> Result<(), Error> {
         .bootstrapper(&ctx)?;
 
     let reducer = config.reducer.bootstrapper(&ctx)?;
-    let storage = config.storage.bootstrapper(&ctx)?;
+    let storages = storages
+        .into_iter()
+        .zip(cursors)
+        .map(|(config, cursor)| {
+            let ctx = Context {
+                cursor,
+                ..ctx.clone()
+            };
+
+            config.bootstrapper(&ctx)
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+
+    metrics::initialize(config.metrics.as_ref())?;
 
     let retries = define_gasket_policy(config.retries.as_ref());
-    let runtime = bootstrap(source, enrich, reducer, storage, retries)?;
+    let runtime = bootstrap(source, enrich, reducer, storages, retries)?;
 
     info!("Scrolls is running...");
 
     while !runtime.should_stop() {
+        if runtime.is_drained()? {
+            info!("Scrolls finished processing, all stages drained");
+            return Ok(());
+        }
+
         console::refresh(&args.console, runtime.all_tethers());
+        metrics::refresh(config.metrics.as_ref(), runtime.all_tethers());
         std::thread::sleep(Duration::from_millis(1500));
     }
 
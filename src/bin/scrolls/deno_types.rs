@@ -0,0 +1,20 @@
+use scrolls::{framework::Error, reducers::deno::SDK_TYPES};
+
+#[derive(clap::Args)]
+#[clap(author, version, about, long_about = None)]
+pub struct Args {
+    /// File to write the type definitions to, stdout if missing
+    #[clap(long, value_parser)]
+    output: Option<std::path::PathBuf>,
+}
+
+/// Writes the type definitions of the `scrolls:sdk` module, so that Deno
+/// reducers can be type checked outside of the daemon
+pub fn run(args: &Args) -> Result<(), Error> {
+    match &args.output {
+        Some(path) => std::fs::write(path, SDK_TYPES).map_err(Error::config)?,
+        None => print!("{}", SDK_TYPES),
+    };
+
+    Ok(())
+}
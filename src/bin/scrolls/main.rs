@@ -3,6 +3,10 @@ use std::process;
 
 mod console;
 mod daemon;
+mod metrics;
+
+#[cfg(feature = "deno")]
+mod deno_types;
 
 #[derive(Parser)]
 #[clap(name = "Scrolls")]
@@ -10,6 +14,10 @@ mod daemon;
 #[clap(author, version, about, long_about = None)]
 enum Scrolls {
     Daemon(daemon::Args),
+
+    /// Prints the type definitions of the scrolls:sdk module of Deno reducers
+    #[cfg(feature = "deno")]
+    DenoTypes(deno_types::Args),
 }
 
 fn main() {
@@ -17,6 +25,9 @@ fn main() {
 
     let result = match args {
         Scrolls::Daemon(x) => daemon::run(&x),
+
+        #[cfg(feature = "deno")]
+        Scrolls::DenoTypes(x) => deno_types::run(&x),
     };
 
     if let Err(err) = &result {
@@ -0,0 +1,215 @@
+use std::{collections::HashMap, net::SocketAddr, sync::Mutex};
+
+use gasket::{metrics::Reading, runtime::Tether};
+use lazy_static::lazy_static;
+use prometheus_exporter::prometheus::{
+    register_int_counter_vec, register_int_gauge, register_int_gauge_vec, IntCounterVec, IntGauge,
+    IntGaugeVec,
+};
+use scrolls::framework::Error;
+use serde::Deserialize;
+use tracing::{info, warn};
+
+const STAGE_STATES: [&str; 6] = [
+    "bootstrap",
+    "working",
+    "teardown",
+    "ended",
+    "blocked",
+    "dropped",
+];
+
+/// Exposes the metrics of every stage through a Prometheus HTTP endpoint
+#[derive(Deserialize)]
+pub struct Config {
+    /// Address to listen on, defaults to `0.0.0.0:9186`
+    address: Option<String>,
+
+    /// Path serving the metrics, defaults to `/metrics`
+    endpoint: Option<String>,
+}
+
+enum StageMetric {
+    Counter(IntCounterVec),
+    Gauge(IntGaugeVec),
+}
+
+struct PrometheusExporter {
+    stage_metrics: Mutex<HashMap<&'static str, StageMetric>>,
+    stage_state: IntGaugeVec,
+    chainsync_lag: IntGauge,
+}
+
+impl PrometheusExporter {
+    fn new() -> Self {
+        Self {
+            stage_metrics: Default::default(),
+            stage_state: register_int_gauge_vec!(
+                "scrolls_stage_state",
+                "Current state of each pipeline stage",
+                &["stage", "state"]
+            )
+            .unwrap(),
+            chainsync_lag: register_int_gauge!(
+                "scrolls_chainsync_lag_slots",
+                "Slots between the tip of the chain and the latest block of the slowest storage"
+            )
+            .unwrap(),
+        }
+    }
+
+    fn register_metric(key: &str, reading: &Reading) -> Option<StageMetric> {
+        let name = format!("scrolls_{}", key);
+        let help = format!("Stage metric `{}`", key);
+
+        let metric = match reading {
+            Reading::Count(_) => {
+                register_int_counter_vec!(name, help, &["stage"]).map(StageMetric::Counter)
+            }
+            Reading::Gauge(_) => {
+                register_int_gauge_vec!(name, help, &["stage"]).map(StageMetric::Gauge)
+            }
+            Reading::Message(_) => return None,
+        };
+
+        metric
+            .map_err(|err| warn!(key, %err, "can't register stage metric"))
+            .ok()
+    }
+
+    fn update_metric(&self, stage: &str, key: &'static str, reading: Reading) {
+        let mut metrics = self.stage_metrics.lock().unwrap();
+
+        if !metrics.contains_key(key) {
+            match Self::register_metric(key, &reading) {
+                Some(metric) => metrics.insert(key, metric),
+                None => return,
+            };
+        }
+
+        match (metrics.get(key), reading) {
+            (Some(StageMetric::Counter(vec)), Reading::Count(x)) => {
+                let counter = vec.with_label_values(&[stage]);
+                let current = counter.get();
+
+                if x > current {
+                    counter.inc_by(x - current);
+                }
+            }
+            (Some(StageMetric::Gauge(vec)), Reading::Gauge(x)) => {
+                vec.with_label_values(&[stage]).set(x);
+            }
+            _ => warn!(stage, key, "stage metric doesn't match its registered type"),
+        }
+    }
+
+    fn update_state(&self, tether: &Tether) {
+        let current = match tether.check_state() {
+            gasket::runtime::TetherState::Dropped => "dropped",
+            gasket::runtime::TetherState::Blocked(_) => "blocked",
+            gasket::runtime::TetherState::Alive(x) => match x {
+                gasket::runtime::StagePhase::Bootstrap => "bootstrap",
+                gasket::runtime::StagePhase::Working => "working",
+                gasket::runtime::StagePhase::Teardown => "teardown",
+                gasket::runtime::StagePhase::Ended => "ended",
+            },
+        };
+
+        for state in STAGE_STATES {
+            self.stage_state
+                .with_label_values(&[tether.name(), state])
+                .set((state == current) as i64);
+        }
+    }
+
+    fn refresh<'a>(&self, tethers: impl Iterator<Item = &'a Tether>) {
+        let mut chain_tip = None;
+        let mut latest_blocks = Vec::new();
+
+        for tether in tethers {
+            self.update_state(tether);
+
+            let readings = match tether.read_metrics() {
+                Ok(x) => x,
+                Err(err) => {
+                    warn!("[{}] error reading metrics: {}", tether.name(), err);
+                    continue;
+                }
+            };
+
+            for (key, value) in readings {
+                match (key, &value) {
+                    ("chain_tip", Reading::Gauge(x)) => chain_tip = Some(*x),
+                    ("latest_block", Reading::Gauge(x)) => latest_blocks.push(*x),
+                    _ => (),
+                };
+
+                self.update_metric(tether.name(), key, value);
+            }
+        }
+
+        if let Some(lag) = chainsync_lag(chain_tip, &latest_blocks) {
+            self.chainsync_lag.set(lag);
+        }
+    }
+}
+
+/// Slots between the tip and the latest block of the storage furthest behind
+fn chainsync_lag(chain_tip: Option<i64>, latest_blocks: &[i64]) -> Option<i64> {
+    let tip = chain_tip?;
+    let latest = latest_blocks.iter().min()?;
+
+    Some((tip - latest).max(0))
+}
+
+lazy_static! {
+    static ref PROMETHEUS_EXPORTER: PrometheusExporter = PrometheusExporter::new();
+}
+
+/// Starts the HTTP listener serving the metrics, if configured
+pub fn initialize(config: Option<&Config>) -> Result<(), Error> {
+    let config = match config {
+        Some(x) => x,
+        None => return Ok(()),
+    };
+
+    let address: SocketAddr = config
+        .address
+        .as_deref()
+        .unwrap_or("0.0.0.0:9186")
+        .parse()
+        .map_err(Error::config)?;
+
+    let mut builder = prometheus_exporter::Exporter::builder(address);
+
+    if let Some(endpoint) = &config.endpoint {
+        builder.with_endpoint(endpoint).map_err(Error::config)?;
+    }
+
+    builder.start().map_err(Error::config)?;
+
+    lazy_static::initialize(&PROMETHEUS_EXPORTER);
+
+    info!(%address, "serving prometheus metrics");
+
+    Ok(())
+}
+
+pub fn refresh<'a>(config: Option<&Config>, tethers: impl Iterator<Item = &'a Tether>) {
+    if config.is_some() {
+        PROMETHEUS_EXPORTER.refresh(tethers);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::chainsync_lag;
+
+    #[test]
+    fn measures_lag_of_slowest_storage() {
+        assert_eq!(chainsync_lag(Some(100), &[90, 60, 95]), Some(40));
+        assert_eq!(chainsync_lag(Some(100), &[120]), Some(0));
+        assert_eq!(chainsync_lag(Some(100), &[]), None);
+        assert_eq!(chainsync_lag(None, &[90]), None);
+    }
+}
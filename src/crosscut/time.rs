@@ -1,3 +1,7 @@
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use pallas::{ledger::traverse::wellknown::GenesisValues, network::miniprotocols::Point};
+
 use super::ChainWellKnownInfo;
 
 #[inline]
@@ -18,6 +22,27 @@ fn compute_era_epoch(era_slot: u64, era_slot_length: u64, era_epoch_length: u64)
     (epoch, reminder)
 }
 
+/// Default distance, in slots, from the tip of the chain past which blocks are
+/// considered stable. Matches the stability window (3k/f) of mainnet.
+pub const DEFAULT_TIP_DISTANCE: u64 = 129_600;
+
+/// Slot the chain should be at according to the wall clock
+pub fn wallclock_slot(chain: &GenesisValues) -> u64 {
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs();
+
+    let elapsed = now.saturating_sub(chain.shelley_known_time);
+
+    chain.shelley_known_slot + elapsed / chain.shelley_slot_length.max(1) as u64
+}
+
+/// Tells if the point is more than `distance` slots behind the wall-clock tip
+pub fn is_far_from_tip(chain: &GenesisValues, point: &Point, distance: u64) -> bool {
+    wallclock_slot(chain).saturating_sub(point.slot_or_default()) > distance
+}
+
 /// A naive, standalone implementation of a time provider
 ///
 /// This time provider doesn't require any external resources other than an
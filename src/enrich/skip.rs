@@ -16,27 +16,38 @@ pub struct Stage {
 #[derive(Default)]
 pub struct Worker;
 
-impl From<&Stage> for Worker {
-    fn from(_: &Stage) -> Self {
-        Self
+#[async_trait::async_trait(?Send)]
+impl gasket::framework::Worker<Stage> for Worker {
+    async fn bootstrap(_: &Stage) -> Result<Self, WorkerError> {
+        Ok(Self)
     }
-}
 
-gasket::impl_mapper!(|_worker: Worker, stage: Stage, unit: ChainEvent| => {
-    let evt = match unit {
-        ChainEvent::Apply(point, record) => {
-            match record {
-                Record::RawBlockPayload(cbor) => Ok(ChainEvent::apply(point.clone(), Record::EnrichedBlockPayload(cbor.clone(), BlockContext::default()))),
-                _ => Err(WorkerError::Panic)
-            }
-        },
-        ChainEvent::Reset(point) => Ok(ChainEvent::reset(point.clone())),
-    }?;
-
-    stage.ops_count.inc(1);
-
-    evt
-});
+    async fn schedule(
+        &mut self,
+        stage: &mut Stage,
+    ) -> Result<WorkSchedule<ChainEvent>, WorkerError> {
+        recv_or_done(&mut stage.input).await
+    }
+
+    async fn execute(&mut self, unit: &ChainEvent, stage: &mut Stage) -> Result<(), WorkerError> {
+        let evt = match unit {
+            ChainEvent::Apply(point, record) => match record {
+                Record::RawBlockPayload(cbor) => Ok(ChainEvent::apply(
+                    point.clone(),
+                    Record::EnrichedBlockPayload(cbor.clone(), BlockContext::default()),
+                )),
+                _ => Err(WorkerError::Panic),
+            },
+            ChainEvent::Reset(point) => Ok(ChainEvent::reset(point.clone())),
+        }?;
+
+        stage.output.send(evt).await.or_panic()?;
+
+        stage.ops_count.inc(1);
+
+        Ok(())
+    }
+}
 
 #[derive(Default, Deserialize)]
 pub struct Config {}
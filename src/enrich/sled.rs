@@ -1,19 +1,29 @@
 use pallas::{
     codec::minicbor,
     ledger::traverse::{Era, MultiEraBlock, MultiEraTx, OutputRef},
+    network::miniprotocols::Point,
 };
 use rayon::prelude::{IntoParallelRefIterator, ParallelIterator};
 use serde::Deserialize;
-use sled::{Db, IVec};
+use sled::{Db, IVec, Tree};
+use tracing::warn;
 
 use gasket::framework::*;
 
 use crate::framework::{
-    model::BlockContext, ChainEvent, Context, EnrichInputPort, EnrichOutputPort, Error, Record,
+    model::BlockContext, recv_or_done, ChainEvent, Context, EnrichInputPort, EnrichOutputPort,
+    Error, Record,
 };
 
+const JOURNAL_TREE: &str = "_journal";
+const DEFAULT_JOURNAL_DEPTH: usize = 2160;
+
 pub struct Worker {
     db: Db,
+    journal: Tree,
+
+    /// Number of entries in the journal, kept to trim it without a scan
+    journaled: usize,
 }
 impl Worker {
     #[inline]
@@ -39,6 +49,132 @@ impl Worker {
         Ok(())
     }
 
+    /// Records which utxos the block is about to insert and remove, keeping
+    /// the previous value of the removed ones so that they can be restored
+    ///
+    /// A block replayed after a restart keeps its existing entry, which holds
+    /// the values from before the utxo set was partially updated.
+    fn journal_block(
+        &mut self,
+        db: &sled::Db,
+        slot: u64,
+        txs: &[MultiEraTx],
+        depth: usize,
+    ) -> Result<(), Error> {
+        let inserted = txs
+            .iter()
+            .flat_map(|tx| {
+                let hash = tx.hash();
+                tx.produces()
+                    .into_iter()
+                    .map(move |(idx, _)| format!("{}#{}", hash, idx))
+            })
+            .collect();
+
+        let mut removed = Vec::new();
+
+        for key in txs.iter().flat_map(|tx| tx.consumes()) {
+            let key = key.output_ref().to_string();
+
+            if let Some(value) = db.get(&key).map_err(Error::storage)? {
+                removed.push((key, value.to_vec()));
+            }
+        }
+
+        let entry: IVec = SledJournalEntry(inserted, removed).try_into()?;
+
+        let swap = self
+            .journal
+            .compare_and_swap(slot.to_be_bytes(), None as Option<IVec>, Some(entry))
+            .map_err(Error::storage)?;
+
+        if swap.is_ok() {
+            self.journaled += 1;
+        }
+
+        while self.journaled > depth {
+            match self.journal.pop_min().map_err(Error::storage)? {
+                Some(_) => self.journaled -= 1,
+                None => self.journaled = 0,
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Journals the block, then updates the utxo set with it, returning the
+    /// utxos it references
+    fn apply_block(
+        &mut self,
+        db: &sled::Db,
+        slot: u64,
+        txs: &[MultiEraTx],
+        depth: usize,
+    ) -> Result<BlockContext, Error> {
+        // before touching the utxo set, we keep track of what the block changes
+        self.journal_block(db, slot, txs, depth)?;
+
+        // first we insert new utxo produced in this block
+        self.insert_produced_utxos(db, txs)?;
+
+        // then we fetch referenced utxo in this block
+        let ctx = self.par_fetch_referenced_utxos(db, txs)?;
+
+        // and finally we remove utxos consumed by the block
+        self.remove_consumed_utxos(db, txs)?;
+
+        Ok(ctx)
+    }
+
+    /// Undoes every journaled block after the rollback point, newest first
+    fn rollback_to(&mut self, db: &sled::Db, point: &Point) -> Result<(), Error> {
+        let slot = point.slot_or_default();
+
+        let undone: Vec<_> = self
+            .journal
+            .range((slot + 1).to_be_bytes()..)
+            .keys()
+            .rev()
+            .collect::<Result<_, _>>()
+            .map_err(Error::storage)?;
+
+        if let Some((oldest, _)) = self.journal.first().map_err(Error::storage)? {
+            if slot_from_key(&oldest) > slot {
+                warn!(
+                    slot,
+                    "rollback goes past the sled journal, utxo set may be stale"
+                );
+            }
+        }
+
+        for key in undone {
+            let entry = match self.journal.get(&key).map_err(Error::storage)? {
+                Some(x) => x,
+                None => continue,
+            };
+
+            let SledJournalEntry(inserted, removed) = entry.try_into()?;
+
+            let mut batch = sled::Batch::default();
+
+            for (key, value) in removed {
+                batch.insert(key.as_bytes(), value);
+            }
+
+            for key in inserted {
+                batch.remove(key.as_bytes());
+            }
+
+            db.apply_batch(batch).map_err(Error::storage)?;
+
+            if self.journal.remove(&key).map_err(Error::storage)?.is_some() {
+                self.journaled -= 1;
+            }
+        }
+
+        Ok(())
+    }
+
     #[inline]
     fn par_fetch_referenced_utxos(
         &self,
@@ -94,26 +230,44 @@ impl Worker {
 impl gasket::framework::Worker<Stage> for Worker {
     async fn bootstrap(stage: &Stage) -> Result<Self, WorkerError> {
         let db = sled::open(&stage.config.db_path).or_panic()?;
-        Ok(Self { db })
+        let journal = db.open_tree(JOURNAL_TREE).or_panic()?;
+        let journaled = journal.len();
+
+        Ok(Self {
+            db,
+            journal,
+            journaled,
+        })
     }
 
     async fn schedule(
         &mut self,
         stage: &mut Stage,
     ) -> Result<WorkSchedule<ChainEvent>, WorkerError> {
-        let msg = stage.input.recv().await.or_panic()?;
-        Ok(WorkSchedule::Unit(msg.payload))
+        recv_or_done(&mut stage.input).await
     }
 
     async fn execute(&mut self, unit: &ChainEvent, stage: &mut Stage) -> Result<(), WorkerError> {
-        let record = unit.record();
         let point = unit.point();
 
-        if record.is_none() {
-            return Ok(());
-        }
+        let record = match unit {
+            ChainEvent::Apply(_, record) => record,
+            ChainEvent::Reset(_) => {
+                let db = self.db.clone();
+                self.rollback_to(&db, point).or_restart()?;
+
+                stage
+                    .output
+                    .send(ChainEvent::reset(point.clone()))
+                    .await
+                    .or_retry()?;
+
+                stage.ops_count.inc(1);
+
+                return Ok(());
+            }
+        };
 
-        let record = record.unwrap();
         match record {
             Record::RawBlockPayload(cbor) => {
                 let block = MultiEraBlock::decode(&cbor)
@@ -123,17 +277,12 @@ impl gasket::framework::Worker<Stage> for Worker {
 
                 let txs = block.txs();
 
-                // first we insert new utxo produced in this block
-                self.insert_produced_utxos(&self.db, &txs).or_restart()?;
-
-                // then we fetch referenced utxo in this block
+                let db = self.db.clone();
+                let depth = stage.config.journal_depth.unwrap_or(DEFAULT_JOURNAL_DEPTH);
                 let ctx = self
-                    .par_fetch_referenced_utxos(&self.db, &txs)
+                    .apply_block(&db, block.slot(), &txs, depth)
                     .or_restart()?;
 
-                // and finally we remove utxos consumed by the block
-                self.remove_consumed_utxos(&self.db, &txs).or_restart()?;
-
                 let evt = ChainEvent::apply(
                     point.clone(),
                     Record::EnrichedBlockPayload(cbor.clone(), ctx),
@@ -164,6 +313,10 @@ pub struct Stage {
 #[derive(Default, Deserialize)]
 pub struct Config {
     pub db_path: String,
+
+    /// Number of blocks kept in the undo journal, defaults to the security
+    /// parameter of the chain
+    pub journal_depth: Option<usize>,
 }
 
 impl Config {
@@ -203,6 +356,39 @@ impl TryFrom<IVec> for SledTxValue {
     }
 }
 
+/// Utxo keys inserted by a block and the previous values of the keys it
+/// removed
+struct SledJournalEntry(Vec<String>, Vec<(String, Vec<u8>)>);
+
+impl TryInto<IVec> for SledJournalEntry {
+    type Error = Error;
+
+    fn try_into(self) -> Result<IVec, Self::Error> {
+        let SledJournalEntry(inserted, removed) = self;
+
+        minicbor::to_vec((inserted, removed))
+            .map(IVec::from)
+            .map_err(Error::cbor)
+    }
+}
+
+impl TryFrom<IVec> for SledJournalEntry {
+    type Error = Error;
+
+    fn try_from(value: IVec) -> Result<Self, Self::Error> {
+        let (inserted, removed): (Vec<String>, Vec<(String, Vec<u8>)>) =
+            minicbor::decode(&value).map_err(Error::cbor)?;
+
+        Ok(SledJournalEntry(inserted, removed))
+    }
+}
+
+fn slot_from_key(key: &IVec) -> u64 {
+    let mut bytes = [0u8; 8];
+    bytes.copy_from_slice(&key[..8]);
+    u64::from_be_bytes(bytes)
+}
+
 #[inline]
 fn fetch_referenced_utxo<'a>(
     db: &sled::Db,
@@ -216,3 +402,107 @@ fn fetch_referenced_utxo<'a>(
 
     Ok(None)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn test_worker() -> Worker {
+        let db = sled::Config::new().temporary(true).open().unwrap();
+        let journal = db.open_tree(JOURNAL_TREE).unwrap();
+
+        Worker {
+            db,
+            journal,
+            journaled: 0,
+        }
+    }
+
+    fn utxo_set(db: &sled::Db) -> Vec<(IVec, IVec)> {
+        db.iter().collect::<Result<_, _>>().unwrap()
+    }
+
+    fn test_block() -> Vec<u8> {
+        let hex = std::fs::read_to_string("assets/test.block").unwrap();
+        hex::decode(hex.trim()).unwrap()
+    }
+
+    #[test]
+    fn restores_utxo_set_on_rollback() {
+        let mut worker = test_worker();
+        let db = worker.db.clone();
+
+        let cbor = test_block();
+        let block = MultiEraBlock::decode(&cbor).unwrap();
+        let txs = block.txs();
+        assert!(txs.len() > 1);
+
+        // the utxos consumed by the block, as if produced by earlier blocks
+        for input in txs.iter().flat_map(|tx| tx.consumes()) {
+            let value: IVec = SledTxValue(5, vec![0x80]).try_into().unwrap();
+            db.insert(input.output_ref().to_string(), value).unwrap();
+        }
+
+        let (first, second) = txs.split_at(txs.len() / 2);
+
+        let initial = utxo_set(&db);
+
+        worker.apply_block(&db, 10, first, 100).unwrap();
+        let after_first = utxo_set(&db);
+        assert_ne!(after_first, initial);
+
+        worker.apply_block(&db, 20, second, 100).unwrap();
+        assert_ne!(utxo_set(&db), after_first);
+
+        worker
+            .rollback_to(&db, &Point::Specific(10, vec![]))
+            .unwrap();
+        assert_eq!(utxo_set(&db), after_first);
+        assert_eq!(worker.journaled, 1);
+
+        worker.rollback_to(&db, &Point::Origin).unwrap();
+        assert_eq!(utxo_set(&db), initial);
+        assert_eq!(worker.journaled, 0);
+    }
+
+    #[test]
+    fn keeps_existing_entry_when_replaying_block() {
+        let mut worker = test_worker();
+        let db = worker.db.clone();
+
+        let cbor = test_block();
+        let block = MultiEraBlock::decode(&cbor).unwrap();
+        let txs = block.txs();
+
+        for input in txs.iter().flat_map(|tx| tx.consumes()) {
+            let value: IVec = SledTxValue(5, vec![0x80]).try_into().unwrap();
+            db.insert(input.output_ref().to_string(), value).unwrap();
+        }
+
+        let initial = utxo_set(&db);
+
+        // a restart replays the block over an already updated utxo set
+        worker.apply_block(&db, 10, &txs, 100).unwrap();
+        worker.apply_block(&db, 10, &txs, 100).unwrap();
+        assert_eq!(worker.journaled, 1);
+
+        worker.rollback_to(&db, &Point::Origin).unwrap();
+        assert_eq!(utxo_set(&db), initial);
+    }
+
+    #[test]
+    fn trims_journal_to_depth() {
+        let mut worker = test_worker();
+        let db = worker.db.clone();
+
+        for slot in 0..10 {
+            worker.apply_block(&db, slot, &[], 3).unwrap();
+        }
+
+        assert_eq!(worker.journaled, 3);
+        assert_eq!(worker.journal.len(), 3);
+
+        let (oldest, _) = worker.journal.first().unwrap().unwrap();
+        assert_eq!(slot_from_key(&oldest), 7);
+    }
+}
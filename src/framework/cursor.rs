@@ -5,10 +5,34 @@ use std::{
 
 use pallas::network::miniprotocols::Point;
 
+use crate::crosscut::PointArg;
+
+use super::errors::Error;
+
 const HARDCODED_BREADCRUMBS: usize = 20;
 
 type State = VecDeque<Point>;
 
+/// Serialization-friendly version of the cursor state, used for persistence
+pub type Breadcrumbs = Vec<PointArg>;
+
+fn push_breadcrumb(state: &mut State, value: Point) {
+    state.push_front(value);
+
+    if state.len() > HARDCODED_BREADCRUMBS {
+        state.pop_back();
+    }
+}
+
+fn truncate_breadcrumbs(state: &mut State, point: &Point) {
+    state.retain(|x| x.slot_or_default() < point.slot_or_default());
+    push_breadcrumb(state, point.clone());
+}
+
+fn to_breadcrumbs(state: &State) -> Breadcrumbs {
+    state.iter().cloned().map(PointArg::from).collect()
+}
+
 // TODO: include exponential breadcrumbs logic here
 #[derive(Clone)]
 pub struct Cursor(Arc<RwLock<State>>);
@@ -18,6 +42,15 @@ impl Cursor {
         Self(Arc::new(RwLock::new(state)))
     }
 
+    pub fn from_breadcrumbs(breadcrumbs: Breadcrumbs) -> Result<Self, Error> {
+        let state = breadcrumbs
+            .into_iter()
+            .map(|x| x.try_into())
+            .collect::<Result<State, Error>>()?;
+
+        Ok(Self::new(state))
+    }
+
     pub fn is_empty(&self) -> bool {
         let v = self.0.read().unwrap();
         v.is_empty()
@@ -35,11 +68,35 @@ impl Cursor {
 
     pub fn add_breadcrumb(&self, value: Point) {
         let mut state = self.0.write().unwrap();
+        push_breadcrumb(&mut state, value);
+    }
 
-        state.push_front(value);
+    /// Drops every breadcrumb past the rollback point, which becomes the
+    /// latest known point
+    pub fn rollback(&self, point: &Point) {
+        let mut state = self.0.write().unwrap();
+        truncate_breadcrumbs(&mut state, point);
+    }
+
+    pub fn breadcrumbs(&self) -> Breadcrumbs {
+        let state = self.0.read().unwrap();
+        to_breadcrumbs(&state)
+    }
+
+    /// Breadcrumbs the cursor would hold after adding the given point
+    ///
+    /// Useful for persisting the cursor in the same transaction as the data
+    /// of the block, before the in-memory cursor is updated.
+    pub fn breadcrumbs_after_apply(&self, value: &Point) -> Breadcrumbs {
+        let mut state = self.clone_state();
+        push_breadcrumb(&mut state, value.clone());
+        to_breadcrumbs(&state)
+    }
 
-        if state.len() > HARDCODED_BREADCRUMBS {
-            state.pop_back();
-        }
+    /// Breadcrumbs the cursor would hold after rolling back to the given point
+    pub fn breadcrumbs_after_rollback(&self, point: &Point) -> Breadcrumbs {
+        let mut state = self.clone_state();
+        truncate_breadcrumbs(&mut state, point);
+        to_breadcrumbs(&state)
     }
 }
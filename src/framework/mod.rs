@@ -1,5 +1,9 @@
+use gasket::framework::{WorkSchedule, WorkerError};
 use pallas::{ledger::traverse::wellknown::GenesisValues, network::miniprotocols::Point};
 use serde::Deserialize;
+use tracing::info;
+
+use crate::crosscut::policies::RuntimePolicy;
 
 pub mod cursor;
 pub mod errors;
@@ -15,6 +19,10 @@ pub enum Record {
     RawBlockPayload(Vec<u8>),
     EnrichedBlockPayload(Vec<u8>, BlockContext),
     CRDTCommand(Vec<CRDTCommand>),
+    /// Rollback to the point of the event followed by the commands that
+    /// compensate for it, which storages apply atomically and journal so that
+    /// a deeper rollback undoes them
+    Compensation(Vec<CRDTCommand>),
 }
 
 #[derive(Debug, Clone)]
@@ -54,10 +62,26 @@ pub type SourceOutputPort = gasket::messaging::tokio::OutputPort<ChainEvent>;
 pub type EnrichInputPort = gasket::messaging::tokio::InputPort<ChainEvent>;
 pub type EnrichOutputPort = gasket::messaging::tokio::OutputPort<ChainEvent>;
 pub type ReducerInputPort = gasket::messaging::tokio::InputPort<ChainEvent>;
-pub type ReducerOutputPort = gasket::messaging::tokio::OutputPort<ChainEvent>;
+/// Reducers send each event to every connected storage stage
+pub type ReducerOutputPort = gasket::messaging::FanoutPort<OutputAdapter, ChainEvent>;
 pub type StorageInputPort = gasket::messaging::tokio::InputPort<ChainEvent>;
 pub type StorageOutputPort = gasket::messaging::tokio::OutputPort<ChainEvent>;
 
+/// Waits for the next event of an input port, finishing the worker once the
+/// upstream stage is gone and its queue is drained
+pub async fn recv_or_done(
+    input: &mut gasket::messaging::tokio::InputPort<ChainEvent>,
+) -> Result<WorkSchedule<ChainEvent>, WorkerError> {
+    match input.recv().await {
+        Ok(msg) => Ok(WorkSchedule::Unit(msg.payload)),
+        Err(gasket::error::Error::RecvError) => {
+            info!("upstream stage finished, no more events to process");
+            Ok(WorkSchedule::Done)
+        }
+        Err(_) => Err(WorkerError::Panic),
+    }
+}
+
 pub type OutputAdapter = gasket::messaging::tokio::ChannelSendAdapter<ChainEvent>;
 pub type InputAdapter = gasket::messaging::tokio::ChannelRecvAdapter<ChainEvent>;
 
@@ -124,15 +148,109 @@ impl IntersectConfig {
     }
 }
 
+/// Optional configuration to stop processing new blocks after processing:
+///   1. a block with the given hash
+///   2. the first block on or after a given absolute slot
+///   3. a total of X blocks
 #[derive(Deserialize, Debug, Clone)]
 pub struct FinalizeConfig {
     until_hash: Option<String>,
     max_block_slot: Option<u64>,
+    max_block_quantity: Option<u64>,
 }
+impl FinalizeConfig {
+    pub fn should_finalize(&self, last_point: &Point, block_count: u64) -> bool {
+        if let Some(expected) = &self.until_hash {
+            if let Point::Specific(_, current) = last_point {
+                if expected == &hex::encode(current) {
+                    return true;
+                }
+            }
+        }
+
+        if let Some(max) = self.max_block_slot {
+            if last_point.slot_or_default() >= max {
+                return true;
+            }
+        }
 
+        if let Some(max) = self.max_block_quantity {
+            if block_count >= max {
+                return true;
+            }
+        }
+
+        false
+    }
+}
+
+#[derive(Clone)]
 pub struct Context {
     pub chain: ChainConfig,
     pub intersect: IntersectConfig,
     pub cursor: Cursor,
     pub finalize: Option<FinalizeConfig>,
+    pub policy: RuntimePolicy,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const HASH: &str = "a1b2c3d4";
+
+    fn finalize(config: serde_json::Value) -> FinalizeConfig {
+        serde_json::from_value(config).unwrap()
+    }
+
+    fn point(slot: u64, hash: &str) -> Point {
+        Point::Specific(slot, hex::decode(hash).unwrap())
+    }
+
+    #[test]
+    fn finalizes_on_hash() {
+        let config = finalize(serde_json::json!({ "until_hash": HASH }));
+
+        assert!(!config.should_finalize(&point(10, "00"), 1));
+        assert!(config.should_finalize(&point(10, HASH), 1));
+        assert!(!config.should_finalize(&Point::Origin, 1));
+    }
+
+    #[test]
+    fn finalizes_on_or_after_slot() {
+        let config = finalize(serde_json::json!({ "max_block_slot": 100 }));
+
+        assert!(!config.should_finalize(&point(99, HASH), 1));
+        assert!(config.should_finalize(&point(100, HASH), 1));
+        assert!(config.should_finalize(&point(150, HASH), 1));
+    }
+
+    #[test]
+    fn finalizes_on_block_quantity() {
+        let config = finalize(serde_json::json!({ "max_block_quantity": 3 }));
+
+        assert!(!config.should_finalize(&point(10, HASH), 2));
+        assert!(config.should_finalize(&point(10, HASH), 3));
+    }
+
+    #[test]
+    fn finalizes_on_any_condition() {
+        let config = finalize(serde_json::json!({
+            "until_hash": HASH,
+            "max_block_slot": 100,
+            "max_block_quantity": 3,
+        }));
+
+        assert!(!config.should_finalize(&point(10, "00"), 1));
+        assert!(config.should_finalize(&point(10, HASH), 1));
+        assert!(config.should_finalize(&point(100, "00"), 1));
+        assert!(config.should_finalize(&point(10, "00"), 3));
+    }
+
+    #[test]
+    fn never_finalizes_without_conditions() {
+        let config = finalize(serde_json::json!({}));
+
+        assert!(!config.should_finalize(&point(u64::MAX, HASH), u64::MAX));
+    }
 }
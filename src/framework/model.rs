@@ -25,6 +25,12 @@ impl BlockContext {
         MultiEraOutput::decode(*era, cbor).map_err(Error::cbor)
     }
 
+    pub fn iter_utxos(&self) -> impl Iterator<Item = (&str, Era, &[u8])> {
+        self.utxos
+            .iter()
+            .map(|(key, (era, cbor))| (key.as_str(), *era, cbor.as_slice()))
+    }
+
     pub fn get_all_keys(&self) -> Vec<String> {
         self.utxos.keys().map(|x| x.clone()).collect()
     }
@@ -55,7 +61,7 @@ pub type Key = String;
 pub type Delta = i64;
 pub type Timestamp = u64;
 
-#[derive(Clone, Debug, Deserialize)]
+#[derive(Clone, Debug, PartialEq, Deserialize)]
 pub enum Value {
     String(String),
     BigInt(i128),
@@ -81,7 +87,7 @@ impl From<serde_json::Value> for Value {
     }
 }
 
-#[derive(Clone, Debug, Deserialize)]
+#[derive(Clone, Debug, PartialEq, Deserialize)]
 #[non_exhaustive]
 pub enum CRDTCommand {
     SetAdd(Set, Member),
@@ -0,0 +1,142 @@
+use pallas::ledger::traverse::{MultiEraAsset, MultiEraBlock, MultiEraOutput};
+use serde::Deserialize;
+
+use crate::framework::model::{self, CRDTCommand};
+use crate::framework::*;
+
+use super::{ReducerConfigTrait, ReducerTrait};
+
+#[derive(Deserialize)]
+pub struct Config {
+    pub key_prefix: Option<String>,
+    pub filter: Option<Vec<String>>,
+    pub policy_id_hex: String,
+    // bool convert to ascii, default true
+    pub convert_to_ascii: Option<bool>,
+}
+
+pub struct Reducer {
+    config: Config,
+    convert_to_ascii: bool,
+}
+
+impl Reducer {
+    fn to_string_output(&self, asset: &MultiEraAsset) -> Option<String> {
+        if !asset.policy().to_string().eq(&self.config.policy_id_hex) {
+            return None;
+        }
+
+        match self.convert_to_ascii {
+            true => String::from_utf8(asset.name().to_vec()).ok(),
+            false => Some(hex::encode(asset.name())),
+        }
+    }
+
+    fn process_txo(
+        &self,
+        txo: &MultiEraOutput,
+        output: &mut Vec<CRDTCommand>,
+    ) -> Result<(), Error> {
+        let asset_names: Vec<_> = txo
+            .non_ada_assets()
+            .iter()
+            .flat_map(|x| x.assets())
+            .filter_map(|x| self.to_string_output(&x))
+            .collect();
+
+        if asset_names.is_empty() {
+            return Ok(());
+        }
+
+        let address = txo
+            .address()
+            .map(|x| x.to_string())
+            .map_err(Error::ledger)?;
+
+        for asset in asset_names {
+            log::debug!("asset match found: ${asset}=>{address}");
+
+            let crdt = CRDTCommand::any_write_wins(
+                self.config.key_prefix.as_deref(),
+                asset,
+                address.clone(),
+            );
+
+            output.push(crdt);
+        }
+
+        Ok(())
+    }
+}
+
+#[async_trait::async_trait]
+impl ReducerTrait for Reducer {
+    async fn reduce_block<'b>(
+        &mut self,
+        block: &'b MultiEraBlock<'b>,
+        _ctx: &model::BlockContext,
+    ) -> Result<Vec<CRDTCommand>, Error> {
+        let mut commands = Vec::new();
+
+        for tx in block.txs().iter() {
+            for (_, txo) in tx.produces() {
+                self.process_txo(&txo, &mut commands)?;
+            }
+        }
+
+        Ok(commands)
+    }
+}
+
+impl ReducerConfigTrait for Config {
+    fn plugin(self, _ctx: &Context) -> Box<dyn ReducerTrait> {
+        let convert_to_ascii = self.convert_to_ascii.unwrap_or(false);
+        let reducer = Reducer {
+            config: self,
+            convert_to_ascii,
+        };
+
+        Box::new(reducer)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::reducers::builtin::tests::reduce_test_block;
+
+    const HOSKY_POLICY: &str = "a0028f350aaabe0545fdcb56b039bfb08e4bb4d8c4d7c3c7d481c235";
+
+    #[test]
+    fn writes_address_holding_each_asset() {
+        let commands = reduce_test_block(Config {
+            key_prefix: None,
+            filter: None,
+            policy_id_hex: HOSKY_POLICY.into(),
+            convert_to_ascii: Some(true),
+        });
+
+        assert_eq!(commands.len(), 157);
+
+        assert_eq!(
+            commands[0],
+            CRDTCommand::AnyWriteWins(
+                "HOSKY".into(),
+                model::Value::String("addr1q8fukvydr8m5y3gztte3d4tnw0v5myvshusmu45phf20h395kqnygcykgjy42m29tksmwnd0js0z8p3swm5ntryhfu8sg7835c".into())
+            )
+        );
+    }
+
+    #[test]
+    fn keeps_asset_name_as_hex() {
+        let commands = reduce_test_block(Config {
+            key_prefix: Some("asset".into()),
+            filter: None,
+            policy_id_hex: HOSKY_POLICY.into(),
+            convert_to_ascii: None,
+        });
+
+        assert_eq!(commands.len(), 157);
+        assert!(matches!(&commands[0], CRDTCommand::AnyWriteWins(k, _) if k == "asset.484f534b59"));
+    }
+}
@@ -0,0 +1,106 @@
+use pallas::crypto::hash::Hash;
+use pallas::ledger::traverse::MultiEraBlock;
+use serde::Deserialize;
+
+use crate::crosscut;
+use crate::framework::model::{self, CRDTCommand};
+use crate::framework::*;
+
+use super::macros::filter_matches;
+use super::{ReducerConfigTrait, ReducerTrait};
+
+#[derive(Deserialize)]
+pub struct Config {
+    pub key_prefix: Option<String>,
+    pub filter: Option<crosscut::filters::Predicate>,
+}
+
+pub struct Reducer {
+    config: Config,
+    policy: crosscut::policies::RuntimePolicy,
+}
+
+impl Reducer {
+    fn send(
+        &mut self,
+        slot: u64,
+        address: &str,
+        tx_hash: Hash<32>,
+        output_idx: usize,
+        output: &mut Vec<CRDTCommand>,
+    ) {
+        let crdt = CRDTCommand::last_write_wins(
+            self.config.key_prefix.as_deref(),
+            &format!("{}#{}", tx_hash, output_idx),
+            address.to_string(),
+            slot,
+        );
+
+        output.push(crdt);
+    }
+}
+
+#[async_trait::async_trait]
+impl ReducerTrait for Reducer {
+    async fn reduce_block<'b>(
+        &mut self,
+        block: &'b MultiEraBlock<'b>,
+        ctx: &model::BlockContext,
+    ) -> Result<Vec<CRDTCommand>, Error> {
+        let mut commands = Vec::new();
+        let slot = block.slot();
+
+        for tx in block.txs() {
+            if filter_matches!(self, block, &tx, ctx) {
+                let tx_hash = tx.hash();
+
+                for (output_idx, tx_out) in tx.outputs().iter().enumerate() {
+                    let address = tx_out
+                        .address()
+                        .map(|x| x.to_string())
+                        .map_err(Error::ledger)?;
+
+                    self.send(slot, &address, tx_hash, output_idx, &mut commands);
+                }
+            }
+        }
+
+        Ok(commands)
+    }
+}
+
+impl ReducerConfigTrait for Config {
+    fn plugin(self, ctx: &Context) -> Box<dyn ReducerTrait> {
+        let reducer = Reducer {
+            config: self,
+            policy: ctx.policy.clone(),
+        };
+
+        Box::new(reducer)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::reducers::builtin::tests::reduce_test_block;
+
+    #[test]
+    fn writes_address_of_each_output() {
+        let commands = reduce_test_block(Config {
+            key_prefix: Some("txo".into()),
+            filter: None,
+        });
+
+        assert_eq!(commands.len(), 343);
+
+        assert_eq!(
+            commands[1],
+            CRDTCommand::LastWriteWins(
+                "txo.c07822dffd150bd0afbdea6773806fc830df843b6f4b45a29ad16fd217d211ef#1".into(),
+                model::Value::String("addr1q8fukvydr8m5y3gztte3d4tnw0v5myvshusmu45phf20h395kqnygcykgjy42m29tksmwnd0js0z8p3swm5ntryhfu8sg7835c".into()),
+                46104248
+            )
+        );
+    }
+}
@@ -0,0 +1,137 @@
+use pallas::ledger::addresses::{Address, StakeAddress};
+use pallas::ledger::traverse::MultiEraBlock;
+use serde::Deserialize;
+
+use crate::framework::model::{self, CRDTCommand};
+use crate::framework::*;
+
+use super::{ReducerConfigTrait, ReducerTrait};
+
+#[derive(Deserialize)]
+pub struct Config {
+    pub key_prefix: Option<String>,
+    pub filter: Option<Vec<String>>,
+}
+
+pub struct Reducer {
+    config: Config,
+}
+
+fn any_address_to_stake_bech32(address: Address) -> Option<String> {
+    match address {
+        Address::Shelley(s) => match StakeAddress::try_from(s).ok() {
+            Some(x) => x.to_bech32().ok(),
+            _ => None,
+        },
+        Address::Byron(_) => None,
+        Address::Stake(_) => None,
+    }
+}
+
+impl Reducer {
+    fn process_address(&mut self, address: Address, output: &mut Vec<CRDTCommand>) {
+        // exit early since we don't care about Byron
+        if matches!(address, Address::Byron(_)) {
+            return;
+        }
+
+        let full_address = address.to_string();
+        let stake_address = any_address_to_stake_bech32(address);
+
+        let stake_address = match stake_address {
+            Some(x) => x,
+            None => return,
+        };
+
+        if let Some(stake_addresses) = &self.config.filter {
+            if stake_addresses.binary_search(&stake_address).is_err() {
+                return;
+            }
+        }
+
+        let crdt = CRDTCommand::set_add(
+            self.config.key_prefix.as_deref(),
+            &stake_address,
+            full_address,
+        );
+
+        output.push(crdt);
+    }
+}
+
+#[async_trait::async_trait]
+impl ReducerTrait for Reducer {
+    async fn reduce_block<'b>(
+        &mut self,
+        block: &'b MultiEraBlock<'b>,
+        _ctx: &model::BlockContext,
+    ) -> Result<Vec<CRDTCommand>, Error> {
+        let mut commands = Vec::new();
+
+        for tx in block.txs().into_iter() {
+            for (_, produced) in tx.produces() {
+                let address = produced.address().map_err(Error::ledger)?;
+                self.process_address(address, &mut commands);
+            }
+        }
+
+        Ok(commands)
+    }
+}
+
+impl ReducerConfigTrait for Config {
+    fn plugin(self, _ctx: &Context) -> Box<dyn ReducerTrait> {
+        let reducer = Reducer { config: self };
+        Box::new(reducer)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::reducers::builtin::tests::reduce_test_block;
+
+    const STAKE: &str = "stake1ux6tqfjyvztyfz24d4z4mgdhfkheg83rscc8d6f43jt57rc3ahxrp";
+
+    #[test]
+    fn stake_bech32() {
+        let addr = Address::from_bech32("addr1q86gknmykuldcngv0atyy56ex598p6m8f24nf9nmehmgpgfcmswqs6wnpls37lh7s3du977cxw67a9dpndnmafjs08asyqxe39").unwrap();
+        let stake_bech32 = any_address_to_stake_bech32(addr).unwrap();
+        assert_eq!(
+            stake_bech32,
+            "stake1uyudc8qgd8fslcgl0mlggk7zl0vr8d0wjksekea75eg8n7cw33m0s"
+        );
+    }
+
+    #[test]
+    fn adds_address_to_its_stake_set() {
+        let commands = reduce_test_block(Config {
+            key_prefix: None,
+            filter: None,
+        });
+
+        // every base address output, enterprise and byron ones have no stake
+        assert_eq!(commands.len(), 232);
+
+        assert_eq!(
+            commands[0],
+            CRDTCommand::SetAdd(STAKE.into(), "addr1q8fukvydr8m5y3gztte3d4tnw0v5myvshusmu45phf20h395kqnygcykgjy42m29tksmwnd0js0z8p3swm5ntryhfu8sg7835c".into())
+        );
+    }
+
+    #[test]
+    fn skips_stake_addresses_out_of_filter() {
+        let commands = reduce_test_block(Config {
+            key_prefix: Some("stake".into()),
+            filter: Some(vec![STAKE.into()]),
+        });
+
+        assert!(!commands.is_empty());
+
+        for command in commands {
+            assert!(
+                matches!(command, CRDTCommand::SetAdd(k, _) if k == format!("stake.{}", STAKE))
+            );
+        }
+    }
+}
@@ -0,0 +1,232 @@
+use pallas::ledger::traverse::wellknown::GenesisValues;
+use pallas::ledger::traverse::MultiEraOutput;
+use pallas::ledger::traverse::{MultiEraBlock, OutputRef};
+use serde::Deserialize;
+
+use crate::crosscut::{self, policies::AppliesPolicy};
+use crate::framework::model::{self, CRDTCommand};
+use crate::framework::*;
+use pallas::crypto::hash::Hash;
+
+use std::str::FromStr;
+
+use super::macros::filter_matches;
+use super::{ReducerConfigTrait, ReducerTrait};
+
+#[derive(Deserialize, Copy, Clone)]
+pub enum AggrType {
+    Epoch,
+}
+
+#[derive(Deserialize)]
+pub struct Config {
+    pub key_prefix: Option<String>,
+    pub filter: Option<crosscut::filters::Predicate>,
+    pub aggr_by: Option<AggrType>,
+
+    /// Policies to match
+    ///
+    /// If specified only those policy ids as hex will be taken into account, if
+    /// not all policy ids will be indexed.
+    pub policy_ids_hex: Option<Vec<String>>,
+}
+
+pub struct Reducer {
+    config: Config,
+    policy: crosscut::policies::RuntimePolicy,
+    genesis: GenesisValues,
+    policy_ids: Option<Vec<Hash<28>>>,
+}
+
+impl Reducer {
+    fn config_key(&self, subject: String, epoch_no: u64) -> String {
+        let def_key_prefix = "asset_holders_by_asset_id";
+
+        match &self.config.aggr_by {
+            Some(AggrType::Epoch) => match &self.config.key_prefix {
+                Some(prefix) => format!("{}.{}.{}", prefix, subject, epoch_no),
+                None => format!("{}.{}", def_key_prefix, subject),
+            },
+            None => match &self.config.key_prefix {
+                Some(prefix) => format!("{}.{}", prefix, subject),
+                None => format!("{}.{}", def_key_prefix, subject),
+            },
+        }
+    }
+
+    fn is_policy_id_accepted(&self, policy_id: &Hash<28>) -> bool {
+        match &self.policy_ids {
+            Some(pids) => pids.contains(policy_id),
+            None => true,
+        }
+    }
+
+    fn process_txo(
+        &self,
+        txo: &MultiEraOutput,
+        epoch_no: u64,
+        sign: i64,
+        output: &mut Vec<CRDTCommand>,
+    ) -> Result<(), Error> {
+        let address = txo
+            .address()
+            .map(|addr| addr.to_string())
+            .map_err(Error::ledger)?;
+
+        for policy_assets in txo.non_ada_assets() {
+            for asset in policy_assets.assets() {
+                let quantity = match asset.output_coin() {
+                    Some(x) => x,
+                    None => continue,
+                };
+
+                if !self.is_policy_id_accepted(asset.policy()) {
+                    continue;
+                }
+
+                let subject = format!("{}{}", asset.policy(), hex::encode(asset.name()));
+                let key = self.config_key(subject, epoch_no);
+                let delta = sign * quantity as i64;
+
+                let crdt = match sign {
+                    x if x < 0 => CRDTCommand::SortedSetRemove(key, address.clone(), delta),
+                    _ => CRDTCommand::SortedSetAdd(key, address.clone(), delta),
+                };
+
+                output.push(crdt);
+            }
+        }
+
+        Ok(())
+    }
+
+    fn process_consumed_txo(
+        &mut self,
+        ctx: &model::BlockContext,
+        input: &OutputRef,
+        epoch_no: u64,
+        output: &mut Vec<CRDTCommand>,
+    ) -> Result<(), Error> {
+        let utxo = ctx.find_utxo(input).apply_policy(&self.policy)?;
+
+        let utxo = match utxo {
+            Some(x) => x,
+            None => return Ok(()),
+        };
+
+        self.process_txo(&utxo, epoch_no, -1, output)
+    }
+
+    fn process_produced_txo(
+        &mut self,
+        tx_output: &MultiEraOutput,
+        epoch_no: u64,
+        output: &mut Vec<CRDTCommand>,
+    ) -> Result<(), Error> {
+        self.process_txo(tx_output, epoch_no, 1, output)
+    }
+}
+
+#[async_trait::async_trait]
+impl ReducerTrait for Reducer {
+    async fn reduce_block<'b>(
+        &mut self,
+        block: &'b MultiEraBlock<'b>,
+        ctx: &model::BlockContext,
+    ) -> Result<Vec<CRDTCommand>, Error> {
+        let mut commands = Vec::new();
+        let (epoch_no, _) = block.epoch(&self.genesis);
+
+        for tx in block.txs().into_iter() {
+            if filter_matches!(self, block, &tx, ctx) {
+                for consumed in tx.consumes().iter().map(|i| i.output_ref()) {
+                    self.process_consumed_txo(ctx, &consumed, epoch_no, &mut commands)?;
+                }
+
+                for (_, meo) in tx.produces() {
+                    self.process_produced_txo(&meo, epoch_no, &mut commands)?;
+                }
+            }
+        }
+
+        Ok(commands)
+    }
+}
+
+impl ReducerConfigTrait for Config {
+    fn plugin(self, ctx: &Context) -> Box<dyn ReducerTrait> {
+        let policy_ids: Option<Vec<Hash<28>>> = self.policy_ids_hex.as_ref().map(|pids| {
+            pids.iter()
+                .map(|pid| Hash::<28>::from_str(pid).expect("invalid policy_id"))
+                .collect()
+        });
+
+        let reducer = Reducer {
+            config: self,
+            policy: ctx.policy.clone(),
+            genesis: ctx.chain.clone().into(),
+            policy_ids,
+        };
+
+        Box::new(reducer)
+    }
+}
+
+// How to query
+// 127.0.0.1:6379> ZRANGEBYSCORE
+// "asset_holders_by_asset_id.
+// 5d9d887de76a2c9d057b3e5d34d5411f7f8dc4d54f0c06e8ed2eb4a9494e4459" 1 +inf
+// 1) "addr1q8lmu79hgm3sppz8dta3aftf0cwh2v2eja56wqvzqy4jj0zjt7qgvj7saxdxve35c4ehuxuam4czlz9fw6ls7zr4as9s609d7u"
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::reducers::builtin::tests::{reduce_test_block, RESOLVED_ADDRESS};
+
+    const DANA: &str = "c88bbd1848db5ea665b1fffbefba86e8dcd723b5085348e8a8d2260f44414e41";
+
+    #[test]
+    fn scores_holders_by_asset_quantity() {
+        let commands = reduce_test_block(Config {
+            key_prefix: None,
+            filter: None,
+            aggr_by: None,
+            policy_ids_hex: Some(vec![DANA[..56].into()]),
+        });
+
+        let key = format!("asset_holders_by_asset_id.{}", DANA);
+
+        assert_eq!(
+            commands,
+            vec![
+                CRDTCommand::SortedSetRemove(key.clone(), RESOLVED_ADDRESS.into(), -81500000),
+                CRDTCommand::SortedSetAdd(key.clone(), RESOLVED_ADDRESS.into(), 81500000),
+                CRDTCommand::SortedSetAdd(
+                    key,
+                    "DdzFFzCqrhsfg1RDGhsD32qughRyo3rZSYbCcbbC3K3HnZQgEg9HFWudMsB4oRceQTN94UBbzHT2NatS4xda9XQ8bqYsiYrkzQhdJg6Y".into(),
+                    1045684000
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn aggregates_holders_by_epoch() {
+        let commands = reduce_test_block(Config {
+            key_prefix: Some("holders".into()),
+            filter: None,
+            aggr_by: Some(AggrType::Epoch),
+            policy_ids_hex: Some(vec![DANA[..56].into()]),
+        });
+
+        assert_eq!(commands.len(), 3);
+
+        for command in commands {
+            assert!(matches!(
+                command,
+                CRDTCommand::SortedSetAdd(k, ..) | CRDTCommand::SortedSetRemove(k, ..)
+                    if k == format!("holders.{}.304", DANA)
+            ));
+        }
+    }
+}
@@ -0,0 +1,156 @@
+use pallas::ledger::traverse::MultiEraOutput;
+use pallas::ledger::traverse::{MultiEraBlock, OutputRef};
+use serde::Deserialize;
+
+use crate::crosscut::{self, policies::AppliesPolicy};
+use crate::framework::model::{self, CRDTCommand};
+use crate::framework::*;
+
+use super::macros::filter_matches;
+use super::{ReducerConfigTrait, ReducerTrait};
+
+#[derive(Deserialize)]
+pub struct Config {
+    pub key_prefix: Option<String>,
+    pub filter: Option<crosscut::filters::Predicate>,
+}
+
+pub struct Reducer {
+    config: Config,
+    policy: crosscut::policies::RuntimePolicy,
+}
+
+impl Reducer {
+    fn config_key(&self, address: &str) -> String {
+        match &self.config.key_prefix {
+            Some(prefix) => format!("{}.{}", prefix, address),
+            None => format!("{}.{}", "balance_by_address", address),
+        }
+    }
+
+    fn process_consumed_txo(
+        &mut self,
+        ctx: &model::BlockContext,
+        input: &OutputRef,
+        output: &mut Vec<CRDTCommand>,
+    ) -> Result<(), Error> {
+        let utxo = ctx.find_utxo(input).apply_policy(&self.policy)?;
+
+        let utxo = match utxo {
+            Some(x) => x,
+            None => return Ok(()),
+        };
+
+        let address = utxo
+            .address()
+            .map(|addr| addr.to_string())
+            .map_err(Error::ledger)?;
+
+        let key = self.config_key(&address);
+        let crdt = CRDTCommand::PNCounter(key, -(utxo.lovelace_amount() as i64));
+
+        output.push(crdt);
+
+        Ok(())
+    }
+
+    fn process_produced_txo(
+        &mut self,
+        tx_output: &MultiEraOutput,
+        output: &mut Vec<CRDTCommand>,
+    ) -> Result<(), Error> {
+        let address = tx_output
+            .address()
+            .map(|x| x.to_string())
+            .map_err(Error::ledger)?;
+
+        let key = self.config_key(&address);
+        let crdt = CRDTCommand::PNCounter(key, tx_output.lovelace_amount() as i64);
+
+        output.push(crdt);
+
+        Ok(())
+    }
+}
+
+#[async_trait::async_trait]
+impl ReducerTrait for Reducer {
+    async fn reduce_block<'b>(
+        &mut self,
+        block: &'b MultiEraBlock<'b>,
+        ctx: &model::BlockContext,
+    ) -> Result<Vec<CRDTCommand>, Error> {
+        let mut commands = Vec::new();
+
+        for tx in block.txs().into_iter() {
+            if filter_matches!(self, block, &tx, ctx) {
+                for consumed in tx.consumes().iter().map(|i| i.output_ref()) {
+                    self.process_consumed_txo(ctx, &consumed, &mut commands)?;
+                }
+
+                for (_, produced) in tx.produces() {
+                    self.process_produced_txo(&produced, &mut commands)?;
+                }
+            }
+        }
+
+        Ok(commands)
+    }
+}
+
+impl ReducerConfigTrait for Config {
+    fn plugin(self, ctx: &Context) -> Box<dyn ReducerTrait> {
+        let reducer = Reducer {
+            config: self,
+            policy: ctx.policy.clone(),
+        };
+
+        Box::new(reducer)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::reducers::builtin::tests::{reduce_test_block, RESOLVED_ADDRESS};
+
+    #[test]
+    fn counts_lovelace_in_and_out_of_addresses() {
+        let commands = reduce_test_block(Config {
+            key_prefix: None,
+            filter: None,
+        });
+
+        // the resolved input plus every output of the block
+        assert_eq!(commands.len(), 344);
+
+        assert_eq!(
+            &commands[..3],
+            &[
+                CRDTCommand::PNCounter(
+                    format!("balance_by_address.{}", RESOLVED_ADDRESS),
+                    -1344798
+                ),
+                CRDTCommand::PNCounter(
+                    "balance_by_address.addr1vy740r73x2w3du2xxt76cs4hdml4zw2c5h7tddcyf3jauys9tyns4"
+                        .into(),
+                    2000000
+                ),
+                CRDTCommand::PNCounter(
+                    "balance_by_address.addr1q8fukvydr8m5y3gztte3d4tnw0v5myvshusmu45phf20h395kqnygcykgjy42m29tksmwnd0js0z8p3swm5ntryhfu8sg7835c".into(),
+                    1783172
+                ),
+            ]
+        );
+
+        let produced: i64 = commands[1..]
+            .iter()
+            .map(|x| match x {
+                CRDTCommand::PNCounter(_, delta) => *delta,
+                x => panic!("unexpected command {:?}", x),
+            })
+            .sum();
+
+        assert_eq!(produced, 270294059828);
+    }
+}
@@ -0,0 +1,82 @@
+use pallas::ledger::traverse::MultiEraBlock;
+use serde::Deserialize;
+
+use crate::crosscut;
+use crate::framework::model::{self, CRDTCommand};
+use crate::framework::*;
+
+use super::macros::filter_matches_block;
+use super::{ReducerConfigTrait, ReducerTrait};
+
+#[derive(Deserialize)]
+pub struct Config {
+    pub key_prefix: Option<String>,
+    pub filter: Option<crosscut::filters::Predicate>,
+}
+
+pub struct Reducer {
+    config: Config,
+    policy: crosscut::policies::RuntimePolicy,
+}
+
+#[async_trait::async_trait]
+impl ReducerTrait for Reducer {
+    async fn reduce_block<'b>(
+        &mut self,
+        block: &'b MultiEraBlock<'b>,
+        ctx: &model::BlockContext,
+    ) -> Result<Vec<CRDTCommand>, Error> {
+        let mut commands = Vec::new();
+
+        if filter_matches_block!(self, block, ctx) {
+            let value = block.header().cbor().to_vec();
+
+            let crdt =
+                CRDTCommand::any_write_wins(self.config.key_prefix.as_deref(), block.hash(), value);
+
+            commands.push(crdt);
+        }
+
+        Ok(commands)
+    }
+}
+
+impl ReducerConfigTrait for Config {
+    fn plugin(self, ctx: &Context) -> Box<dyn ReducerTrait> {
+        let reducer = Reducer {
+            config: self,
+            policy: ctx.policy.clone(),
+        };
+
+        Box::new(reducer)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use pallas::crypto::hash::Hasher;
+
+    use super::*;
+    use crate::reducers::builtin::tests::reduce_test_block;
+
+    const BLOCK_HASH: &str = "9811acbdc17c620e2671e00c84ec6ebfe2c622b6a78766054a7d1b23e54e82e2";
+
+    #[test]
+    fn writes_header_cbor_by_block_hash() {
+        let commands = reduce_test_block(Config {
+            key_prefix: Some("header".into()),
+            filter: None,
+        });
+
+        assert_eq!(commands.len(), 1);
+
+        match &commands[0] {
+            CRDTCommand::AnyWriteWins(key, model::Value::Cbor(cbor)) => {
+                assert_eq!(key, &format!("header.{}", BLOCK_HASH));
+                assert_eq!(cbor.len(), 1006);
+                assert_eq!(Hasher::<256>::hash(cbor).to_string(), BLOCK_HASH);
+            }
+            x => panic!("unexpected command {:?}", x),
+        }
+    }
+}
@@ -9,7 +9,7 @@ use serde::Deserialize;
 use serde_json::json;
 
 use crate::framework::model::CRDTCommand;
-use crate::framework::{model, Error};
+use crate::framework::{model, Context, Error};
 
 use super::{ReducerConfigTrait, ReducerTrait};
 
@@ -20,7 +20,7 @@ pub struct Config {
     pub address_as_key: Option<bool>,
 }
 impl ReducerConfigTrait for Config {
-    fn plugin(self) -> Box<dyn ReducerTrait> {
+    fn plugin(self, _ctx: &Context) -> Box<dyn ReducerTrait> {
         let reducer = Reducer { config: self };
         Box::new(reducer)
     }
@@ -144,3 +144,65 @@ impl ReducerTrait for Reducer {
         Ok(commands)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::reducers::builtin::tests::{reduce_test_block, RESOLVED_ADDRESS, RESOLVED_INPUT};
+
+    const TX: &str = "32690a68fcde5505d51809ba0c2317f7015550e2c5744351714331b0cd78fadd";
+
+    #[test]
+    fn moves_full_utxos_of_filtered_addresses() {
+        let commands = reduce_test_block(Config {
+            filter: vec![RESOLVED_ADDRESS.into()],
+            prefix: None,
+            address_as_key: None,
+        });
+
+        let amount = json!([
+            { "unit": "lovelace", "quantity": "1344798" },
+            { "unit": "44414e41", "quantity": "81500000" },
+        ]);
+
+        let utxo = json!({ "address": RESOLVED_ADDRESS, "amount": amount });
+
+        match &commands[..] {
+            [CRDTCommand::SetRemove(removed, removed_utxo), CRDTCommand::SetAdd(added, added_utxo)] =>
+            {
+                assert_eq!(removed, RESOLVED_INPUT);
+                assert_eq!(added, &format!("{}#0", TX));
+
+                for value in [removed_utxo, added_utxo] {
+                    assert_eq!(
+                        serde_json::from_str::<serde_json::Value>(value).unwrap(),
+                        utxo
+                    );
+                }
+            }
+            x => panic!("unexpected commands {:?}", x),
+        }
+    }
+
+    #[test]
+    fn keys_full_utxos_by_address() {
+        let commands = reduce_test_block(Config {
+            filter: vec![RESOLVED_ADDRESS.into()],
+            prefix: None,
+            address_as_key: Some(true),
+        });
+
+        assert_eq!(commands.len(), 2);
+
+        match &commands[1] {
+            CRDTCommand::SetAdd(key, value) => {
+                assert_eq!(key, RESOLVED_ADDRESS);
+
+                let value: serde_json::Value = serde_json::from_str(value).unwrap();
+                assert_eq!(value["tx_hash"], TX);
+                assert_eq!(value["output_index"], 0);
+            }
+            x => panic!("unexpected command {:?}", x),
+        }
+    }
+}
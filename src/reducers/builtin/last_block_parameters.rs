@@ -0,0 +1,186 @@
+use pallas::ledger::traverse::wellknown::GenesisValues;
+use pallas::ledger::traverse::MultiEraBlock;
+use serde::Deserialize;
+
+use crate::framework::model::{self, CRDTCommand, Value};
+use crate::framework::*;
+
+use super::{ReducerConfigTrait, ReducerTrait};
+
+#[derive(Deserialize)]
+pub struct Config {
+    pub key_prefix: Option<String>,
+}
+
+pub struct Reducer {
+    config: Config,
+    genesis: GenesisValues,
+}
+
+impl Reducer {
+    fn current_epoch(&self, block: &MultiEraBlock, key: &str, output: &mut Vec<CRDTCommand>) {
+        let (epoch_no, _) = block.epoch(&self.genesis);
+
+        let crdt = CRDTCommand::AnyWriteWins(
+            format!("{}.{}", key, "epoch_no"),
+            Value::BigInt(epoch_no as i128),
+        );
+
+        output.push(crdt);
+    }
+
+    fn current_height(&self, block: &MultiEraBlock, key: &str, output: &mut Vec<CRDTCommand>) {
+        let crdt = CRDTCommand::AnyWriteWins(
+            format!("{}.{}", key, "height"),
+            Value::BigInt(block.number() as i128),
+        );
+
+        output.push(crdt);
+    }
+
+    fn current_slot(&self, block: &MultiEraBlock, key: &str, output: &mut Vec<CRDTCommand>) {
+        let crdt = CRDTCommand::AnyWriteWins(
+            format!("{}.{}", key, "slot_no"),
+            Value::BigInt(block.slot() as i128),
+        );
+
+        output.push(crdt);
+    }
+
+    fn current_block_hash(&self, block: &MultiEraBlock, key: &str, output: &mut Vec<CRDTCommand>) {
+        let crdt = CRDTCommand::AnyWriteWins(
+            format!("{}.{}", key, "block_hash"),
+            Value::String(block.hash().to_string()),
+        );
+
+        output.push(crdt);
+    }
+
+    fn current_block_era(&self, block: &MultiEraBlock, key: &str, output: &mut Vec<CRDTCommand>) {
+        let crdt = CRDTCommand::AnyWriteWins(
+            format!("{}.{}", key, "block_era"),
+            Value::String(block.era().to_string()),
+        );
+
+        output.push(crdt);
+    }
+
+    fn current_block_last_tx_hash(
+        &self,
+        block: &MultiEraBlock,
+        key: &str,
+        output: &mut Vec<CRDTCommand>,
+    ) {
+        let txs = block.txs();
+
+        if let (Some(first), Some(last)) = (txs.first(), txs.last()) {
+            let crdt = CRDTCommand::AnyWriteWins(
+                format!("{}.{}", key, "first_transaction_hash"),
+                Value::String(first.hash().to_string()),
+            );
+
+            output.push(crdt);
+
+            let crdt = CRDTCommand::AnyWriteWins(
+                format!("{}.{}", key, "last_transaction_hash"),
+                Value::String(last.hash().to_string()),
+            );
+
+            output.push(crdt);
+        }
+    }
+
+    fn current_block_last_tx_count(
+        &self,
+        block: &MultiEraBlock,
+        key: &str,
+        output: &mut Vec<CRDTCommand>,
+    ) {
+        let crdt = CRDTCommand::AnyWriteWins(
+            format!("{}.{}", key, "transactions_count"),
+            Value::BigInt(block.tx_count() as i128),
+        );
+
+        output.push(crdt);
+    }
+}
+
+#[async_trait::async_trait]
+impl ReducerTrait for Reducer {
+    async fn reduce_block<'b>(
+        &mut self,
+        block: &'b MultiEraBlock<'b>,
+        _ctx: &model::BlockContext,
+    ) -> Result<Vec<CRDTCommand>, Error> {
+        let mut commands = Vec::new();
+
+        let key = self
+            .config
+            .key_prefix
+            .clone()
+            .unwrap_or_else(|| "last_block".to_string());
+
+        self.current_epoch(block, &key, &mut commands);
+        self.current_height(block, &key, &mut commands);
+        self.current_slot(block, &key, &mut commands);
+        self.current_block_hash(block, &key, &mut commands);
+        self.current_block_era(block, &key, &mut commands);
+        self.current_block_last_tx_hash(block, &key, &mut commands);
+        self.current_block_last_tx_count(block, &key, &mut commands);
+
+        Ok(commands)
+    }
+}
+
+impl ReducerConfigTrait for Config {
+    fn plugin(self, ctx: &Context) -> Box<dyn ReducerTrait> {
+        let reducer = Reducer {
+            config: self,
+            genesis: ctx.chain.clone().into(),
+        };
+
+        Box::new(reducer)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::reducers::builtin::tests::reduce_test_block;
+
+    fn string(key: &str, value: &str) -> CRDTCommand {
+        CRDTCommand::AnyWriteWins(key.into(), Value::String(value.into()))
+    }
+
+    fn int(key: &str, value: i128) -> CRDTCommand {
+        CRDTCommand::AnyWriteWins(key.into(), Value::BigInt(value))
+    }
+
+    #[test]
+    fn writes_block_parameters() {
+        let commands = reduce_test_block(Config { key_prefix: None });
+
+        assert_eq!(
+            commands,
+            vec![
+                int("last_block.epoch_no", 304),
+                int("last_block.height", 6538269),
+                int("last_block.slot_no", 46104248),
+                string(
+                    "last_block.block_hash",
+                    "9811acbdc17c620e2671e00c84ec6ebfe2c622b6a78766054a7d1b23e54e82e2"
+                ),
+                string("last_block.block_era", "Alonzo"),
+                string(
+                    "last_block.first_transaction_hash",
+                    "c07822dffd150bd0afbdea6773806fc830df843b6f4b45a29ad16fd217d211ef"
+                ),
+                string(
+                    "last_block.last_transaction_hash",
+                    "f2d9065d12295f832db4b7ecc7278d86d308924b296f82f60b9a871ec3fc4418"
+                ),
+                int("last_block.transactions_count", 115),
+            ]
+        );
+    }
+}
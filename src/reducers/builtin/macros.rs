@@ -1,8 +1,7 @@
 macro_rules! filter_matches {
     ($reducer:ident, $block:expr, $tx:expr, $ctx:expr) => {
         match &$reducer.config.filter {
-            Some(x) => crosscut::filters::eval_predicate(x, $block, $tx, $ctx, &$reducer.policy)
-                .or_panic()?,
+            Some(x) => crosscut::filters::eval_predicate(x, $block, $tx, $ctx, &$reducer.policy)?,
             // if we don't have a filter, everything goes through
             None => true,
         }
@@ -18,8 +17,7 @@ macro_rules! filter_matches_block {
 
                 for tx in $block.txs().into_iter() {
                     ret |=
-                        crosscut::filters::eval_predicate(x, $block, &tx, $ctx, &$reducer.policy)
-                            .or_panic()?;
+                        crosscut::filters::eval_predicate(x, $block, &tx, $ctx, &$reducer.policy)?;
                 }
 
                 ret
@@ -5,18 +5,68 @@ use serde::Deserialize;
 use crate::framework::model::CRDTCommand;
 use crate::framework::*;
 
+mod macros;
+
+mod address_by_asset;
+mod address_by_txo;
+mod addresses_by_stake;
+mod asset_holders_by_asset_id;
+mod balance_by_address;
+mod block_header_by_hash;
 mod full_utxos_by_address;
+mod last_block_parameters;
+mod point_by_tx;
+mod pool_by_stake;
+mod supply_by_asset;
+mod tx_by_hash;
+mod tx_count_by_address;
+mod tx_count_by_native_token_policy_id;
+mod utxo_by_address;
+mod utxo_by_stake;
+mod utxos_by_asset;
 
 #[derive(Deserialize)]
 #[serde(tag = "type")]
 pub enum ReducerConfig {
     FullUtxosByAddress(full_utxos_by_address::Config),
+    UtxoByAddress(utxo_by_address::Config),
+    AddressByTxo(address_by_txo::Config),
+    BalanceByAddress(balance_by_address::Config),
+    TxByHash(tx_by_hash::Config),
+    TxCountByAddress(tx_count_by_address::Config),
+    BlockHeaderByHash(block_header_by_hash::Config),
+    AddressByAsset(address_by_asset::Config),
+    LastBlockParameters(last_block_parameters::Config),
+    TxCountByNativeTokenPolicyId(tx_count_by_native_token_policy_id::Config),
+    AssetHoldersByAssetId(asset_holders_by_asset_id::Config),
+    PointByTx(point_by_tx::Config),
+    PoolByStake(pool_by_stake::Config),
+    SupplyByAsset(supply_by_asset::Config),
+    AddressesByStake(addresses_by_stake::Config),
+    UtxoByStake(utxo_by_stake::Config),
+    UtxosByAsset(utxos_by_asset::Config),
 }
 
 impl ReducerConfig {
-    pub fn into_reducer(self) -> Box<dyn ReducerTrait> {
+    pub fn into_reducer(self, ctx: &Context) -> Box<dyn ReducerTrait> {
         match self {
-            ReducerConfig::FullUtxosByAddress(x) => x.plugin(),
+            ReducerConfig::FullUtxosByAddress(x) => x.plugin(ctx),
+            ReducerConfig::UtxoByAddress(x) => x.plugin(ctx),
+            ReducerConfig::AddressByTxo(x) => x.plugin(ctx),
+            ReducerConfig::BalanceByAddress(x) => x.plugin(ctx),
+            ReducerConfig::TxByHash(x) => x.plugin(ctx),
+            ReducerConfig::TxCountByAddress(x) => x.plugin(ctx),
+            ReducerConfig::BlockHeaderByHash(x) => x.plugin(ctx),
+            ReducerConfig::AddressByAsset(x) => x.plugin(ctx),
+            ReducerConfig::LastBlockParameters(x) => x.plugin(ctx),
+            ReducerConfig::TxCountByNativeTokenPolicyId(x) => x.plugin(ctx),
+            ReducerConfig::AssetHoldersByAssetId(x) => x.plugin(ctx),
+            ReducerConfig::PointByTx(x) => x.plugin(ctx),
+            ReducerConfig::PoolByStake(x) => x.plugin(ctx),
+            ReducerConfig::SupplyByAsset(x) => x.plugin(ctx),
+            ReducerConfig::AddressesByStake(x) => x.plugin(ctx),
+            ReducerConfig::UtxoByStake(x) => x.plugin(ctx),
+            ReducerConfig::UtxosByAsset(x) => x.plugin(ctx),
         }
     }
 }
@@ -27,12 +77,12 @@ pub struct Config {
 }
 
 impl Config {
-    pub fn bootstrapper(self, _ctx: &Context) -> Result<Stage, Error> {
+    pub fn bootstrapper(self, ctx: &Context) -> Result<Stage, Error> {
         let stage = Stage {
             reducers: self
                 .reducers
                 .into_iter()
-                .map(|x| x.into_reducer())
+                .map(|x| x.into_reducer(ctx))
                 .collect(),
             ..Default::default()
         };
@@ -56,39 +106,47 @@ pub struct Stage {
 #[derive(Default)]
 pub struct Worker;
 
-impl From<&Stage> for Worker {
-    fn from(_: &Stage) -> Self {
-        Self
+#[async_trait::async_trait(?Send)]
+impl gasket::framework::Worker<Stage> for Worker {
+    async fn bootstrap(_: &Stage) -> Result<Self, WorkerError> {
+        Ok(Self)
     }
-}
 
-gasket::impl_splitter!(|_worker: Worker, stage: Stage, unit: ChainEvent| => {
-    let record = unit.record();
-    if record.is_none() {
-        return Ok(());
+    async fn schedule(
+        &mut self,
+        stage: &mut Stage,
+    ) -> Result<WorkSchedule<ChainEvent>, WorkerError> {
+        recv_or_done(&mut stage.input).await
     }
 
-    let record = record.unwrap();
-
-    let commands = match record {
-        Record::EnrichedBlockPayload(block, ctx) => {
-            let block = MultiEraBlock::decode(block)
-            .map_err(Error::cbor)
-            .or_panic()?;
-
-            let mut commands: Vec<CRDTCommand> = Vec::new();
+    async fn execute(&mut self, unit: &ChainEvent, stage: &mut Stage) -> Result<(), WorkerError> {
+        let evt = match unit {
+            ChainEvent::Apply(point, record) => match record {
+                Record::EnrichedBlockPayload(block, ctx) => {
+                    let block = MultiEraBlock::decode(block)
+                        .map_err(Error::cbor)
+                        .or_panic()?;
+
+                    let mut commands: Vec<CRDTCommand> = Vec::new();
+
+                    for x in stage.reducers.iter_mut() {
+                        commands.append(&mut x.reduce_block(&block, ctx).await.or_retry()?)
+                    }
+
+                    ChainEvent::apply(point.clone(), Record::CRDTCommand(commands))
+                }
+                _ => todo!(),
+            },
+            ChainEvent::Reset(point) => ChainEvent::reset(point.clone()),
+        };
 
-            for x in stage.reducers.iter_mut() {
-                commands.append(&mut x.reduce_block(&block, ctx).await.or_retry()?)
-            }
+        stage.output.send(evt).await.or_panic()?;
 
-            Ok(commands)
-        },
-        _ => todo!(),
-    }?;
+        stage.ops_count.inc(1);
 
-    Some(ChainEvent::apply(unit.point().clone(), Record::CRDTCommand(commands)))
-});
+        Ok(())
+    }
+}
 
 #[async_trait::async_trait]
 pub trait ReducerTrait: Send + Sync {
@@ -100,5 +158,57 @@ pub trait ReducerTrait: Send + Sync {
 }
 
 trait ReducerConfigTrait {
-    fn plugin(self) -> Box<dyn ReducerTrait>;
+    fn plugin(self, ctx: &Context) -> Box<dyn ReducerTrait>;
+}
+
+#[cfg(test)]
+mod tests {
+    use pallas::ledger::traverse::Era;
+
+    use super::*;
+    use crate::crosscut::policies::{ErrorAction, RuntimePolicy};
+
+    /// Input of the first tx of the fixture block that the tests resolve
+    pub const RESOLVED_INPUT: &str =
+        "2b7477352414ffbd07435257f4f224dcc1fe68f220d8b02c6367e852994514e8#87";
+
+    /// Address holding the resolved input, along with some DANA tokens
+    pub const RESOLVED_ADDRESS: &str = "addr1q8yeypnvelmg4k6798zrnkpq87wr5t7fq9l0ns5tz8sp4tp46m44a24uzq0yf3cyaqgl783zxc04lsuhmz04g5c4023s4hv4yn";
+
+    fn test_context() -> Context {
+        Context {
+            chain: ChainConfig::Mainnet,
+            intersect: IntersectConfig::Tip,
+            cursor: Cursor::new(Default::default()),
+            finalize: None,
+            policy: RuntimePolicy {
+                missing_data: Some(ErrorAction::Skip),
+                ..Default::default()
+            },
+        }
+    }
+
+    /// Runs the reducer over `assets/test.block`, a mainnet Alonzo block
+    ///
+    /// Only the [`RESOLVED_INPUT`] is known to the block context, which maps
+    /// it to the first output of the tx 111 of the same block. The rest of the
+    /// inputs are skipped as missing data.
+    pub fn reduce_test_block(config: impl ReducerConfigTrait) -> Vec<CRDTCommand> {
+        let hex = std::fs::read_to_string("assets/test.block").unwrap();
+        let cbor = hex::decode(hex.trim()).unwrap();
+        let block = MultiEraBlock::decode(&cbor).unwrap();
+        let txs = block.txs();
+
+        let mut ctx = model::BlockContext::default();
+        let input = txs[0].consumes()[0].output_ref();
+        assert_eq!(input.to_string(), RESOLVED_INPUT);
+        ctx.import_ref_output(&input, Era::Alonzo, txs[111].outputs()[0].encode());
+
+        let mut reducer = config.plugin(&test_context());
+
+        tokio::runtime::Runtime::new()
+            .unwrap()
+            .block_on(reducer.reduce_block(&block, &ctx))
+            .unwrap()
+    }
 }
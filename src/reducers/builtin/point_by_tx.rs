@@ -0,0 +1,84 @@
+use pallas::crypto::hash::Hash;
+use pallas::ledger::traverse::MultiEraBlock;
+use serde::Deserialize;
+
+use crate::framework::model::{self, CRDTCommand};
+use crate::framework::*;
+
+use super::{ReducerConfigTrait, ReducerTrait};
+
+#[derive(Deserialize)]
+pub struct Config {
+    pub key_prefix: Option<String>,
+}
+
+pub struct Reducer {
+    config: Config,
+}
+
+impl Reducer {
+    fn send_set_add(
+        &mut self,
+        tx_hash: Hash<32>,
+        block_slot: u64,
+        block_hash: Hash<32>,
+        output: &mut Vec<CRDTCommand>,
+    ) {
+        let key = match &self.config.key_prefix {
+            Some(prefix) => format!("{}.{}", prefix, tx_hash),
+            None => format!("{}", tx_hash),
+        };
+
+        let member = format!("{},{}", block_slot, block_hash);
+        let crdt = CRDTCommand::GrowOnlySetAdd(key, member);
+
+        output.push(crdt);
+    }
+}
+
+#[async_trait::async_trait]
+impl ReducerTrait for Reducer {
+    async fn reduce_block<'b>(
+        &mut self,
+        block: &'b MultiEraBlock<'b>,
+        _ctx: &model::BlockContext,
+    ) -> Result<Vec<CRDTCommand>, Error> {
+        let mut commands = Vec::new();
+        let block_hash = block.hash();
+        let block_slot = block.slot();
+
+        for tx in &block.txs() {
+            self.send_set_add(tx.hash(), block_slot, block_hash, &mut commands);
+        }
+
+        Ok(commands)
+    }
+}
+
+impl ReducerConfigTrait for Config {
+    fn plugin(self, _ctx: &Context) -> Box<dyn ReducerTrait> {
+        let reducer = Reducer { config: self };
+        Box::new(reducer)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::reducers::builtin::tests::reduce_test_block;
+
+    #[test]
+    fn adds_block_point_of_each_tx() {
+        let commands = reduce_test_block(Config { key_prefix: None });
+
+        assert_eq!(commands.len(), 115);
+
+        assert_eq!(
+            commands[0],
+            CRDTCommand::GrowOnlySetAdd(
+                "c07822dffd150bd0afbdea6773806fc830df843b6f4b45a29ad16fd217d211ef".into(),
+                "46104248,9811acbdc17c620e2671e00c84ec6ebfe2c622b6a78766054a7d1b23e54e82e2".into()
+            )
+        );
+    }
+}
@@ -0,0 +1,115 @@
+use pallas::ledger::primitives::alonzo;
+use pallas::ledger::primitives::alonzo::{PoolKeyhash, StakeCredential};
+use pallas::ledger::traverse::MultiEraBlock;
+use serde::Deserialize;
+
+use crate::framework::model::{self, CRDTCommand};
+use crate::framework::*;
+
+use super::{ReducerConfigTrait, ReducerTrait};
+
+#[derive(Deserialize)]
+pub struct Config {
+    pub key_prefix: Option<String>,
+}
+
+pub struct Reducer {
+    config: Config,
+}
+
+impl Reducer {
+    fn send_key_write(
+        &mut self,
+        cred: &StakeCredential,
+        pool: &PoolKeyhash,
+        slot: u64,
+        output: &mut Vec<CRDTCommand>,
+    ) {
+        let key = match cred {
+            StakeCredential::AddrKeyhash(x) => x.to_string(),
+            StakeCredential::Scripthash(x) => x.to_string(),
+        };
+
+        let value = pool.to_string();
+
+        let crdt =
+            CRDTCommand::last_write_wins(self.config.key_prefix.as_deref(), &key, value, slot);
+
+        output.push(crdt);
+    }
+}
+
+#[async_trait::async_trait]
+impl ReducerTrait for Reducer {
+    async fn reduce_block<'b>(
+        &mut self,
+        block: &'b MultiEraBlock<'b>,
+        _ctx: &model::BlockContext,
+    ) -> Result<Vec<CRDTCommand>, Error> {
+        let mut commands = Vec::new();
+        let slot = block.slot();
+
+        for tx in block.txs() {
+            if tx.is_valid() {
+                for cert in tx.certs() {
+                    if let Some(alonzo::Certificate::StakeDelegation(cred, pool)) = cert.as_alonzo()
+                    {
+                        self.send_key_write(cred, pool, slot, &mut commands);
+                    }
+                }
+            }
+        }
+
+        Ok(commands)
+    }
+}
+
+impl ReducerConfigTrait for Config {
+    fn plugin(self, _ctx: &Context) -> Box<dyn ReducerTrait> {
+        let reducer = Reducer { config: self };
+        Box::new(reducer)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::reducers::builtin::tests::reduce_test_block;
+
+    fn delegation(stake: &str, pool: &str) -> CRDTCommand {
+        CRDTCommand::LastWriteWins(
+            format!("pool.{}", stake),
+            model::Value::String(pool.into()),
+            46104248,
+        )
+    }
+
+    #[test]
+    fn writes_pool_of_each_delegation() {
+        let commands = reduce_test_block(Config {
+            key_prefix: Some("pool".into()),
+        });
+
+        assert_eq!(
+            commands,
+            vec![
+                delegation(
+                    "534d793e7dbb6d30b3a4026bd13971f789bd964675db7fe710cc5e05",
+                    "fa58244068bef886049b3e8fbba8aa39f227ce78168efdf7629111c5"
+                ),
+                delegation(
+                    "6f1388e64fa7f667fbe6cdbdcb7025a862158035a2468bac41979783",
+                    "7f6c103302f96390d478a170fe80938b76fccd8a23490e3b6ddebcf7"
+                ),
+                delegation(
+                    "0637d34ee4282779ff2b793a8ec8f64128468014349813060c13fa54",
+                    "a89b86373838360143aba3911eaae54bf9420868965d48d447e4510a"
+                ),
+                delegation(
+                    "8037f763da312b59a394f64776f87aed8735be8729860b86f14a3e9d",
+                    "2d10d9ee5a86c5cedb75cacd033a2945cfd80315eb978858244cec1c"
+                ),
+            ]
+        );
+    }
+}
@@ -0,0 +1,127 @@
+use std::str::FromStr;
+
+use pallas::crypto::hash::Hash;
+use pallas::ledger::traverse::{MultiEraBlock, MultiEraPolicyAssets};
+use serde::Deserialize;
+
+use crate::framework::model::{self, CRDTCommand};
+use crate::framework::*;
+
+use super::{ReducerConfigTrait, ReducerTrait};
+
+#[derive(Deserialize)]
+pub struct Config {
+    pub key_prefix: Option<String>,
+    pub policy_ids_hex: Option<Vec<String>>,
+}
+
+pub struct Reducer {
+    config: Config,
+    policy_ids: Option<Vec<Hash<28>>>,
+}
+
+impl Reducer {
+    fn is_policy_id_accepted(&self, policy_id: &Hash<28>) -> bool {
+        match &self.policy_ids {
+            Some(pids) => pids.contains(policy_id),
+            None => true,
+        }
+    }
+
+    fn process_asset(
+        &mut self,
+        policy: &Hash<28>,
+        asset: &[u8],
+        qty: i64,
+        output: &mut Vec<CRDTCommand>,
+    ) {
+        if !self.is_policy_id_accepted(policy) {
+            return;
+        }
+
+        let asset_id = &format!("{}{}", policy, hex::encode(asset));
+
+        let key = match &self.config.key_prefix {
+            Some(prefix) => format!("{}.{}", prefix, asset_id),
+            None => format!("{}.{}", "supply_by_asset", asset_id),
+        };
+
+        let crdt = CRDTCommand::PNCounter(key, qty);
+
+        output.push(crdt);
+    }
+}
+
+#[async_trait::async_trait]
+impl ReducerTrait for Reducer {
+    async fn reduce_block<'b>(
+        &mut self,
+        block: &'b MultiEraBlock<'b>,
+        _ctx: &model::BlockContext,
+    ) -> Result<Vec<CRDTCommand>, Error> {
+        let mut commands = Vec::new();
+
+        for tx in block.txs().into_iter() {
+            for mint in tx.mints() {
+                if let MultiEraPolicyAssets::AlonzoCompatibleMint(policy, assets) = mint {
+                    for (name, amount) in assets.iter() {
+                        self.process_asset(policy, name, *amount, &mut commands);
+                    }
+                }
+            }
+        }
+
+        Ok(commands)
+    }
+}
+
+impl ReducerConfigTrait for Config {
+    fn plugin(self, _ctx: &Context) -> Box<dyn ReducerTrait> {
+        let policy_ids: Option<Vec<Hash<28>>> = self.policy_ids_hex.as_ref().map(|pids| {
+            pids.iter()
+                .map(|pid| Hash::<28>::from_str(pid).expect("invalid policy_id"))
+                .collect()
+        });
+
+        let reducer = Reducer {
+            config: self,
+            policy_ids,
+        };
+
+        Box::new(reducer)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::reducers::builtin::tests::reduce_test_block;
+
+    #[test]
+    fn counts_minted_assets() {
+        let commands = reduce_test_block(Config {
+            key_prefix: None,
+            policy_ids_hex: None,
+        });
+
+        assert_eq!(
+            commands,
+            vec![CRDTCommand::PNCounter(
+                "supply_by_asset.4c9f7d6c24ba8e2b12f3269ac38d706025e39a50a524afe6eaf79d955665676769656d6174653035393438".into(),
+                1
+            )]
+        );
+    }
+
+    #[test]
+    fn skips_mints_of_other_policies() {
+        let commands = reduce_test_block(Config {
+            key_prefix: None,
+            policy_ids_hex: Some(vec![
+                "a0028f350aaabe0545fdcb56b039bfb08e4bb4d8c4d7c3c7d481c235".into(),
+            ]),
+        });
+
+        assert!(commands.is_empty());
+    }
+}
@@ -0,0 +1,144 @@
+use pallas::ledger::traverse::wellknown::GenesisValues;
+use pallas::ledger::traverse::{MultiEraBlock, MultiEraTx};
+use serde::Deserialize;
+use serde_json::json;
+
+use crate::crosscut;
+use crate::framework::model::{self, CRDTCommand};
+use crate::framework::*;
+
+use super::macros::filter_matches;
+use super::{ReducerConfigTrait, ReducerTrait};
+
+#[derive(Deserialize, Copy, Clone, Default)]
+pub enum Projection {
+    #[default]
+    Cbor,
+    Json,
+}
+
+#[derive(Deserialize)]
+pub struct Config {
+    pub key_prefix: Option<String>,
+    pub filter: Option<crosscut::filters::Predicate>,
+    pub projection: Option<Projection>,
+}
+
+pub struct Reducer {
+    config: Config,
+    policy: crosscut::policies::RuntimePolicy,
+    genesis: GenesisValues,
+}
+
+impl Reducer {
+    fn send(&mut self, block: &MultiEraBlock, tx: &MultiEraTx, output: &mut Vec<CRDTCommand>) {
+        let key_prefix = self.config.key_prefix.as_deref();
+        let crdt = match self.config.projection.unwrap_or_default() {
+            Projection::Cbor => {
+                let cbor = tx.encode();
+                CRDTCommand::any_write_wins(key_prefix, tx.hash(), cbor)
+            }
+            Projection::Json => {
+                let cbor = tx.encode();
+                let slot = block.slot();
+                let ts = self.genesis.slot_to_wallclock(slot);
+                let json = json!({ "cbor": hex::encode(cbor), "slot": slot, "time": ts});
+                CRDTCommand::any_write_wins(key_prefix, tx.hash(), json.to_string())
+            }
+        };
+
+        output.push(crdt);
+    }
+}
+
+#[async_trait::async_trait]
+impl ReducerTrait for Reducer {
+    async fn reduce_block<'b>(
+        &mut self,
+        block: &'b MultiEraBlock<'b>,
+        ctx: &model::BlockContext,
+    ) -> Result<Vec<CRDTCommand>, Error> {
+        let mut commands = Vec::new();
+
+        for tx in &block.txs() {
+            if filter_matches!(self, block, tx, ctx) {
+                self.send(block, tx, &mut commands);
+            }
+        }
+
+        Ok(commands)
+    }
+}
+
+impl ReducerConfigTrait for Config {
+    fn plugin(self, ctx: &Context) -> Box<dyn ReducerTrait> {
+        let reducer = Reducer {
+            config: self,
+            policy: ctx.policy.clone(),
+            genesis: ctx.chain.clone().into(),
+        };
+
+        Box::new(reducer)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use pallas::ledger::traverse::Era;
+
+    use super::*;
+    use crate::reducers::builtin::tests::reduce_test_block;
+
+    const FIRST_TX: &str = "c07822dffd150bd0afbdea6773806fc830df843b6f4b45a29ad16fd217d211ef";
+
+    fn tx_hash(cbor: &[u8]) -> String {
+        MultiEraTx::decode(Era::Alonzo, cbor)
+            .unwrap()
+            .hash()
+            .to_string()
+    }
+
+    #[test]
+    fn writes_tx_cbor_by_hash() {
+        let commands = reduce_test_block(Config {
+            key_prefix: Some("tx".into()),
+            filter: None,
+            projection: None,
+        });
+
+        assert_eq!(commands.len(), 115);
+
+        match &commands[0] {
+            CRDTCommand::AnyWriteWins(key, model::Value::Cbor(cbor)) => {
+                assert_eq!(key, &format!("tx.{}", FIRST_TX));
+                assert_eq!(tx_hash(cbor), FIRST_TX);
+            }
+            x => panic!("unexpected command {:?}", x),
+        }
+    }
+
+    #[test]
+    fn writes_tx_json_with_wallclock() {
+        let commands = reduce_test_block(Config {
+            key_prefix: None,
+            filter: None,
+            projection: Some(Projection::Json),
+        });
+
+        assert_eq!(commands.len(), 115);
+
+        match &commands[0] {
+            CRDTCommand::AnyWriteWins(key, model::Value::String(json)) => {
+                assert_eq!(key, FIRST_TX);
+
+                let json: serde_json::Value = serde_json::from_str(json).unwrap();
+                assert_eq!(json["slot"], 46104248);
+                assert_eq!(json["time"], 1637670539);
+
+                let cbor = hex::decode(json["cbor"].as_str().unwrap()).unwrap();
+                assert_eq!(tx_hash(&cbor), FIRST_TX);
+            }
+            x => panic!("unexpected command {:?}", x),
+        }
+    }
+}
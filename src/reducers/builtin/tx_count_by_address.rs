@@ -0,0 +1,158 @@
+use pallas::ledger::traverse::MultiEraOutput;
+use pallas::ledger::traverse::{MultiEraBlock, OutputRef};
+use serde::Deserialize;
+use std::collections::HashSet;
+
+use crate::crosscut::{self, policies::AppliesPolicy};
+use crate::framework::model::{self, CRDTCommand};
+use crate::framework::*;
+
+use super::macros::filter_matches;
+use super::{ReducerConfigTrait, ReducerTrait};
+
+#[derive(Deserialize)]
+pub struct Config {
+    pub key_prefix: Option<String>,
+    pub filter: Option<crosscut::filters::Predicate>,
+}
+
+pub struct Reducer {
+    config: Config,
+    policy: crosscut::policies::RuntimePolicy,
+}
+
+impl Reducer {
+    fn increment(
+        &self,
+        address: String,
+        seen: &mut HashSet<String>,
+        output: &mut Vec<CRDTCommand>,
+    ) {
+        if seen.insert(address.clone()) {
+            let key = match &self.config.key_prefix {
+                Some(prefix) => format!("{}.{}", prefix, address),
+                None => format!("{}.{}", "txcount_by_address", address),
+            };
+
+            let crdt = CRDTCommand::PNCounter(key, 1);
+
+            output.push(crdt);
+        }
+    }
+
+    fn process_inbound_txo(
+        &mut self,
+        ctx: &model::BlockContext,
+        input: &OutputRef,
+        seen: &mut HashSet<String>,
+        output: &mut Vec<CRDTCommand>,
+    ) -> Result<(), Error> {
+        let utxo = ctx.find_utxo(input).apply_policy(&self.policy)?;
+
+        let utxo = match utxo {
+            Some(x) => x,
+            None => return Ok(()),
+        };
+
+        let address = utxo
+            .address()
+            .map(|addr| addr.to_string())
+            .map_err(Error::ledger)?;
+
+        self.increment(address, seen, output);
+
+        Ok(())
+    }
+
+    fn process_outbound_txo(
+        &mut self,
+        tx_output: &MultiEraOutput,
+        seen: &mut HashSet<String>,
+        output: &mut Vec<CRDTCommand>,
+    ) -> Result<(), Error> {
+        let address = tx_output
+            .address()
+            .map(|x| x.to_string())
+            .map_err(Error::ledger)?;
+
+        self.increment(address, seen, output);
+
+        Ok(())
+    }
+}
+
+#[async_trait::async_trait]
+impl ReducerTrait for Reducer {
+    async fn reduce_block<'b>(
+        &mut self,
+        block: &'b MultiEraBlock<'b>,
+        ctx: &model::BlockContext,
+    ) -> Result<Vec<CRDTCommand>, Error> {
+        let mut commands = Vec::new();
+
+        for tx in block.txs().into_iter() {
+            if filter_matches!(self, block, &tx, ctx) {
+                let mut seen = HashSet::new();
+
+                for input in tx.inputs().iter().map(|i| i.output_ref()) {
+                    self.process_inbound_txo(ctx, &input, &mut seen, &mut commands)?;
+                }
+
+                for tx_output in tx.outputs().iter() {
+                    self.process_outbound_txo(tx_output, &mut seen, &mut commands)?;
+                }
+            }
+        }
+
+        Ok(commands)
+    }
+}
+
+impl ReducerConfigTrait for Config {
+    fn plugin(self, ctx: &Context) -> Box<dyn ReducerTrait> {
+        let reducer = Reducer {
+            config: self,
+            policy: ctx.policy.clone(),
+        };
+
+        Box::new(reducer)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::reducers::builtin::tests::{reduce_test_block, RESOLVED_ADDRESS};
+
+    #[test]
+    fn counts_each_address_once_per_tx() {
+        let commands = reduce_test_block(Config {
+            key_prefix: None,
+            filter: None,
+        });
+
+        // the distinct addresses of each tx, along with the resolved input
+        assert_eq!(commands.len(), 343);
+
+        let key = |address: &str| format!("txcount_by_address.{}", address);
+
+        assert_eq!(
+            &commands[..3],
+            &[
+                CRDTCommand::PNCounter(key(RESOLVED_ADDRESS), 1),
+                CRDTCommand::PNCounter(
+                    key("addr1vy740r73x2w3du2xxt76cs4hdml4zw2c5h7tddcyf3jauys9tyns4"),
+                    1
+                ),
+                CRDTCommand::PNCounter(
+                    key("addr1q8fukvydr8m5y3gztte3d4tnw0v5myvshusmu45phf20h395kqnygcykgjy42m29tksmwnd0js0z8p3swm5ntryhfu8sg7835c"),
+                    1
+                ),
+            ]
+        );
+
+        assert!(commands
+            .iter()
+            .all(|x| matches!(x, CRDTCommand::PNCounter(_, 1))));
+    }
+}
@@ -0,0 +1,123 @@
+use serde::Deserialize;
+
+use pallas::ledger::traverse::wellknown::GenesisValues;
+use pallas::ledger::traverse::{Feature, MultiEraBlock};
+
+use crate::framework::model::{self, CRDTCommand};
+use crate::framework::*;
+
+use super::{ReducerConfigTrait, ReducerTrait};
+
+#[derive(Deserialize, Copy, Clone)]
+pub enum AggrType {
+    Epoch,
+}
+
+#[derive(Deserialize)]
+pub struct Config {
+    pub key_prefix: Option<String>,
+    pub aggr_by: Option<AggrType>,
+}
+
+pub struct Reducer {
+    config: Config,
+    genesis: GenesisValues,
+}
+
+impl Reducer {
+    fn config_key(&self, policy_id: String, epoch_no: u64) -> String {
+        let def_key_prefix = "transaction_count_by_native_token_policy";
+
+        match &self.config.aggr_by {
+            Some(AggrType::Epoch) => match &self.config.key_prefix {
+                Some(prefix) => format!("{}.{}.{}", prefix, policy_id, epoch_no),
+                None => format!("{}.{}", def_key_prefix, policy_id),
+            },
+            None => match &self.config.key_prefix {
+                Some(prefix) => format!("{}.{}", prefix, policy_id),
+                None => format!("{}.{}", def_key_prefix, policy_id),
+            },
+        }
+    }
+}
+
+#[async_trait::async_trait]
+impl ReducerTrait for Reducer {
+    async fn reduce_block<'b>(
+        &mut self,
+        block: &'b MultiEraBlock<'b>,
+        _ctx: &model::BlockContext,
+    ) -> Result<Vec<CRDTCommand>, Error> {
+        let mut commands = Vec::new();
+
+        if block.era().has_feature(Feature::MultiAssets) {
+            let (epoch_no, _) = block.epoch(&self.genesis);
+
+            for tx in block.txs() {
+                if tx.is_valid() {
+                    for mint in tx.mints() {
+                        let policy_id = hex::encode(mint.policy().as_slice());
+                        let number_of_minted_or_destroyed = mint.assets().len();
+
+                        let key = self.config_key(policy_id, epoch_no);
+
+                        let crdt =
+                            CRDTCommand::PNCounter(key, number_of_minted_or_destroyed as i64);
+
+                        commands.push(crdt);
+                    }
+                }
+            }
+        }
+
+        Ok(commands)
+    }
+}
+
+impl ReducerConfigTrait for Config {
+    fn plugin(self, ctx: &Context) -> Box<dyn ReducerTrait> {
+        let reducer = Reducer {
+            config: self,
+            genesis: ctx.chain.clone().into(),
+        };
+
+        Box::new(reducer)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::reducers::builtin::tests::reduce_test_block;
+
+    const POLICY: &str = "4c9f7d6c24ba8e2b12f3269ac38d706025e39a50a524afe6eaf79d95";
+
+    #[test]
+    fn counts_mints_by_policy() {
+        let commands = reduce_test_block(Config {
+            key_prefix: None,
+            aggr_by: None,
+        });
+
+        assert_eq!(
+            commands,
+            vec![CRDTCommand::PNCounter(
+                format!("transaction_count_by_native_token_policy.{}", POLICY),
+                1
+            )]
+        );
+    }
+
+    #[test]
+    fn counts_mints_by_policy_and_epoch() {
+        let commands = reduce_test_block(Config {
+            key_prefix: Some("mints".into()),
+            aggr_by: Some(AggrType::Epoch),
+        });
+
+        assert_eq!(
+            commands,
+            vec![CRDTCommand::PNCounter(format!("mints.{}.304", POLICY), 1)]
+        );
+    }
+}
@@ -0,0 +1,170 @@
+use pallas::ledger::traverse::MultiEraOutput;
+use pallas::ledger::traverse::{MultiEraBlock, MultiEraTx, OutputRef};
+use serde::Deserialize;
+
+use crate::crosscut::{self, policies::AppliesPolicy};
+use crate::framework::model::{self, CRDTCommand};
+use crate::framework::*;
+
+use super::{ReducerConfigTrait, ReducerTrait};
+
+#[derive(Deserialize)]
+pub struct Config {
+    pub key_prefix: Option<String>,
+    pub filter: Option<Vec<String>>,
+}
+
+pub struct Reducer {
+    config: Config,
+    policy: crosscut::policies::RuntimePolicy,
+}
+
+impl Reducer {
+    fn process_consumed_txo(
+        &mut self,
+        ctx: &model::BlockContext,
+        input: &OutputRef,
+        output: &mut Vec<CRDTCommand>,
+    ) -> Result<(), Error> {
+        let utxo = ctx.find_utxo(input).apply_policy(&self.policy)?;
+
+        let utxo = match utxo {
+            Some(x) => x,
+            None => return Ok(()),
+        };
+
+        let address = utxo
+            .address()
+            .map(|x| x.to_string())
+            .map_err(Error::ledger)?;
+
+        if let Some(addresses) = &self.config.filter {
+            if addresses.binary_search(&address).is_err() {
+                return Ok(());
+            }
+        }
+
+        let crdt = CRDTCommand::set_remove(
+            self.config.key_prefix.as_deref(),
+            &address,
+            input.to_string(),
+        );
+
+        output.push(crdt);
+
+        Ok(())
+    }
+
+    fn process_produced_txo(
+        &mut self,
+        tx: &MultiEraTx,
+        tx_output: &MultiEraOutput,
+        output_idx: usize,
+        output: &mut Vec<CRDTCommand>,
+    ) -> Result<(), Error> {
+        let tx_hash = tx.hash();
+        let address = tx_output
+            .address()
+            .map(|addr| addr.to_string())
+            .map_err(Error::ledger)?;
+
+        if let Some(addresses) = &self.config.filter {
+            if addresses.binary_search(&address).is_err() {
+                return Ok(());
+            }
+        }
+
+        let crdt = CRDTCommand::set_add(
+            self.config.key_prefix.as_deref(),
+            &address,
+            format!("{}#{}", tx_hash, output_idx),
+        );
+
+        output.push(crdt);
+
+        Ok(())
+    }
+}
+
+#[async_trait::async_trait]
+impl ReducerTrait for Reducer {
+    async fn reduce_block<'b>(
+        &mut self,
+        block: &'b MultiEraBlock<'b>,
+        ctx: &model::BlockContext,
+    ) -> Result<Vec<CRDTCommand>, Error> {
+        let mut commands = Vec::new();
+
+        for tx in block.txs().into_iter() {
+            for consumed in tx.consumes().iter().map(|i| i.output_ref()) {
+                self.process_consumed_txo(ctx, &consumed, &mut commands)?;
+            }
+
+            for (idx, produced) in tx.produces() {
+                self.process_produced_txo(&tx, &produced, idx, &mut commands)?;
+            }
+        }
+
+        Ok(commands)
+    }
+}
+
+impl ReducerConfigTrait for Config {
+    fn plugin(self, ctx: &Context) -> Box<dyn ReducerTrait> {
+        let reducer = Reducer {
+            config: self,
+            policy: ctx.policy.clone(),
+        };
+
+        Box::new(reducer)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::reducers::builtin::tests::{reduce_test_block, RESOLVED_ADDRESS, RESOLVED_INPUT};
+
+    #[test]
+    fn moves_utxos_between_address_sets() {
+        let commands = reduce_test_block(Config {
+            key_prefix: None,
+            filter: None,
+        });
+
+        // the resolved input plus every output of the block
+        assert_eq!(commands.len(), 344);
+
+        assert_eq!(
+            &commands[..2],
+            &[
+                CRDTCommand::SetRemove(RESOLVED_ADDRESS.into(), RESOLVED_INPUT.into()),
+                CRDTCommand::SetAdd(
+                    "addr1vy740r73x2w3du2xxt76cs4hdml4zw2c5h7tddcyf3jauys9tyns4".into(),
+                    "c07822dffd150bd0afbdea6773806fc830df843b6f4b45a29ad16fd217d211ef#0".into()
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn skips_addresses_out_of_filter() {
+        let commands = reduce_test_block(Config {
+            key_prefix: Some("utxo".into()),
+            filter: Some(vec![RESOLVED_ADDRESS.into()]),
+        });
+
+        let key = format!("utxo.{}", RESOLVED_ADDRESS);
+
+        assert_eq!(
+            commands,
+            vec![
+                CRDTCommand::SetRemove(key.clone(), RESOLVED_INPUT.into()),
+                CRDTCommand::SetAdd(
+                    key,
+                    "32690a68fcde5505d51809ba0c2317f7015550e2c5744351714331b0cd78fadd#0".into()
+                ),
+            ]
+        );
+    }
+}
@@ -0,0 +1,199 @@
+use pallas::ledger::addresses::{Address, StakeAddress};
+use pallas::ledger::traverse::MultiEraOutput;
+use pallas::ledger::traverse::{MultiEraBlock, MultiEraTx, OutputRef};
+use serde::Deserialize;
+
+use crate::crosscut::{self, policies::AppliesPolicy};
+use crate::framework::model::{self, CRDTCommand};
+use crate::framework::*;
+
+use super::{ReducerConfigTrait, ReducerTrait};
+
+#[derive(Deserialize)]
+pub struct Config {
+    pub key_prefix: Option<String>,
+    pub filter: Option<Vec<String>>,
+}
+
+pub struct Reducer {
+    config: Config,
+    policy: crosscut::policies::RuntimePolicy,
+}
+
+fn any_address_to_stake_bech32(address: Address) -> Option<String> {
+    match address {
+        Address::Shelley(s) => match StakeAddress::try_from(s).ok() {
+            Some(x) => x.to_bech32().ok(),
+            _ => None,
+        },
+        Address::Byron(_) => None,
+        Address::Stake(_) => None,
+    }
+}
+
+impl Reducer {
+    fn stake_address_for(&self, address: Address) -> Option<String> {
+        let stake_address = any_address_to_stake_bech32(address)?;
+
+        if let Some(stake_addresses) = &self.config.filter {
+            if stake_addresses.binary_search(&stake_address).is_err() {
+                return None;
+            }
+        }
+
+        Some(stake_address)
+    }
+
+    fn process_consumed_txo(
+        &mut self,
+        ctx: &model::BlockContext,
+        input: &OutputRef,
+        output: &mut Vec<CRDTCommand>,
+    ) -> Result<(), Error> {
+        let utxo = ctx.find_utxo(input).apply_policy(&self.policy)?;
+
+        let utxo = match utxo {
+            Some(x) => x,
+            None => return Ok(()),
+        };
+
+        let address = utxo.address().map_err(Error::ledger)?;
+
+        let stake_address = match self.stake_address_for(address) {
+            Some(x) => x,
+            None => return Ok(()),
+        };
+
+        let crdt = CRDTCommand::set_remove(
+            self.config.key_prefix.as_deref(),
+            &stake_address,
+            input.to_string(),
+        );
+
+        output.push(crdt);
+
+        Ok(())
+    }
+
+    fn process_produced_txo(
+        &mut self,
+        tx: &MultiEraTx,
+        tx_output: &MultiEraOutput,
+        output_idx: usize,
+        output: &mut Vec<CRDTCommand>,
+    ) -> Result<(), Error> {
+        let tx_hash = tx.hash();
+        let address = tx_output.address().map_err(Error::ledger)?;
+
+        let stake_address = match self.stake_address_for(address) {
+            Some(x) => x,
+            None => return Ok(()),
+        };
+
+        let crdt = CRDTCommand::set_add(
+            self.config.key_prefix.as_deref(),
+            &stake_address,
+            format!("{}#{}", tx_hash, output_idx),
+        );
+
+        output.push(crdt);
+
+        Ok(())
+    }
+}
+
+#[async_trait::async_trait]
+impl ReducerTrait for Reducer {
+    async fn reduce_block<'b>(
+        &mut self,
+        block: &'b MultiEraBlock<'b>,
+        ctx: &model::BlockContext,
+    ) -> Result<Vec<CRDTCommand>, Error> {
+        let mut commands = Vec::new();
+
+        for tx in block.txs().into_iter() {
+            for consumed in tx.consumes().iter().map(|i| i.output_ref()) {
+                self.process_consumed_txo(ctx, &consumed, &mut commands)?;
+            }
+
+            for (idx, produced) in tx.produces() {
+                self.process_produced_txo(&tx, &produced, idx, &mut commands)?;
+            }
+        }
+
+        Ok(commands)
+    }
+}
+
+impl ReducerConfigTrait for Config {
+    fn plugin(self, ctx: &Context) -> Box<dyn ReducerTrait> {
+        let reducer = Reducer {
+            config: self,
+            policy: ctx.policy.clone(),
+        };
+
+        Box::new(reducer)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::reducers::builtin::tests::{reduce_test_block, RESOLVED_INPUT};
+
+    /// Stake address of the resolved input
+    const STAKE: &str = "stake1uy6ad667427pq8jycuzwsy0lrc3rv86lcwta38652v2h4gcs2q7n7";
+
+    #[test]
+    fn stake_bech32() {
+        let addr = Address::from_bech32("addr1q86gknmykuldcngv0atyy56ex598p6m8f24nf9nmehmgpgfcmswqs6wnpls37lh7s3du977cxw67a9dpndnmafjs08asyqxe39").unwrap();
+        let stake_bech32 = any_address_to_stake_bech32(addr).unwrap();
+        assert_eq!(
+            stake_bech32,
+            "stake1uyudc8qgd8fslcgl0mlggk7zl0vr8d0wjksekea75eg8n7cw33m0s"
+        );
+    }
+
+    #[test]
+    fn moves_utxos_between_stake_sets() {
+        let commands = reduce_test_block(Config {
+            key_prefix: None,
+            filter: None,
+        });
+
+        // the resolved input plus every base address output
+        assert_eq!(commands.len(), 233);
+
+        assert_eq!(
+            &commands[..2],
+            &[
+                CRDTCommand::SetRemove(STAKE.into(), RESOLVED_INPUT.into()),
+                CRDTCommand::SetAdd(
+                    "stake1ux6tqfjyvztyfz24d4z4mgdhfkheg83rscc8d6f43jt57rc3ahxrp".into(),
+                    "c07822dffd150bd0afbdea6773806fc830df843b6f4b45a29ad16fd217d211ef#1".into()
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn skips_stake_addresses_out_of_filter() {
+        let commands = reduce_test_block(Config {
+            key_prefix: Some("utxo".into()),
+            filter: Some(vec![STAKE.into()]),
+        });
+
+        let key = format!("utxo.{}", STAKE);
+
+        assert_eq!(
+            commands,
+            vec![
+                CRDTCommand::SetRemove(key.clone(), RESOLVED_INPUT.into()),
+                CRDTCommand::SetAdd(
+                    key,
+                    "32690a68fcde5505d51809ba0c2317f7015550e2c5744351714331b0cd78fadd#0".into()
+                ),
+            ]
+        );
+    }
+}
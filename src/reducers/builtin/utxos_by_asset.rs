@@ -0,0 +1,161 @@
+use std::str::FromStr;
+
+use pallas::crypto::hash::Hash;
+use pallas::ledger::traverse::{MultiEraBlock, MultiEraOutput};
+use serde::Deserialize;
+
+use crate::crosscut;
+use crate::framework::model::{self, CRDTCommand};
+use crate::framework::*;
+
+use super::{ReducerConfigTrait, ReducerTrait};
+
+#[derive(Deserialize)]
+pub struct Config {
+    pub key_prefix: Option<String>,
+    pub policy_ids_hex: Option<Vec<String>>,
+}
+
+pub struct Reducer {
+    config: Config,
+    policy: crosscut::policies::RuntimePolicy,
+    policy_ids: Option<Vec<Hash<28>>>,
+}
+
+impl Reducer {
+    fn is_policy_id_accepted(&self, policy_id: &Hash<28>) -> bool {
+        match &self.policy_ids {
+            Some(pids) => pids.contains(policy_id),
+            None => true,
+        }
+    }
+
+    fn process_asset(
+        &mut self,
+        tx_hash: &Hash<32>,
+        txo_idx: u64,
+        policy: &Hash<28>,
+        asset: &[u8],
+        delta: i64,
+        output: &mut Vec<CRDTCommand>,
+    ) {
+        if !self.is_policy_id_accepted(policy) {
+            return;
+        }
+
+        let prefix = self.config.key_prefix.as_deref();
+        let key = &format!("{}{}", policy, hex::encode(asset));
+        let member = format!("{}#{}", tx_hash, txo_idx);
+
+        let crdt = match delta {
+            x if x < 0 => CRDTCommand::sorted_set_remove(prefix, key, member, delta),
+            _ => CRDTCommand::sorted_set_add(prefix, key, member, delta),
+        };
+
+        output.push(crdt);
+    }
+
+    fn process_txo(
+        &mut self,
+        tx_hash: &Hash<32>,
+        txo_idx: u64,
+        txo: &MultiEraOutput,
+        sign: i64,
+        output: &mut Vec<CRDTCommand>,
+    ) {
+        for policy_assets in txo.non_ada_assets() {
+            for asset in policy_assets.assets() {
+                if let Some(qty) = asset.output_coin() {
+                    self.process_asset(
+                        tx_hash,
+                        txo_idx,
+                        asset.policy(),
+                        asset.name(),
+                        sign * qty as i64,
+                        output,
+                    );
+                }
+            }
+        }
+    }
+}
+
+#[async_trait::async_trait]
+impl ReducerTrait for Reducer {
+    async fn reduce_block<'b>(
+        &mut self,
+        block: &'b MultiEraBlock<'b>,
+        ctx: &model::BlockContext,
+    ) -> Result<Vec<CRDTCommand>, Error> {
+        let mut commands = Vec::new();
+
+        for tx in block.txs().into_iter() {
+            for (tx_ref, tx_output) in ctx.find_consumed_txos(&tx, &self.policy)? {
+                self.process_txo(tx_ref.hash(), tx_ref.index(), &tx_output, -1, &mut commands);
+            }
+
+            for (idx, txo) in tx.produces() {
+                self.process_txo(&tx.hash(), idx as u64, &txo, 1, &mut commands);
+            }
+        }
+
+        Ok(commands)
+    }
+}
+
+impl ReducerConfigTrait for Config {
+    fn plugin(self, ctx: &Context) -> Box<dyn ReducerTrait> {
+        let policy_ids: Option<Vec<Hash<28>>> = self.policy_ids_hex.as_ref().map(|pids| {
+            pids.iter()
+                .map(|pid| Hash::<28>::from_str(pid).expect("invalid policy_id"))
+                .collect()
+        });
+
+        let reducer = Reducer {
+            config: self,
+            policy: ctx.policy.clone(),
+            policy_ids,
+        };
+
+        Box::new(reducer)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::reducers::builtin::tests::{reduce_test_block, RESOLVED_INPUT};
+
+    const DANA_POLICY: &str = "c88bbd1848db5ea665b1fffbefba86e8dcd723b5085348e8a8d2260f";
+
+    #[test]
+    fn scores_utxos_by_asset_quantity() {
+        let commands = reduce_test_block(Config {
+            key_prefix: None,
+            policy_ids_hex: None,
+        });
+
+        // the asset in the resolved input plus every asset of the outputs
+        assert_eq!(commands.len(), 267);
+    }
+
+    #[test]
+    fn skips_assets_of_other_policies() {
+        let commands = reduce_test_block(Config {
+            key_prefix: Some("utxos".into()),
+            policy_ids_hex: Some(vec![DANA_POLICY.into()]),
+        });
+
+        let key = format!("utxos.{}44414e41", DANA_POLICY);
+        let tx = "32690a68fcde5505d51809ba0c2317f7015550e2c5744351714331b0cd78fadd";
+
+        assert_eq!(
+            commands,
+            vec![
+                CRDTCommand::SortedSetRemove(key.clone(), RESOLVED_INPUT.into(), -81500000),
+                CRDTCommand::SortedSetAdd(key.clone(), format!("{}#0", tx), 81500000),
+                CRDTCommand::SortedSetAdd(key, format!("{}#1", tx), 1045684000),
+            ]
+        );
+    }
+}
@@ -1,30 +1,53 @@
 use std::path::PathBuf;
 
 use deno_runtime::deno_core::{self, op2, ModuleSpecifier, OpState};
-use deno_runtime::permissions::PermissionsContainer;
+use deno_runtime::permissions::{Permissions, PermissionsContainer, PermissionsOptions};
 use deno_runtime::worker::{MainWorker as DenoWorker, WorkerOptions};
 use gasket::framework::*;
-use pallas::interop::utxorpc::map_block;
-use pallas::ledger::traverse::MultiEraBlock;
+use pallas::interop::utxorpc::{map_block, map_tx_output};
+use pallas::ledger::traverse::{wellknown::GenesisValues, MultiEraBlock, MultiEraInput};
+use pallas::network::miniprotocols::Point;
 use serde::Deserialize;
 use serde_json::json;
-use tracing::trace;
+use tracing::{debug, error, trace};
 use utxorpc::proto::cardano::v1 as u5c;
 
-use crate::framework::model::CRDTCommand;
+use crate::crosscut::time::{is_far_from_tip, DEFAULT_TIP_DISTANCE};
+use crate::framework::model::{BlockContext, CRDTCommand};
 use crate::framework::*;
 
+/// Type definitions of the `scrolls:sdk` module and of the reducer exports
+pub const SDK_TYPES: &str = include_str!("./sdk.d.ts");
+
+const DEFAULT_BATCH_BLOCKS: usize = 100;
+
 const SYNC_CALL_SNIPPET: &str = r#"Deno[Deno.internal].core.ops.op_put_record(reduce(Deno[Deno.internal].core.ops.op_pop_record()));"#;
 
 const ASYNC_CALL_SNIPPET: &str = r#"reduce(Deno[Deno.internal].core.ops.op_pop_record()).then(x => Deno[Deno.internal].core.ops.op_put_record(x));"#;
 
-deno_core::extension!(deno_reducer, ops = [op_pop_record, op_put_record]);
+const SYNC_BATCH_SNIPPET: &str = r#"Deno[Deno.internal].core.ops.op_put_record(reduceBatch(Deno[Deno.internal].core.ops.op_pop_record()));"#;
+
+const ASYNC_BATCH_SNIPPET: &str = r#"reduceBatch(Deno[Deno.internal].core.ops.op_pop_record()).then(x => Deno[Deno.internal].core.ops.op_put_record(x));"#;
+
+const BATCH_CHECK_SNIPPET: &str = r#"if (typeof globalThis["reduceBatch"] !== "function") throw new Error("missing reduceBatch export");"#;
+
+const SYNC_ROLLBACK_SNIPPET: &str = r#"if (globalThis["rollback"]) Deno[Deno.internal].core.ops.op_put_record(rollback(Deno[Deno.internal].core.ops.op_pop_record()));"#;
+
+const ASYNC_ROLLBACK_SNIPPET: &str = r#"if (globalThis["rollback"]) rollback(Deno[Deno.internal].core.ops.op_pop_record()).then(x => Deno[Deno.internal].core.ops.op_put_record(x));"#;
+
+deno_core::extension!(
+    deno_reducer,
+    ops = [op_pop_record, op_put_record, op_get_params]
+);
+
+/// Record handed to the js runtime, kept apart from the returned value
+struct InputRecord(serde_json::Value);
 
 #[op2]
 #[serde]
 pub fn op_pop_record(state: &mut OpState) -> Result<serde_json::Value, deno_core::error::AnyError> {
-    let block: u5c::Block = state.take();
-    Ok(json!(block))
+    let InputRecord(record) = state.take();
+    Ok(record)
 }
 
 #[op2]
@@ -40,21 +63,102 @@ pub fn op_put_record(
     Ok(())
 }
 
+/// Parameters from the config, exposed to the module as `params`
+struct ReducerParams(serde_json::Value);
+
+#[op2]
+#[serde]
+pub fn op_get_params(state: &mut OpState) -> Result<serde_json::Value, deno_core::error::AnyError> {
+    Ok(state.borrow::<ReducerParams>().0.clone())
+}
+
+/// Resources the module is allowed to access, anything not listed is denied
+#[derive(Deserialize, Default, Clone)]
+pub struct PermissionsConfig {
+    allow_net: Option<Vec<String>>,
+    allow_read: Option<Vec<PathBuf>>,
+    allow_env: Option<Vec<String>>,
+}
+
+impl PermissionsConfig {
+    fn container(&self) -> Result<PermissionsContainer, Error> {
+        // an empty list would grant access to everything
+        fn allow_list<T: Clone>(list: &Option<Vec<T>>) -> Option<Vec<T>> {
+            list.clone().filter(|x| !x.is_empty())
+        }
+
+        let options = PermissionsOptions {
+            allow_net: allow_list(&self.allow_net),
+            allow_read: allow_list(&self.allow_read),
+            allow_env: allow_list(&self.allow_env),
+            prompt: false,
+            ..Default::default()
+        };
+
+        let permissions = Permissions::from_options(&options).map_err(Error::config)?;
+
+        Ok(PermissionsContainer::new(permissions))
+    }
+}
+
 #[derive(Deserialize)]
 pub struct Config {
     main_module: String,
     use_async: bool,
+
+    #[serde(default)]
+    permissions: PermissionsConfig,
+
+    params: Option<serde_json::Value>,
+
+    batch: Option<BatchConfig>,
+
+    /// Logs and skips malformed commands, and the output of calls that throw,
+    /// instead of failing the block
+    skip_malformed: Option<bool>,
+}
+
+/// Reduces several blocks with a single call to the `reduceBatch` export of
+/// the module, only while far from the tip of the chain
+#[derive(Deserialize)]
+pub struct BatchConfig {
+    /// Max number of blocks per call, defaults to 100
+    pub max_blocks: Option<usize>,
+
+    /// Distance in slots to the wall-clock tip under which blocks aren't
+    /// batched, defaults to 129600 (the stability window of mainnet)
+    pub tip_distance: Option<u64>,
 }
 
 impl Config {
-    pub fn bootstrapper(self, _ctx: &Context) -> Result<Stage, Error> {
+    pub fn bootstrapper(self, ctx: &Context) -> Result<Stage, Error> {
+        let batch_limits = self.batch.map(|x| BatchLimits {
+            max_blocks: x.max_blocks.unwrap_or(DEFAULT_BATCH_BLOCKS),
+            tip_distance: x.tip_distance.unwrap_or(DEFAULT_TIP_DISTANCE),
+            chain: ctx.chain.clone().into(),
+        });
+
         let stage = Stage {
             main_module: PathBuf::from(self.main_module),
+            permissions: self.permissions,
+            params: self.params.unwrap_or_else(|| json!({})),
             call_snippet: if self.use_async {
                 ASYNC_CALL_SNIPPET
             } else {
                 SYNC_CALL_SNIPPET
             },
+            rollback_snippet: if self.use_async {
+                ASYNC_ROLLBACK_SNIPPET
+            } else {
+                SYNC_ROLLBACK_SNIPPET
+            },
+            batch_snippet: if self.use_async {
+                ASYNC_BATCH_SNIPPET
+            } else {
+                SYNC_BATCH_SNIPPET
+            },
+            batch_limits,
+            skip_malformed: self.skip_malformed.unwrap_or_default(),
             ..Default::default()
         };
 
@@ -62,42 +166,172 @@ impl Config {
     }
 }
 
-async fn setup_deno(main_module: &PathBuf) -> Result<DenoWorker, WorkerError> {
+fn resolve_inputs(inputs: &[MultiEraInput], mapped: &mut [u5c::TxInput], ctx: &BlockContext) {
+    for (input, mapped) in inputs.iter().zip(mapped.iter_mut()) {
+        mapped.as_output = ctx
+            .find_utxo(&input.output_ref())
+            .ok()
+            .map(|x| map_tx_output(&x));
+    }
+}
+
+/// Maps the block into its utxorpc form, attaching the outputs that the
+/// enrich stage resolved to the inputs, collateral and reference inputs of
+/// each tx. Inputs missing from the context are left without an output.
+fn map_enriched_block(block: &MultiEraBlock, ctx: &BlockContext) -> u5c::Block {
+    let mut mapped = map_block(block);
+
+    let mapped_txs = mapped.body.iter_mut().flat_map(|x| x.tx.iter_mut());
+
+    for (tx, mapped_tx) in block.txs().iter().zip(mapped_txs) {
+        resolve_inputs(&tx.inputs(), &mut mapped_tx.inputs, ctx);
+        resolve_inputs(&tx.reference_inputs(), &mut mapped_tx.reference_inputs, ctx);
+
+        if let Some(collateral) = mapped_tx.collateral.as_mut() {
+            resolve_inputs(&tx.collateral(), &mut collateral.collateral, ctx);
+        }
+    }
+
+    mapped
+}
+
+fn point_to_json(point: &Point) -> serde_json::Value {
+    match point {
+        Point::Origin => json!(null),
+        Point::Specific(slot, hash) => json!({ "slot": slot, "hash": hex::encode(hash) }),
+    }
+}
+
+/// Parses the commands returned by the js runtime, a malformed output fails
+/// the block unless the config opts into skipping it
+fn parse_output(
+    out: serde_json::Value,
+    skip_malformed: bool,
+) -> Result<Option<Vec<CRDTCommand>>, Error> {
+    let items = match out {
+        serde_json::Value::Null => return Ok(None),
+        serde_json::Value::Array(x) => x,
+        x if skip_malformed => {
+            error!(output = %x, "reducer output isn't a list of commands, ignoring it");
+            return Ok(None);
+        }
+        x => {
+            let msg = format!("reducer output isn't a list of commands: {}", x);
+            return Err(Error::reducer(msg));
+        }
+    };
+
+    let mut commands = Vec::with_capacity(items.len());
+
+    for (index, item) in items.into_iter().enumerate() {
+        match CRDTCommand::deserialize(&item) {
+            Ok(x) => commands.push(x),
+            Err(err) if skip_malformed => {
+                error!(index, command = %item, %err, "skipping malformed command");
+            }
+            Err(err) => {
+                let msg = format!("malformed command at index {}: {} ({})", index, item, err);
+                return Err(Error::reducer(msg));
+            }
+        }
+    }
+
+    Ok(Some(commands))
+}
+
+async fn setup_deno(stage: &Stage) -> Result<DenoWorker, WorkerError> {
     let empty_module = deno_core::ModuleSpecifier::parse("data:text/javascript;base64,").unwrap();
 
+    let permissions = stage.permissions.container().or_panic()?;
+
     let mut deno = DenoWorker::bootstrap_from_options(
         empty_module,
-        PermissionsContainer::allow_all(),
+        permissions,
         WorkerOptions {
             extensions: vec![deno_reducer::init_ops()],
             ..Default::default()
         },
     );
 
-    let code = deno_core::FastString::from(std::fs::read_to_string(main_module).unwrap());
+    deno.js_runtime
+        .op_state()
+        .borrow_mut()
+        .put(ReducerParams(stage.params.clone()));
+
+    let sdk_code = deno_core::FastString::from_static(include_str!("./sdk.js"));
+
+    deno.js_runtime
+        .load_side_module(
+            &ModuleSpecifier::parse("scrolls:sdk").unwrap(),
+            Some(sdk_code),
+        )
+        .await
+        .map_err(|err| Error::reducer(format!("loading scrolls:sdk failed: {}", err)))
+        .or_panic()?;
+
+    let code = std::fs::read_to_string(&stage.main_module)
+        .map_err(Error::config)
+        .or_panic()?;
 
     deno.js_runtime
         .load_side_module(
             &ModuleSpecifier::parse("scrolls:reducer").unwrap(),
-            Some(code),
+            Some(deno_core::FastString::from(code)),
         )
         .await
-        .unwrap();
+        .map_err(|err| Error::reducer(format!("loading the main module failed: {}", err)))
+        .or_panic()?;
 
     let runtime_code = deno_core::FastString::from_static(include_str!("./runtime.js"));
 
     deno.execute_script("[scrolls:runtime.js]", runtime_code)
+        .map_err(|err| Error::reducer(format!("runtime.js failed: {}", err)))
         .or_panic()?;
-    deno.run_event_loop(false).await.unwrap();
+
+    // top-level awaits of the module settle here, a failure can't be skipped
+    deno.run_event_loop(false)
+        .await
+        .map_err(|err| Error::reducer(format!("module evaluation failed: {}", err)))
+        .or_panic()?;
+
+    // batches would otherwise only fail once the first one is flushed
+    if stage.batch_limits.is_some() {
+        let check = deno_core::FastString::from_static(BATCH_CHECK_SNIPPET);
+
+        deno.execute_script("<batch-check>", check)
+            .map_err(|_| {
+                Error::config("batch is configured but the module doesn't export reduceBatch")
+            })
+            .or_panic()?;
+    }
 
     Ok(deno)
 }
 
+/// Batching limits, resolved from the config
+struct BatchLimits {
+    max_blocks: usize,
+    tip_distance: u64,
+    chain: GenesisValues,
+}
+
+/// Either a chain event or the end of the pending batch
+pub enum Unit {
+    Event(ChainEvent),
+    Flush,
+}
+
 #[derive(Default, Stage)]
-#[stage(name = "reducer-deno", unit = "ChainEvent", worker = "Worker")]
+#[stage(name = "reducer-deno", unit = "Unit", worker = "Worker")]
 pub struct Stage {
     main_module: PathBuf,
+    permissions: PermissionsConfig,
+    params: serde_json::Value,
     call_snippet: &'static str,
+    rollback_snippet: &'static str,
+    batch_snippet: &'static str,
+    batch_limits: Option<BatchLimits>,
+    skip_malformed: bool,
 
     pub input: ReducerInputPort,
     pub output: ReducerOutputPort,
@@ -105,64 +339,293 @@ pub struct Stage {
     ops_count: gasket::metrics::Counter,
 }
 
+impl Stage {
+    /// Tells if the block can wait in the batch, which only happens far from
+    /// the tip of the chain
+    fn is_batched(&self, point: &Point) -> bool {
+        match &self.batch_limits {
+            Some(limits) => is_far_from_tip(&limits.chain, point, limits.tip_distance),
+            None => false,
+        }
+    }
+
+    fn is_full(&self, batch: &[(Point, serde_json::Value)]) -> bool {
+        match &self.batch_limits {
+            Some(limits) => batch.len() >= limits.max_blocks,
+            None => true,
+        }
+    }
+}
+
 pub struct Worker {
     runtime: DenoWorker,
+
+    /// Mapped blocks waiting for the next `reduceBatch` call
+    batch: Vec<(Point, serde_json::Value)>,
+
+    /// Latest block handed to the module, `None` until the first one
+    applied: Option<Point>,
 }
 
-#[async_trait::async_trait(?Send)]
-impl gasket::framework::Worker<Stage> for Worker {
-    async fn bootstrap(stage: &Stage) -> Result<Self, WorkerError> {
-        let runtime = setup_deno(&stage.main_module).await?;
-        Ok(Self { runtime })
+impl Worker {
+    /// Calls an export of the module with the record, a call that throws or
+    /// rejects fails the block unless the config opts into skipping it
+    async fn call_js(
+        &mut self,
+        stage: &Stage,
+        name: &str,
+        snippet: &'static str,
+        record: serde_json::Value,
+    ) -> Result<Option<serde_json::Value>, WorkerError> {
+        let deno = &mut self.runtime;
+
+        trace!(?record, "sending record to js runtime");
+        deno.js_runtime
+            .op_state()
+            .borrow_mut()
+            .put(InputRecord(record));
+
+        let script = deno_core::FastString::from_static(snippet);
+
+        let result = match deno.execute_script("<anon>", script) {
+            Ok(_) => deno.run_event_loop(false).await,
+            Err(err) => Err(err),
+        };
+
+        let state = deno.js_runtime.op_state();
+        let mut state = state.borrow_mut();
+
+        // the rollback hook is optional, so the record might not have been used
+        state.try_take::<InputRecord>();
+
+        let out: Option<serde_json::Value> = state.try_take();
+
+        match result {
+            Ok(_) => (),
+            Err(err) if stage.skip_malformed => {
+                error!(call = name, %err, "reducer call failed, ignoring its output");
+                return Ok(None);
+            }
+            Err(err) => {
+                let msg = format!("{} call failed: {}", name, err);
+                return Err(Error::reducer(msg)).or_panic();
+            }
+        }
+
+        trace!(?out, "received record from js runtime");
+        Ok(out)
     }
 
-    async fn schedule(
+    async fn send_block(
         &mut self,
         stage: &mut Stage,
-    ) -> Result<WorkSchedule<ChainEvent>, WorkerError> {
-        let msg = stage.input.recv().await.or_panic()?;
-        Ok(WorkSchedule::Unit(msg.payload))
+        point: &Point,
+        out: Option<Vec<CRDTCommand>>,
+    ) -> Result<(), WorkerError> {
+        let commands = match out {
+            Some(x) => x,
+            None => return Ok(()),
+        };
+
+        let evt = ChainEvent::apply(point.clone(), Record::CRDTCommand(commands));
+        stage.output.send(evt).await.or_retry()?;
+
+        Ok(())
     }
 
-    async fn execute(&mut self, unit: &ChainEvent, stage: &mut Stage) -> Result<(), WorkerError> {
-        let record = unit.record();
-        if record.is_none() {
+    /// Reduces the pending blocks with a single `reduceBatch` call, which
+    /// returns the commands of each block in the same order
+    async fn flush(&mut self, stage: &mut Stage) -> Result<(), WorkerError> {
+        if self.batch.is_empty() {
             return Ok(());
         }
 
-        let record = record.unwrap();
+        let (points, blocks): (Vec<_>, Vec<_>) =
+            std::mem::take(&mut self.batch).into_iter().unzip();
+
+        debug!(blocks = blocks.len(), "reducing batch of blocks");
+
+        let out = match self
+            .call_js(
+                stage,
+                "reduceBatch",
+                stage.batch_snippet,
+                serde_json::Value::Array(blocks),
+            )
+            .await?
+        {
+            Some(serde_json::Value::Array(x)) if x.len() == points.len() => x,
+            None => vec![serde_json::Value::Null; points.len()],
+            Some(x) => {
+                let msg = format!("reduceBatch didn't return one result per block: {}", x);
+                return Err(Error::message(msg)).or_panic();
+            }
+        };
 
-        match record {
-            Record::EnrichedBlockPayload(block, _) => {
+        for (point, out) in points.iter().zip(out) {
+            let out = parse_output(out, stage.skip_malformed).or_panic()?;
+            self.send_block(stage, point, out).await?;
+        }
+
+        Ok(())
+    }
+}
+
+fn event_unit(schedule: WorkSchedule<ChainEvent>) -> WorkSchedule<Unit> {
+    match schedule {
+        WorkSchedule::Unit(x) => WorkSchedule::Unit(Unit::Event(x)),
+        WorkSchedule::Idle => WorkSchedule::Idle,
+        WorkSchedule::Done => WorkSchedule::Done,
+    }
+}
+
+#[async_trait::async_trait(?Send)]
+impl gasket::framework::Worker<Stage> for Worker {
+    async fn bootstrap(stage: &Stage) -> Result<Self, WorkerError> {
+        let runtime = setup_deno(stage).await?;
+
+        Ok(Self {
+            runtime,
+            batch: vec![],
+            applied: None,
+        })
+    }
+
+    async fn schedule(&mut self, stage: &mut Stage) -> Result<WorkSchedule<Unit>, WorkerError> {
+        match recv_or_done(&mut stage.input).await? {
+            // the pending blocks are reduced before the stage finishes
+            WorkSchedule::Done if !self.batch.is_empty() => Ok(WorkSchedule::Unit(Unit::Flush)),
+            x => Ok(event_unit(x)),
+        }
+    }
+
+    async fn execute(&mut self, unit: &Unit, stage: &mut Stage) -> Result<(), WorkerError> {
+        let unit = match unit {
+            Unit::Flush => return self.flush(stage).await,
+            Unit::Event(x) => x,
+        };
+
+        match unit {
+            ChainEvent::Apply(point, Record::EnrichedBlockPayload(block, ctx)) => {
                 let block = MultiEraBlock::decode(block)
                     .map_err(Error::cbor)
                     .or_panic()?;
-                let block = map_block(&block);
+                let block = json!(map_enriched_block(&block, ctx));
 
-                let deno = &mut self.runtime;
+                self.applied = Some(point.clone());
 
-                trace!(?record, "sending record to js runtime");
-                deno.js_runtime.op_state().borrow_mut().put(block);
+                if stage.is_batched(point) {
+                    self.batch.push((point.clone(), block));
 
-                let script = deno_core::FastString::from_static(stage.call_snippet);
-                deno.execute_script("<anon>", script).or_panic()?;
-                deno.run_event_loop(false).await.unwrap();
+                    if stage.is_full(&self.batch) {
+                        self.flush(stage).await?;
+                    }
+                } else {
+                    self.flush(stage).await?;
 
-                let out: Option<serde_json::Value> =
-                    deno.js_runtime.op_state().borrow_mut().try_take();
+                    let out = self
+                        .call_js(stage, "reduce", stage.call_snippet, block)
+                        .await?;
 
-                trace!(?out, "received record from js runtime");
-                if let Some(crdt_json) = out {
-                    let commands: Vec<CRDTCommand> =
-                        serde_json::from_value(crdt_json).or_panic()?;
-                    let evt =
-                        ChainEvent::apply(unit.point().clone(), Record::CRDTCommand(commands));
-                    stage.output.send(evt).await.or_retry()?;
+                    let out =
+                        parse_output(out.unwrap_or_default(), stage.skip_malformed).or_panic()?;
+                    self.send_block(stage, point, out).await?;
                 }
             }
-            _ => todo!(),
+            ChainEvent::Reset(point) => {
+                self.flush(stage).await?;
+
+                // the reset at the intersection, and any other that doesn't
+                // drop a block handed to the module, has nothing to compensate
+                let dropped = self
+                    .applied
+                    .as_ref()
+                    .map(|x| x.slot_or_default() > point.slot_or_default())
+                    .unwrap_or_default();
+
+                let out = match dropped {
+                    true => {
+                        self.applied = Some(point.clone());
+
+                        let out = self
+                            .call_js(
+                                stage,
+                                "rollback",
+                                stage.rollback_snippet,
+                                point_to_json(point),
+                            )
+                            .await?;
+
+                        parse_output(out.unwrap_or_default(), stage.skip_malformed).or_panic()?
+                    }
+                    false => None,
+                };
+
+                // storages roll back their own journal, the compensating
+                // commands travel along so that both happen atomically
+                let evt = match out {
+                    Some(commands) if !commands.is_empty() => {
+                        ChainEvent::apply(point.clone(), Record::Compensation(commands))
+                    }
+                    _ => ChainEvent::reset(point.clone()),
+                };
+
+                stage.output.send(evt).await.or_retry()?;
+            }
+            _ => Err(Error::message("deno reducer only accepts enriched blocks")).or_panic()?,
         };
 
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_list_of_commands() {
+        let out = json!([{ "PNCounter": ["c", 1] }, { "SetAdd": ["s", "m"] }]);
+        let commands = parse_output(out, false).unwrap().unwrap();
+
+        assert_eq!(commands.len(), 2);
+        assert!(matches!(&commands[0], CRDTCommand::PNCounter(k, 1) if k == "c"));
+        assert!(matches!(&commands[1], CRDTCommand::SetAdd(k, m) if k == "s" && m == "m"));
+    }
+
+    #[test]
+    fn treats_null_as_no_output() {
+        assert!(parse_output(json!(null), false).unwrap().is_none());
+    }
+
+    #[test]
+    fn fails_on_malformed_command_with_its_index() {
+        let out = json!([{ "PNCounter": ["c", 1] }, { "Bogus": [] }]);
+        let err = parse_output(out, false).unwrap_err();
+
+        let msg = err.to_string();
+        assert!(
+            msg.starts_with("reducer error: malformed command at index 1"),
+            "{}",
+            msg
+        );
+    }
+
+    #[test]
+    fn fails_on_output_other_than_list() {
+        let err = parse_output(json!({ "PNCounter": ["c", 1] }), false).unwrap_err();
+
+        assert!(matches!(err, Error::ReducerError(_)));
+    }
+
+    #[test]
+    fn skips_malformed_commands_when_configured() {
+        let out = json!([{ "Bogus": [] }, { "PNCounter": ["c", 1] }, 42]);
+        let commands = parse_output(out, true).unwrap().unwrap();
+
+        assert_eq!(commands.len(), 1);
+        assert!(matches!(&commands[0], CRDTCommand::PNCounter(k, 1) if k == "c"));
+
+        assert!(parse_output(json!("nope"), true).unwrap().is_none());
+    }
+}
@@ -1,3 +1,7 @@
-import("scrolls:reducer").then(({ reduce }) => {
+globalThis["params"] = Deno[Deno.internal].core.ops.op_get_params();
+
+import("scrolls:reducer").then(({ reduce, reduceBatch, rollback }) => {
   globalThis["reduce"] = reduce;
+  globalThis["reduceBatch"] = reduceBatch;
+  globalThis["rollback"] = rollback;
 });
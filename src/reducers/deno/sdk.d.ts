@@ -0,0 +1,173 @@
+// Type definitions of the `scrolls:sdk` module and of the records handed to
+// the reducer exports.
+
+/** Bytes, base64-encoded */
+export type Bytes = string;
+
+/** 64-bit integers, encoded as strings */
+export type U64 = string;
+
+// Blocks follow the json form of the utxorpc cardano spec, where fields with
+// default values are left out.
+
+export interface Asset {
+  name?: Bytes;
+  outputCoin?: U64;
+  mintCoin?: U64;
+}
+
+export interface Multiasset {
+  policyId?: Bytes;
+  assets?: Asset[];
+}
+
+export interface TxOutput {
+  address?: Bytes;
+  coin?: U64;
+  assets?: Multiasset[];
+  datum?: unknown;
+  datumHash?: Bytes;
+  script?: unknown;
+}
+
+export interface Redeemer {
+  purpose?: string;
+  datum?: unknown;
+}
+
+export interface TxInput {
+  txHash?: Bytes;
+  outputIndex?: number;
+  /** The consumed output, when resolved by the enrich stage */
+  asOutput?: TxOutput;
+  redeemer?: Redeemer;
+}
+
+export interface Collateral {
+  collateral?: TxInput[];
+  collateralReturn?: TxOutput;
+  totalCollateral?: U64;
+}
+
+export interface Withdrawal {
+  rewardAccount?: Bytes;
+  coin?: U64;
+}
+
+export interface TxValidity {
+  start?: U64;
+  ttl?: U64;
+}
+
+export interface Metadata {
+  label?: U64;
+  value?: unknown;
+}
+
+export interface AuxData {
+  metadata?: Metadata[];
+  scripts?: unknown[];
+}
+
+export interface Tx {
+  inputs?: TxInput[];
+  outputs?: TxOutput[];
+  certificates?: unknown[];
+  withdrawals?: Withdrawal[];
+  mint?: Multiasset[];
+  referenceInputs?: TxInput[];
+  witnesses?: unknown;
+  collateral?: Collateral;
+  fee?: U64;
+  validity?: TxValidity;
+  successful?: boolean;
+  auxiliary?: AuxData;
+}
+
+export interface BlockHeader {
+  slot?: U64;
+  hash?: Bytes;
+}
+
+export interface Block {
+  header?: BlockHeader;
+  body?: { tx?: Tx[] };
+}
+
+/** Point of a chain reset, `null` for the origin */
+export type Point = { slot: number; hash: string } | null;
+
+export type Value =
+  | { String: string }
+  | { BigInt: number }
+  | { Cbor: number[] }
+  | { Json: unknown };
+
+/** Anything that converts into a value: strings, integers, bytes or json */
+export type ValueLike = Value | string | number | Uint8Array | object;
+
+export type CRDTCommand =
+  | { SetAdd: [string, string] }
+  | { SetRemove: [string, string] }
+  | { SortedSetAdd: [string, string, number] }
+  | { SortedSetRemove: [string, string, number] }
+  | { TwoPhaseSetAdd: [string, string] }
+  | { TwoPhaseSetRemove: [string, string] }
+  | { GrowOnlySetAdd: [string, string] }
+  | { LastWriteWins: [string, Value, number] }
+  | { AnyWriteWins: [string, Value] }
+  | { PNCounter: [string, number] }
+  | { HashCounter: [string, string, number] }
+  | { HashSetValue: [string, string, Value] }
+  | { HashUnsetKey: [string, string] };
+
+export type Reduce = (block: Block) => CRDTCommand[] | null | Promise<CRDTCommand[] | null>;
+
+export type ReduceBatch = (
+  blocks: Block[],
+) => (CRDTCommand[] | null)[] | Promise<(CRDTCommand[] | null)[]>;
+
+export type Rollback = (point: Point) => CRDTCommand[] | null | Promise<CRDTCommand[] | null>;
+
+export const params: Record<string, unknown>;
+
+export function prefixed(prefix: string | null | undefined, ...parts: string[]): string;
+
+export const Value: {
+  string(x: string): Value;
+  bigInt(x: number): Value;
+  cbor(x: string | Uint8Array | number[]): Value;
+  json(x: unknown): Value;
+};
+
+export function setAdd(key: string, member: string): CRDTCommand;
+export function setRemove(key: string, member: string): CRDTCommand;
+export function sortedSetAdd(key: string, member: string, delta: number): CRDTCommand;
+export function sortedSetRemove(key: string, member: string, delta: number): CRDTCommand;
+export function twoPhaseSetAdd(key: string, member: string): CRDTCommand;
+export function twoPhaseSetRemove(key: string, member: string): CRDTCommand;
+export function growOnlySetAdd(key: string, member: string): CRDTCommand;
+export function lastWriteWins(key: string, value: ValueLike, timestamp: number): CRDTCommand;
+export function anyWriteWins(key: string, value: ValueLike): CRDTCommand;
+export function pnCounter(key: string, delta: number): CRDTCommand;
+export function hashCounter(key: string, member: string, delta: number): CRDTCommand;
+export function hashSetValue(key: string, member: string, value: ValueLike): CRDTCommand;
+export function hashUnsetKey(key: string, member: string): CRDTCommand;
+
+export interface Builders {
+  setAdd: typeof setAdd;
+  setRemove: typeof setRemove;
+  sortedSetAdd: typeof sortedSetAdd;
+  sortedSetRemove: typeof sortedSetRemove;
+  twoPhaseSetAdd: typeof twoPhaseSetAdd;
+  twoPhaseSetRemove: typeof twoPhaseSetRemove;
+  growOnlySetAdd: typeof growOnlySetAdd;
+  lastWriteWins: typeof lastWriteWins;
+  anyWriteWins: typeof anyWriteWins;
+  pnCounter: typeof pnCounter;
+  hashCounter: typeof hashCounter;
+  hashSetValue: typeof hashSetValue;
+  hashUnsetKey: typeof hashUnsetKey;
+}
+
+export function withPrefix(prefix: string | null | undefined): Builders;
@@ -0,0 +1,187 @@
+// Builders for the CRDT commands understood by the storage stages, mirroring
+// the serde layout of `model::CRDTCommand`. Types are declared in `sdk.d.ts`.
+
+/** Parameters of the reducer, taken from the `params` table of the config */
+export const params = globalThis["params"] ?? {};
+
+function fail(command, message) {
+  throw new TypeError(`${command}: ${message}`);
+}
+
+function checkString(command, name, value) {
+  if (typeof value !== "string") {
+    fail(command, `${name} must be a string, got ${typeof value}`);
+  }
+}
+
+function checkInteger(command, name, value) {
+  if (!Number.isSafeInteger(value)) {
+    fail(command, `${name} must be a safe integer, got ${value}`);
+  }
+}
+
+function hexToBytes(hex) {
+  if (hex.length % 2 !== 0 || /[^0-9a-fA-F]/.test(hex)) {
+    fail("Cbor", "hex string is malformed");
+  }
+
+  const bytes = [];
+
+  for (let i = 0; i < hex.length; i += 2) {
+    bytes.push(parseInt(hex.slice(i, i + 2), 16));
+  }
+
+  return bytes;
+}
+
+/** Joins a prefix and the parts of a key, as the builtin reducers do */
+export function prefixed(prefix, ...parts) {
+  return [prefix, ...parts].filter((x) => x !== undefined && x !== null).join(".");
+}
+
+export const Value = {
+  string(x) {
+    checkString("String", "value", x);
+    return { String: x };
+  },
+
+  bigInt(x) {
+    checkInteger("BigInt", "value", x);
+    return { BigInt: x };
+  },
+
+  cbor(x) {
+    if (typeof x === "string") {
+      return { Cbor: hexToBytes(x) };
+    }
+
+    if (x instanceof Uint8Array || Array.isArray(x)) {
+      return { Cbor: Array.from(x) };
+    }
+
+    fail("Cbor", "value must be a hex string or bytes");
+  },
+
+  json(x) {
+    return { Json: x };
+  },
+};
+
+function toValue(command, x) {
+  if (x !== null && typeof x === "object") {
+    const keys = Object.keys(x);
+
+    if (keys.length === 1 && ["String", "BigInt", "Cbor", "Json"].includes(keys[0])) {
+      return x;
+    }
+  }
+
+  switch (typeof x) {
+    case "string":
+      return Value.string(x);
+    case "number":
+      return Value.bigInt(x);
+    case "undefined":
+      fail(command, "value is missing");
+  }
+
+  return x instanceof Uint8Array ? Value.cbor(x) : Value.json(x);
+}
+
+function keyMember(command, key, member) {
+  checkString(command, "key", key);
+  checkString(command, "member", member);
+  return { [command]: [key, member] };
+}
+
+function keyMemberDelta(command, key, member, delta) {
+  checkString(command, "key", key);
+  checkString(command, "member", member);
+  checkInteger(command, "delta", delta);
+  return { [command]: [key, member, delta] };
+}
+
+export function setAdd(key, member) {
+  return keyMember("SetAdd", key, member);
+}
+
+export function setRemove(key, member) {
+  return keyMember("SetRemove", key, member);
+}
+
+export function sortedSetAdd(key, member, delta) {
+  return keyMemberDelta("SortedSetAdd", key, member, delta);
+}
+
+export function sortedSetRemove(key, member, delta) {
+  return keyMemberDelta("SortedSetRemove", key, member, delta);
+}
+
+export function twoPhaseSetAdd(key, member) {
+  return keyMember("TwoPhaseSetAdd", key, member);
+}
+
+export function twoPhaseSetRemove(key, member) {
+  return keyMember("TwoPhaseSetRemove", key, member);
+}
+
+export function growOnlySetAdd(key, member) {
+  return keyMember("GrowOnlySetAdd", key, member);
+}
+
+export function lastWriteWins(key, value, timestamp) {
+  checkString("LastWriteWins", "key", key);
+  checkInteger("LastWriteWins", "timestamp", timestamp);
+
+  if (timestamp < 0) {
+    fail("LastWriteWins", "timestamp can't be negative");
+  }
+
+  return { LastWriteWins: [key, toValue("LastWriteWins", value), timestamp] };
+}
+
+export function anyWriteWins(key, value) {
+  checkString("AnyWriteWins", "key", key);
+  return { AnyWriteWins: [key, toValue("AnyWriteWins", value)] };
+}
+
+export function pnCounter(key, delta) {
+  checkString("PNCounter", "key", key);
+  checkInteger("PNCounter", "delta", delta);
+  return { PNCounter: [key, delta] };
+}
+
+export function hashCounter(key, member, delta) {
+  return keyMemberDelta("HashCounter", key, member, delta);
+}
+
+export function hashSetValue(key, member, value) {
+  checkString("HashSetValue", "key", key);
+  checkString("HashSetValue", "member", member);
+  return { HashSetValue: [key, member, toValue("HashSetValue", value)] };
+}
+
+export function hashUnsetKey(key, member) {
+  return keyMember("HashUnsetKey", key, member);
+}
+
+/** Same builders, with every key prefixed */
+export function withPrefix(prefix) {
+  const key = (x) => prefixed(prefix, x);
+
+  return {
+    setAdd: (k, m) => setAdd(key(k), m),
+    setRemove: (k, m) => setRemove(key(k), m),
+    sortedSetAdd: (k, m, d) => sortedSetAdd(key(k), m, d),
+    sortedSetRemove: (k, m, d) => sortedSetRemove(key(k), m, d),
+    twoPhaseSetAdd: (k, m) => twoPhaseSetAdd(key(k), m),
+    twoPhaseSetRemove: (k, m) => twoPhaseSetRemove(key(k), m),
+    growOnlySetAdd: (k, m) => growOnlySetAdd(key(k), m),
+    lastWriteWins: (k, v, t) => lastWriteWins(key(k), v, t),
+    anyWriteWins: (k, v) => anyWriteWins(key(k), v),
+    pnCounter: (k, d) => pnCounter(key(k), d),
+    hashCounter: (k, m, d) => hashCounter(key(k), m, d),
+    hashSetValue: (k, m, v) => hashSetValue(key(k), m, v),
+    hashUnsetKey: (k, m) => hashUnsetKey(key(k), m),
+  };
+}
@@ -11,11 +11,17 @@ pub mod builtin;
 #[cfg(feature = "deno")]
 pub mod deno;
 
+#[cfg(feature = "wasm")]
+pub mod wasm;
+
 pub enum Bootstrapper {
     BuiltIn(builtin::Stage),
 
     #[cfg(feature = "deno")]
     Deno(deno::Stage),
+
+    #[cfg(feature = "wasm")]
+    Wasm(wasm::Stage),
 }
 
 impl StageBootstrapper for Bootstrapper {
@@ -25,6 +31,9 @@ impl StageBootstrapper for Bootstrapper {
 
             #[cfg(feature = "deno")]
             Bootstrapper::Deno(p) => p.output.connect(adapter),
+
+            #[cfg(feature = "wasm")]
+            Bootstrapper::Wasm(p) => p.output.connect(adapter),
         }
     }
 
@@ -34,6 +43,9 @@ impl StageBootstrapper for Bootstrapper {
 
             #[cfg(feature = "deno")]
             Bootstrapper::Deno(p) => p.input.connect(adapter),
+
+            #[cfg(feature = "wasm")]
+            Bootstrapper::Wasm(p) => p.input.connect(adapter),
         }
     }
 
@@ -43,6 +55,9 @@ impl StageBootstrapper for Bootstrapper {
 
             #[cfg(feature = "deno")]
             Bootstrapper::Deno(s) => gasket::runtime::spawn_stage(s, policy),
+
+            #[cfg(feature = "wasm")]
+            Bootstrapper::Wasm(s) => gasket::runtime::spawn_stage(s, policy),
         }
     }
 }
@@ -54,6 +69,9 @@ pub enum Config {
 
     #[cfg(feature = "deno")]
     Deno(deno::Config),
+
+    #[cfg(feature = "wasm")]
+    Wasm(wasm::Config),
 }
 
 impl Config {
@@ -63,6 +81,9 @@ impl Config {
 
             #[cfg(feature = "deno")]
             Config::Deno(c) => Ok(Bootstrapper::Deno(c.bootstrapper(ctx)?)),
+
+            #[cfg(feature = "wasm")]
+            Config::Wasm(c) => Ok(Bootstrapper::Wasm(c.bootstrapper(ctx)?)),
         }
     }
 }
@@ -0,0 +1,350 @@
+use std::path::PathBuf;
+
+use gasket::framework::*;
+use pallas::network::miniprotocols::Point;
+use serde::Deserialize;
+use serde_json::json;
+use tracing::{debug, trace};
+use wasmtime::{
+    Engine, Instance, Memory, Module, Store, StoreLimits, StoreLimitsBuilder, TypedFunc,
+};
+
+use crate::framework::model::{BlockContext, CRDTCommand};
+use crate::framework::*;
+
+const DEFAULT_FUEL_LIMIT: u64 = 1_000_000_000;
+const DEFAULT_MEMORY_LIMIT_MB: usize = 64;
+
+/// Serializes a block and its context into the JSON document handed to the
+/// guest
+///
+/// The block is passed as hex-encoded CBOR and the resolved inputs are keyed
+/// by `<tx-hash>#<index>`, each with its era number and hex-encoded CBOR.
+fn encode_input(point: &Point, block: &[u8], ctx: &BlockContext) -> Vec<u8> {
+    let point = match point {
+        Point::Origin => json!(null),
+        Point::Specific(slot, hash) => json!({ "slot": slot, "hash": hex::encode(hash) }),
+    };
+
+    let utxos: serde_json::Map<_, _> = ctx
+        .iter_utxos()
+        .map(|(key, era, cbor)| {
+            let value = json!({ "era": u16::from(era), "cbor": hex::encode(cbor) });
+            (key.to_owned(), value)
+        })
+        .collect();
+
+    let input = json!({
+        "point": point,
+        "block": hex::encode(block),
+        "context": { "utxos": utxos },
+    });
+
+    input.to_string().into_bytes()
+}
+
+/// Keeps the root cause of a trap, such as running out of fuel, which the
+/// default formatting of the error leaves out
+fn trap(error: wasmtime::Error) -> Error {
+    Error::reducer(format!("{:#}", error))
+}
+
+/// Handles of a module instance that follows the reducer ABI
+///
+/// The module exports its `memory`, an `alloc(len) -> ptr` function used by
+/// the host to copy the input in, and a `reduce(ptr, len) -> i64` function
+/// that returns the location of a JSON array of commands packed as
+/// `ptr << 32 | len`. An optional `dealloc(ptr, len)` export lets the guest
+/// reclaim both buffers once the host is done with them.
+struct Guest {
+    store: Store<StoreLimits>,
+    memory: Memory,
+    alloc: TypedFunc<u32, u32>,
+    dealloc: Option<TypedFunc<(u32, u32), ()>>,
+    reduce: TypedFunc<(u32, u32), u64>,
+}
+
+impl Guest {
+    fn instantiate(engine: &Engine, module: &Module, memory_limit: usize) -> Result<Self, Error> {
+        let limits = StoreLimitsBuilder::new()
+            .memory_size(memory_limit)
+            .instances(1)
+            .build();
+
+        let mut store = Store::new(engine, limits);
+        store.limiter(|x| x);
+
+        let instance = Instance::new(&mut store, module, &[]).map_err(Error::reducer)?;
+
+        let memory = instance
+            .get_memory(&mut store, "memory")
+            .ok_or_else(|| Error::reducer("module doesn't export its memory"))?;
+
+        let alloc = instance
+            .get_typed_func(&mut store, "alloc")
+            .map_err(Error::reducer)?;
+
+        let dealloc = instance.get_typed_func(&mut store, "dealloc").ok();
+
+        let reduce = instance
+            .get_typed_func(&mut store, "reduce")
+            .map_err(Error::reducer)?;
+
+        Ok(Self {
+            store,
+            memory,
+            alloc,
+            dealloc,
+            reduce,
+        })
+    }
+
+    fn refuel(&mut self, fuel: u64) -> Result<(), Error> {
+        let remaining = self.store.consume_fuel(0).map_err(Error::reducer)?;
+
+        if fuel > remaining {
+            self.store
+                .add_fuel(fuel - remaining)
+                .map_err(Error::reducer)?;
+        }
+
+        Ok(())
+    }
+
+    fn release(&mut self, ptr: u32, len: u32) -> Result<(), Error> {
+        if let Some(dealloc) = &self.dealloc {
+            dealloc.call(&mut self.store, (ptr, len)).map_err(trap)?;
+        }
+
+        Ok(())
+    }
+
+    fn call(&mut self, input: &[u8], fuel: u64) -> Result<Vec<CRDTCommand>, Error> {
+        self.refuel(fuel)?;
+
+        let len = u32::try_from(input.len()).map_err(Error::reducer)?;
+
+        let ptr = self.alloc.call(&mut self.store, len).map_err(trap)?;
+
+        self.memory
+            .write(&mut self.store, ptr as usize, input)
+            .map_err(Error::reducer)?;
+
+        let packed = self
+            .reduce
+            .call(&mut self.store, (ptr, len))
+            .map_err(trap)?;
+
+        self.release(ptr, len)?;
+
+        let (out_ptr, out_len) = ((packed >> 32) as u32, packed as u32);
+
+        if out_len == 0 {
+            return Ok(vec![]);
+        }
+
+        let mut output = vec![0; out_len as usize];
+
+        self.memory
+            .read(&self.store, out_ptr as usize, &mut output)
+            .map_err(Error::reducer)?;
+
+        self.release(out_ptr, out_len)?;
+
+        serde_json::from_slice(&output).map_err(Error::reducer)
+    }
+}
+
+/// Runs a sandboxed WebAssembly module as reducer
+///
+/// Each block is handed to the module together with its resolved inputs and
+/// the module answers with the CRDT commands to apply. The fuel limit bounds
+/// the instructions executed per block and the memory limit bounds the
+/// linear memory of the instance.
+#[derive(Deserialize)]
+pub struct Config {
+    module: PathBuf,
+    fuel_limit: Option<u64>,
+    memory_limit_mb: Option<usize>,
+}
+
+impl Config {
+    pub fn bootstrapper(self, _ctx: &Context) -> Result<Stage, Error> {
+        let mut config = wasmtime::Config::new();
+        config.consume_fuel(true);
+
+        let engine = Engine::new(&config).map_err(Error::config)?;
+        let module = Module::from_file(&engine, &self.module).map_err(Error::config)?;
+
+        let stage = Stage {
+            engine,
+            module,
+            fuel_limit: self.fuel_limit.unwrap_or(DEFAULT_FUEL_LIMIT),
+            memory_limit: self.memory_limit_mb.unwrap_or(DEFAULT_MEMORY_LIMIT_MB) * 1024 * 1024,
+            input: Default::default(),
+            output: Default::default(),
+            ops_count: Default::default(),
+        };
+
+        Ok(stage)
+    }
+}
+
+#[derive(Stage)]
+#[stage(name = "reducer-wasm", unit = "ChainEvent", worker = "Worker")]
+pub struct Stage {
+    engine: Engine,
+    module: Module,
+    fuel_limit: u64,
+    memory_limit: usize,
+
+    pub input: ReducerInputPort,
+    pub output: ReducerOutputPort,
+
+    #[metric]
+    ops_count: gasket::metrics::Counter,
+}
+
+pub struct Worker {
+    guest: Guest,
+}
+
+#[async_trait::async_trait(?Send)]
+impl gasket::framework::Worker<Stage> for Worker {
+    async fn bootstrap(stage: &Stage) -> Result<Self, WorkerError> {
+        let guest =
+            Guest::instantiate(&stage.engine, &stage.module, stage.memory_limit).or_panic()?;
+
+        Ok(Self { guest })
+    }
+
+    async fn schedule(
+        &mut self,
+        stage: &mut Stage,
+    ) -> Result<WorkSchedule<ChainEvent>, WorkerError> {
+        recv_or_done(&mut stage.input).await
+    }
+
+    async fn execute(&mut self, unit: &ChainEvent, stage: &mut Stage) -> Result<(), WorkerError> {
+        let evt = match unit {
+            ChainEvent::Apply(point, record) => match record {
+                Record::EnrichedBlockPayload(block, ctx) => {
+                    let input = encode_input(point, block, ctx);
+
+                    trace!(?point, "sending block to wasm module");
+                    let commands = self.guest.call(&input, stage.fuel_limit).or_panic()?;
+                    debug!(
+                        ?point,
+                        commands = commands.len(),
+                        "wasm module reduced block"
+                    );
+
+                    ChainEvent::apply(point.clone(), Record::CRDTCommand(commands))
+                }
+                _ => Err(Error::message("wasm reducer only accepts enriched blocks")).or_panic()?,
+            },
+            ChainEvent::Reset(point) => ChainEvent::reset(point.clone()),
+        };
+
+        stage.output.send(evt).await.or_panic()?;
+
+        stage.ops_count.inc(1);
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use pallas::{
+        crypto::hash::Hash,
+        ledger::traverse::{Era, OutputRef},
+    };
+
+    use super::*;
+
+    /// Fixture module answering with a copy of its input, see its wat source
+    const ECHO_MODULE: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/assets/wasm/echo.wasm");
+
+    const PAGE_SIZE: usize = 65536;
+
+    fn echo_guest(memory_limit: usize) -> Guest {
+        let mut config = wasmtime::Config::new();
+        config.consume_fuel(true);
+
+        let engine = Engine::new(&config).unwrap();
+        let module = Module::from_file(&engine, ECHO_MODULE).unwrap();
+
+        Guest::instantiate(&engine, &module, memory_limit).unwrap()
+    }
+
+    #[test]
+    fn encodes_block_with_its_context() {
+        let mut ctx = BlockContext::default();
+        let input_ref = OutputRef::new(Hash::new([0xab; 32]), 1);
+        ctx.import_ref_output(&input_ref, Era::Alonzo, vec![0x82, 0x01]);
+
+        let point = Point::Specific(42, vec![0xcd; 32]);
+        let input = encode_input(&point, &[0xde, 0xad], &ctx);
+        let input: serde_json::Value = serde_json::from_slice(&input).unwrap();
+
+        assert_eq!(
+            input["point"],
+            json!({ "slot": 42, "hash": "cd".repeat(32) })
+        );
+        assert_eq!(input["block"], json!("dead"));
+
+        let utxo = &input["context"]["utxos"][format!("{}#1", "ab".repeat(32))];
+        assert_eq!(utxo, &json!({ "era": 5, "cbor": "8201" }));
+    }
+
+    #[test]
+    fn encodes_origin_as_null() {
+        let input = encode_input(&Point::Origin, &[], &BlockContext::default());
+        let input: serde_json::Value = serde_json::from_slice(&input).unwrap();
+
+        assert_eq!(input["point"], json!(null));
+        assert_eq!(input["context"]["utxos"], json!({}));
+    }
+
+    #[test]
+    fn decodes_commands_returned_by_module() {
+        let mut guest = echo_guest(PAGE_SIZE);
+
+        let commands = guest
+            .call(
+                br#"[{"PNCounter":["c.1",2]},{"SetAdd":["s.1","a"]}]"#,
+                10_000,
+            )
+            .unwrap();
+
+        assert!(matches!(
+            commands.as_slice(),
+            [CRDTCommand::PNCounter(k, 2), CRDTCommand::SetAdd(s, m)]
+                if k == "c.1" && s == "s.1" && m == "a"
+        ));
+    }
+
+    #[test]
+    fn releases_buffers_after_each_call() {
+        // input and output of a single call fit in the memory limit, but the
+        // buffers of two calls don't unless the first ones were released
+        let mut guest = echo_guest(2 * PAGE_SIZE);
+        let input = format!("[{}]", " ".repeat(PAGE_SIZE / 2));
+
+        for _ in 0..3 {
+            let commands = guest.call(input.as_bytes(), 1_000_000).unwrap();
+            assert!(commands.is_empty());
+        }
+    }
+
+    #[test]
+    fn fails_when_fuel_runs_out() {
+        let mut guest = echo_guest(PAGE_SIZE);
+
+        // the fixture spins forever on an empty input
+        let error = guest.call(&[], 10_000).unwrap_err();
+
+        assert!(error.to_string().contains("fuel"), "{}", error);
+    }
+}
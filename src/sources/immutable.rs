@@ -0,0 +1,441 @@
+use std::collections::VecDeque;
+use std::fs::File;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+
+use gasket::framework::*;
+use pallas::codec::minicbor;
+use pallas::ledger::traverse::MultiEraBlock;
+use pallas::network::miniprotocols::Point;
+use serde::Deserialize;
+use tracing::{debug, info};
+
+use crate::framework::cursor::Cursor;
+use crate::framework::errors::Error;
+use crate::framework::*;
+
+use super::Finished;
+
+#[derive(Stage)]
+#[stage(name = "source-immutable", unit = "ChainEvent", worker = "Worker")]
+pub struct Stage {
+    config: Config,
+
+    intersect: IntersectConfig,
+
+    cursor: Cursor,
+
+    finalize: Option<FinalizeConfig>,
+
+    block_count: u64,
+
+    pub finished: Finished,
+
+    pub output: SourceOutputPort,
+
+    #[metric]
+    ops_count: gasket::metrics::Counter,
+
+    #[metric]
+    chain_tip: gasket::metrics::Gauge,
+}
+
+/// Lists the chunk files of the ImmutableDB, sorted by chunk number
+fn list_chunks(dir: &Path) -> Result<VecDeque<PathBuf>, Error> {
+    let mut chunks = Vec::new();
+
+    for entry in std::fs::read_dir(dir).map_err(Error::source)? {
+        let path = entry.map_err(Error::source)?.path();
+
+        if path.extension().map(|x| x == "chunk").unwrap_or_default() {
+            let number = path
+                .file_stem()
+                .and_then(|x| x.to_str())
+                .and_then(|x| x.parse::<u64>().ok())
+                .ok_or_else(|| Error::source(format!("invalid chunk name {:?}", path)))?;
+
+            chunks.push((number, path));
+        }
+    }
+
+    chunks.sort_by_key(|(number, _)| *number);
+
+    Ok(chunks.into_iter().map(|(_, path)| path).collect())
+}
+
+/// Splits a chunk file into the CBOR of each of its blocks
+fn read_chunk(path: &Path) -> Result<VecDeque<Vec<u8>>, Error> {
+    debug!(?path, "reading chunk");
+
+    let bytes = std::fs::read(path).map_err(Error::source)?;
+    let mut decoder = minicbor::Decoder::new(&bytes);
+    let mut blocks = VecDeque::new();
+
+    while decoder.position() < bytes.len() {
+        let start = decoder.position();
+        decoder.skip().map_err(Error::cbor)?;
+        let end = decoder.position();
+
+        blocks.push_back(bytes[start..end].to_vec());
+    }
+
+    Ok(blocks)
+}
+
+fn block_point(cbor: &[u8]) -> Result<Point, Error> {
+    let block = MultiEraBlock::decode(cbor).map_err(Error::cbor)?;
+    Ok(Point::Specific(block.slot(), block.hash().to_vec()))
+}
+
+/// Slot of the first block of a chunk, `None` if the chunk is empty
+///
+/// Only the start of the file is read, enough to decode its first block.
+fn first_slot(path: &Path) -> Result<Option<u64>, Error> {
+    let mut file = File::open(path).map_err(Error::source)?;
+    let mut bytes = Vec::new();
+
+    loop {
+        let read = (&mut file)
+            .take(64 * 1024)
+            .read_to_end(&mut bytes)
+            .map_err(Error::source)?;
+
+        if bytes.is_empty() {
+            return Ok(None);
+        }
+
+        let mut decoder = minicbor::Decoder::new(&bytes);
+
+        match decoder.skip() {
+            Ok(()) => {
+                let point = block_point(&bytes[..decoder.position()])?;
+                return Ok(Some(point.slot_or_default()));
+            }
+            Err(x) if x.is_end_of_input() && read > 0 => continue,
+            Err(x) => return Err(Error::cbor(x)),
+        }
+    }
+}
+
+pub struct Worker {
+    chunks: VecDeque<PathBuf>,
+    pending: VecDeque<Vec<u8>>,
+    reset: Option<Point>,
+    finalized: bool,
+}
+
+impl Worker {
+    /// Slot of the first block at or after the given chunk, `None` if all the
+    /// remaining chunks are empty
+    fn chunk_start(&self, index: usize) -> Result<Option<u64>, Error> {
+        for chunk in self.chunks.iter().skip(index) {
+            if let Some(slot) = first_slot(chunk)? {
+                return Ok(Some(slot));
+            }
+        }
+
+        Ok(None)
+    }
+
+    /// Index of the only chunk that can hold a block at the given slot
+    ///
+    /// Chunks hold increasing slots, so this binary searches for the last
+    /// chunk starting at or before the slot, decoding a single block per step.
+    fn find_chunk(&self, slot: u64) -> Result<Option<usize>, Error> {
+        let (mut low, mut high) = (0, self.chunks.len());
+
+        while low < high {
+            let mid = low + (high - low) / 2;
+
+            match self.chunk_start(mid)? {
+                Some(start) if start <= slot => low = mid + 1,
+                _ => high = mid,
+            }
+        }
+
+        Ok(low.checked_sub(1))
+    }
+
+    /// Moves the reader right after the most recent of the given points
+    fn seek(&mut self, points: &[Point]) -> Result<Point, Error> {
+        let mut points = points.to_vec();
+        points.sort_by_key(|x| std::cmp::Reverse(x.slot_or_default()));
+
+        for point in points {
+            let slot = point.slot_or_default();
+
+            let index = match self.find_chunk(slot)? {
+                Some(x) => x,
+                None => continue,
+            };
+
+            let blocks = read_chunk(&self.chunks[index])?;
+
+            for (offset, cbor) in blocks.iter().enumerate() {
+                let candidate = block_point(cbor)?;
+
+                if candidate.slot_or_default() > slot {
+                    break;
+                }
+
+                if candidate == point {
+                    self.pending = blocks.iter().skip(offset + 1).cloned().collect();
+                    self.chunks.drain(..=index);
+
+                    return Ok(point);
+                }
+            }
+        }
+
+        Err(Error::IntersectNotFound)
+    }
+}
+
+#[async_trait::async_trait(?Send)]
+impl gasket::framework::Worker<Stage> for Worker {
+    async fn bootstrap(stage: &Stage) -> Result<Self, WorkerError> {
+        let dir = stage.config.path.join("immutable");
+        let chunks = list_chunks(&dir).or_panic()?;
+
+        info!(?dir, chunks = chunks.len(), "opened immutable db");
+
+        let mut worker = Self {
+            chunks,
+            pending: Default::default(),
+            reset: Some(Point::Origin),
+            finalized: false,
+        };
+
+        let points = match stage.cursor.is_empty() {
+            true => match &stage.intersect {
+                IntersectConfig::Origin => None,
+                IntersectConfig::Tip => {
+                    info!("intersecting tip, no blocks will be read");
+                    worker.chunks.clear();
+                    worker.reset = None;
+                    None
+                }
+                x => x.points(),
+            },
+            false => Some(stage.cursor.clone_state().into()),
+        };
+
+        if let Some(points) = points {
+            let point = worker.seek(&points).or_panic()?;
+            info!(?point, "intersected");
+            worker.reset = Some(point);
+        }
+
+        Ok(worker)
+    }
+
+    async fn schedule(
+        &mut self,
+        stage: &mut Stage,
+    ) -> Result<WorkSchedule<ChainEvent>, WorkerError> {
+        if self.finalized {
+            info!("source finalized, no more blocks will be read");
+            stage.finished.set();
+            return Ok(WorkSchedule::Done);
+        }
+
+        if let Some(point) = self.reset.take() {
+            return Ok(WorkSchedule::Unit(ChainEvent::Reset(point)));
+        }
+
+        loop {
+            if let Some(cbor) = self.pending.pop_front() {
+                let point = block_point(&cbor).or_panic()?;
+                let record = Record::RawBlockPayload(cbor);
+
+                return Ok(WorkSchedule::Unit(ChainEvent::Apply(point, record)));
+            }
+
+            match self.chunks.pop_front() {
+                Some(chunk) => self.pending = read_chunk(&chunk).or_panic()?,
+                None => {
+                    info!("reached the end of the immutable db");
+                    stage.finished.set();
+                    return Ok(WorkSchedule::Done);
+                }
+            }
+        }
+    }
+
+    async fn execute(&mut self, unit: &ChainEvent, stage: &mut Stage) -> Result<(), WorkerError> {
+        let point = unit.point().clone();
+
+        stage
+            .output
+            .send(gasket::messaging::Message::from(unit.clone()))
+            .await
+            .or_panic()?;
+
+        stage.ops_count.inc(1);
+        stage.chain_tip.set(point.slot_or_default() as i64);
+
+        if let ChainEvent::Apply(..) = unit {
+            stage.block_count += 1;
+
+            if let Some(finalize) = &stage.finalize {
+                if finalize.should_finalize(&point, stage.block_count) {
+                    info!(?point, "finalize condition reached");
+                    self.finalized = true;
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[derive(Deserialize)]
+pub struct Config {
+    /// Path of the node database, the one holding the `immutable` folder
+    path: PathBuf,
+}
+
+impl Config {
+    pub fn bootstrapper(self, ctx: &Context) -> Result<Stage, Error> {
+        let stage = Stage {
+            config: self,
+            intersect: ctx.intersect.clone(),
+            cursor: ctx.cursor.clone(),
+            finalize: ctx.finalize.clone(),
+            block_count: 0,
+            finished: Default::default(),
+            output: Default::default(),
+            ops_count: Default::default(),
+            chain_tip: Default::default(),
+        };
+
+        Ok(stage)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The test Alonzo block, tagged with its era as in chunk files, moved to
+    /// the given slot
+    fn block_at(slot: u32) -> Vec<u8> {
+        let hex = include_str!("../../assets/test.block");
+        let mut block = hex::decode(hex.trim()).unwrap();
+
+        // the slot is the second field of the header body, a 4-byte uint
+        block[11..15].copy_from_slice(&slot.to_be_bytes());
+
+        block
+    }
+
+    fn point_at(slot: u32) -> Point {
+        block_point(&block_at(slot)).unwrap()
+    }
+
+    /// Temporary directory of a test, removed once dropped
+    struct TestDir(PathBuf);
+
+    impl TestDir {
+        /// Named after the test and the process, so concurrent runs don't
+        /// clash
+        fn new(name: &str) -> Self {
+            let dir = std::env::temp_dir().join(format!(
+                "scrolls-immutable-{}-{}",
+                name,
+                std::process::id()
+            ));
+
+            let _ = std::fs::remove_dir_all(&dir);
+            std::fs::create_dir_all(&dir).unwrap();
+
+            Self(dir)
+        }
+    }
+
+    impl Drop for TestDir {
+        fn drop(&mut self) {
+            let _ = std::fs::remove_dir_all(&self.0);
+        }
+    }
+
+    /// Writes an ImmutableDB with one chunk per list of block slots, which
+    /// lives as long as the returned dir
+    fn worker(name: &str, chunks: &[&[u32]]) -> (TestDir, Worker) {
+        let dir = TestDir::new(name);
+
+        for (number, slots) in chunks.iter().enumerate() {
+            let bytes: Vec<u8> = slots.iter().flat_map(|x| block_at(*x)).collect();
+            std::fs::write(dir.0.join(format!("{:05}.chunk", number)), bytes).unwrap();
+        }
+
+        let worker = Worker {
+            chunks: list_chunks(&dir.0).unwrap(),
+            pending: Default::default(),
+            reset: None,
+            finalized: false,
+        };
+
+        (dir, worker)
+    }
+
+    fn pending_slots(worker: &Worker) -> Vec<u64> {
+        worker
+            .pending
+            .iter()
+            .map(|x| block_point(x).unwrap().slot_or_default())
+            .collect()
+    }
+
+    #[test]
+    fn reads_first_slot_of_chunks() {
+        let (_dir, worker) = worker("first-slot", &[&[100_000, 110_000], &[]]);
+
+        assert_eq!(first_slot(&worker.chunks[0]).unwrap(), Some(100_000));
+        assert_eq!(first_slot(&worker.chunks[1]).unwrap(), None);
+    }
+
+    #[test]
+    fn seeks_most_recent_point() {
+        let (_dir, mut worker) = worker(
+            "most-recent",
+            &[
+                &[100_000, 110_000],
+                &[],
+                &[200_000, 210_000, 220_000],
+                &[300_000],
+            ],
+        );
+
+        let points = [point_at(110_000), point_at(210_000), point_at(250_000)];
+        let point = worker.seek(&points).unwrap();
+
+        assert_eq!(point, point_at(210_000));
+        assert_eq!(pending_slots(&worker), vec![220_000]);
+        assert_eq!(worker.chunks.len(), 1);
+    }
+
+    #[test]
+    fn finds_chunks_around_empty_ones() {
+        let chunks: &[&[u32]] = &[&[], &[100_000], &[], &[], &[200_000], &[]];
+
+        let (_first_dir, mut first) = worker("around-empty-first", chunks);
+        assert_eq!(first.seek(&[point_at(100_000)]).unwrap(), point_at(100_000));
+        assert_eq!(first.chunks.len(), 4);
+
+        let (_last_dir, mut last) = worker("around-empty-last", chunks);
+        assert_eq!(last.seek(&[point_at(200_000)]).unwrap(), point_at(200_000));
+        assert!(last.pending.is_empty());
+        assert_eq!(last.chunks.len(), 1);
+    }
+
+    #[test]
+    fn fails_when_no_point_is_found() {
+        let (_dir, mut worker) = worker("not-found", &[&[100_000], &[200_000]]);
+
+        let result = worker.seek(&[Point::Specific(200_000, vec![0; 32]), point_at(50_000)]);
+
+        assert!(matches!(result, Err(Error::IntersectNotFound)));
+        assert_eq!(worker.chunks.len(), 2);
+    }
+}
@@ -1,14 +1,49 @@
 use gasket::{messaging::SendPort, runtime::Tether};
 use serde::Deserialize;
+use std::sync::{
+    atomic::{AtomicBool, Ordering},
+    Arc,
+};
 
 use crate::framework::{errors::Error, *};
 
+pub mod immutable;
 pub mod n2c;
 pub mod n2n;
+pub mod replay;
 
 pub enum Bootstrapper {
     N2N(n2n::Stage),
     N2C(n2c::Stage),
+    Immutable(immutable::Stage),
+    Replay(replay::Stage),
+}
+
+/// Raised by the source once it reaches its end cleanly, either the finalize
+/// config or the end of its blocks. It outlives the stage, which is dropped
+/// by the time the pipeline drains.
+#[derive(Clone, Default)]
+pub struct Finished(Arc<AtomicBool>);
+
+impl Finished {
+    pub fn set(&self) {
+        self.0.store(true, Ordering::SeqCst);
+    }
+
+    pub fn is_set(&self) -> bool {
+        self.0.load(Ordering::SeqCst)
+    }
+}
+
+impl Bootstrapper {
+    pub fn finished(&self) -> Finished {
+        match self {
+            Bootstrapper::N2N(s) => s.finished.clone(),
+            Bootstrapper::N2C(s) => s.finished.clone(),
+            Bootstrapper::Immutable(s) => s.finished.clone(),
+            Bootstrapper::Replay(s) => s.finished.clone(),
+        }
+    }
 }
 
 impl StageBootstrapper for Bootstrapper {
@@ -16,6 +51,8 @@ impl StageBootstrapper for Bootstrapper {
         match self {
             Bootstrapper::N2N(p) => p.output.connect(adapter),
             Bootstrapper::N2C(p) => p.output.connect(adapter),
+            Bootstrapper::Immutable(p) => p.output.connect(adapter),
+            Bootstrapper::Replay(p) => p.output.connect(adapter),
         }
     }
 
@@ -27,6 +64,8 @@ impl StageBootstrapper for Bootstrapper {
         match self {
             Bootstrapper::N2N(s) => gasket::runtime::spawn_stage(s, policy),
             Bootstrapper::N2C(s) => gasket::runtime::spawn_stage(s, policy),
+            Bootstrapper::Immutable(s) => gasket::runtime::spawn_stage(s, policy),
+            Bootstrapper::Replay(s) => gasket::runtime::spawn_stage(s, policy),
         }
     }
 }
@@ -38,6 +77,10 @@ pub enum Config {
 
     #[cfg(target_family = "unix")]
     N2C(n2c::Config),
+
+    Immutable(immutable::Config),
+
+    Replay(replay::Config),
 }
 
 impl Config {
@@ -45,6 +88,8 @@ impl Config {
         match self {
             Config::N2N(c) => Ok(Bootstrapper::N2N(c.bootstrapper(ctx)?)),
             Config::N2C(c) => Ok(Bootstrapper::N2C(c.bootstrapper(ctx)?)),
+            Config::Immutable(c) => Ok(Bootstrapper::Immutable(c.bootstrapper(ctx)?)),
+            Config::Replay(c) => Ok(Bootstrapper::Replay(c.bootstrapper(ctx)?)),
         }
     }
 }
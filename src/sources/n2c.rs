@@ -14,6 +14,8 @@ use crate::framework::cursor::Cursor;
 use crate::framework::errors::Error;
 use crate::framework::*;
 
+use super::Finished;
+
 #[derive(Stage)]
 #[stage(
     name = "source-n2c",
@@ -29,6 +31,12 @@ pub struct Stage {
 
     cursor: Cursor,
 
+    finalize: Option<FinalizeConfig>,
+
+    block_count: u64,
+
+    pub finished: Finished,
+
     pub output: SourceOutputPort,
 
     #[metric]
@@ -81,6 +89,7 @@ async fn intersect_from_cursor(peer: &mut NodeClient, cursor: &Cursor) -> Result
 }
 
 pub struct Worker {
+    finalized: bool,
     peer_session: NodeClient,
 }
 
@@ -98,15 +107,23 @@ impl Worker {
 
                 debug!(slot, %hash, "chain sync roll forward");
 
-                let evt = ChainEvent::apply(
-                    Point::Specific(slot, hash.to_vec()),
-                    Record::RawBlockPayload(cbor.to_vec()),
-                );
+                let point = Point::Specific(slot, hash.to_vec());
+
+                let evt = ChainEvent::apply(point.clone(), Record::RawBlockPayload(cbor.to_vec()));
 
                 stage.output.send(evt).await.or_panic()?;
 
                 stage.chain_tip.set(tip.0.slot_or_default() as i64);
 
+                stage.block_count += 1;
+
+                if let Some(finalize) = &stage.finalize {
+                    if finalize.should_finalize(&point, stage.block_count) {
+                        info!(slot, %hash, "finalize condition reached");
+                        self.finalized = true;
+                    }
+                }
+
                 Ok(())
             }
             NextResponse::RollBackward(point, tip) => {
@@ -146,15 +163,24 @@ impl gasket::framework::Worker<Stage> for Worker {
             intersect_from_cursor(&mut peer_session, &stage.cursor).await?;
         }
 
-        let worker = Self { peer_session };
+        let worker = Self {
+            finalized: false,
+            peer_session,
+        };
 
         Ok(worker)
     }
 
     async fn schedule(
         &mut self,
-        _stage: &mut Stage,
+        stage: &mut Stage,
     ) -> Result<WorkSchedule<NextResponse<BlockContent>>, WorkerError> {
+        if self.finalized {
+            info!("source finalized, no more blocks will be requested");
+            stage.finished.set();
+            return Ok(WorkSchedule::Done);
+        }
+
         let client = self.peer_session.chainsync();
 
         let next = match client.has_agency() {
@@ -198,6 +224,9 @@ impl Config {
             chain: ctx.chain.clone().into(),
             intersect: ctx.intersect.clone(),
             cursor: ctx.cursor.clone(),
+            finalize: ctx.finalize.clone(),
+            block_count: 0,
+            finished: Default::default(),
             output: Default::default(),
             ops_count: Default::default(),
             chain_tip: Default::default(),
@@ -1,21 +1,33 @@
+use std::sync::atomic::{AtomicUsize, Ordering};
+
 use gasket::framework::*;
 use pallas::ledger::traverse::wellknown::GenesisValues;
 use serde::Deserialize;
-use tracing::{debug, info};
+use tracing::{debug, info, warn};
 
 use pallas::ledger::traverse::MultiEraHeader;
-use pallas::network::facades::PeerClient;
-use pallas::network::miniprotocols::chainsync::{self, HeaderContent, NextResponse};
-use pallas::network::miniprotocols::Point;
+use pallas::network::miniprotocols::chainsync::{HeaderContent, Message, NextResponse, Tip};
+use pallas::network::miniprotocols::{
+    blockfetch, handshake, Point, PROTOCOL_N2N_BLOCK_FETCH, PROTOCOL_N2N_CHAIN_SYNC,
+    PROTOCOL_N2N_HANDSHAKE,
+};
+use pallas::network::multiplexer::{self, Bearer, ChannelBuffer, Plexer};
+use tokio::task::JoinHandle;
 
 use crate::framework::cursor::Cursor;
 use crate::framework::errors::Error;
 use crate::framework::*;
 
+use super::Finished;
+
+const DEFAULT_FETCH_WINDOW: usize = 50;
+
+const DEFAULT_PIPELINE_DEPTH: usize = 10;
+
 #[derive(Stage)]
 #[stage(
     name = "source-n2n",
-    unit = "NextResponse<HeaderContent>",
+    unit = "Vec<NextResponse<HeaderContent>>",
     worker = "Worker"
 )]
 pub struct Stage {
@@ -27,6 +39,14 @@ pub struct Stage {
 
     cursor: Cursor,
 
+    finalize: Option<FinalizeConfig>,
+
+    block_count: u64,
+
+    pub finished: Finished,
+
+    next_peer: AtomicUsize,
+
     pub output: SourceOutputPort,
 
     #[metric]
@@ -34,6 +54,10 @@ pub struct Stage {
 
     #[metric]
     chain_tip: gasket::metrics::Gauge,
+
+    /// Index in the configured list of the peer currently in use
+    #[metric]
+    active_peer: gasket::metrics::Gauge,
 }
 
 fn to_traverse(header: &HeaderContent) -> Result<MultiEraHeader<'_>, WorkerError> {
@@ -45,25 +69,216 @@ fn to_traverse(header: &HeaderContent) -> Result<MultiEraHeader<'_>, WorkerError
     out.or_panic()
 }
 
+/// Bookkeeping of the chain-sync requests pipelined to a peer
+///
+/// The peer answers requests in the order they were sent, so a count of the
+/// unanswered ones is enough to know how many more can be sent.
+#[derive(Debug, Default)]
+struct Pipeline {
+    /// Requests sent to the peer that haven't been answered yet
+    in_flight: usize,
+
+    /// Whether the last header received was the tip of the peer, requests
+    /// aren't pipelined while following the tip
+    at_tip: bool,
+}
+
+impl Pipeline {
+    /// Number of requests to send for `depth` of them to be in flight, which is
+    /// a single one while following the tip
+    fn requests_to_send(&self, depth: usize) -> usize {
+        let depth = match self.at_tip {
+            true => 1,
+            false => depth.max(1),
+        };
+
+        depth.saturating_sub(self.in_flight)
+    }
+
+    fn sent(&mut self) {
+        self.in_flight += 1;
+    }
+
+    fn answered(&mut self) -> Result<(), WorkerError> {
+        if self.in_flight == 0 {
+            warn!("chain-sync response without a request in flight");
+            return Err(WorkerError::Restart);
+        }
+
+        self.in_flight -= 1;
+
+        Ok(())
+    }
+
+    fn roll_forward(&mut self, slot: u64, tip: &Tip) -> Result<(), WorkerError> {
+        self.answered()?;
+        self.at_tip = slot >= tip.0.slot_or_default();
+
+        Ok(())
+    }
+
+    fn roll_backward(&mut self) -> Result<(), WorkerError> {
+        self.answered()
+    }
+
+    /// The request stays in flight until the peer has a new block
+    fn await_reply(&mut self) {
+        self.at_tip = true;
+    }
+}
+
+/// Node-to-node session whose chain-sync requests are pipelined
+///
+/// The chain-sync client of pallas waits for each response before sending the
+/// next request, so the session drives the mini-protocol over its channel
+/// instead.
+struct PeerSession {
+    plexer_handle: JoinHandle<Result<(), multiplexer::Error>>,
+    chainsync: ChannelBuffer,
+    blockfetch: blockfetch::Client,
+    pipeline: Pipeline,
+}
+
+impl PeerSession {
+    async fn connect(address: &str, magic: u64) -> Result<Self, WorkerError> {
+        let bearer = Bearer::connect_tcp(address).await.or_retry()?;
+
+        let mut plexer = Plexer::new(bearer);
+
+        let handshake = plexer.subscribe_client(PROTOCOL_N2N_HANDSHAKE);
+        let chainsync = plexer.subscribe_client(PROTOCOL_N2N_CHAIN_SYNC);
+        let blockfetch = plexer.subscribe_client(PROTOCOL_N2N_BLOCK_FETCH);
+
+        let plexer_handle = tokio::spawn(async move { plexer.run().await });
+
+        let mut session = Self {
+            plexer_handle,
+            chainsync: ChannelBuffer::new(chainsync),
+            blockfetch: blockfetch::Client::new(blockfetch),
+            pipeline: Pipeline::default(),
+        };
+
+        let versions = handshake::n2n::VersionTable::v7_and_above(magic);
+
+        let confirmation = handshake::Client::new(handshake).handshake(versions).await;
+
+        match confirmation {
+            Ok(handshake::Confirmation::Accepted(..)) => Ok(session),
+            Ok(x) => {
+                warn!(confirmation = ?x, "handshake refused");
+                session.abort();
+                Err(WorkerError::Retry)
+            }
+            Err(err) => {
+                session.abort();
+                Err(err).or_retry()
+            }
+        }
+    }
+
+    async fn send(&mut self, msg: &Message<HeaderContent>) -> Result<(), WorkerError> {
+        self.chainsync.send_msg_chunks(msg).await.or_restart()
+    }
+
+    async fn recv(&mut self) -> Result<Message<HeaderContent>, WorkerError> {
+        self.chainsync.recv_full_msg().await.or_restart()
+    }
+
+    async fn find_intersect(
+        &mut self,
+        points: Vec<Point>,
+    ) -> Result<(Option<Point>, Tip), WorkerError> {
+        self.send(&Message::FindIntersect(points)).await?;
+
+        match self.recv().await? {
+            Message::IntersectFound(point, tip) => Ok((Some(point), tip)),
+            Message::IntersectNotFound(tip) => Ok((None, tip)),
+            x => unexpected(x),
+        }
+    }
+
+    async fn intersect_origin(&mut self) -> Result<Point, WorkerError> {
+        match self.find_intersect(vec![Point::Origin]).await? {
+            (Some(point), _) => Ok(point),
+            (None, _) => unexpected_intersection(),
+        }
+    }
+
+    async fn intersect_tip(&mut self) -> Result<Point, WorkerError> {
+        let (_, Tip(tip, _)) = self.find_intersect(vec![Point::Origin]).await?;
+
+        match self.find_intersect(vec![tip]).await? {
+            (Some(point), _) => Ok(point),
+            (None, _) => unexpected_intersection(),
+        }
+    }
+
+    /// Sends requests for the next headers until `depth` of them are in flight
+    async fn request_next(&mut self, depth: usize) -> Result<(), WorkerError> {
+        for _ in 0..self.pipeline.requests_to_send(depth) {
+            debug!("requesting next block");
+            self.send(&Message::RequestNext).await?;
+            self.pipeline.sent();
+        }
+
+        Ok(())
+    }
+
+    /// Receives the response to the oldest request in flight
+    async fn recv_next(&mut self) -> Result<NextResponse<HeaderContent>, WorkerError> {
+        match self.recv().await? {
+            Message::RollForward(header, tip) => {
+                let slot = to_traverse(&header)?.slot();
+                self.pipeline.roll_forward(slot, &tip)?;
+
+                Ok(NextResponse::RollForward(header, tip))
+            }
+            Message::RollBackward(point, tip) => {
+                self.pipeline.roll_backward()?;
+
+                Ok(NextResponse::RollBackward(point, tip))
+            }
+            Message::AwaitReply => {
+                self.pipeline.await_reply();
+
+                Ok(NextResponse::Await)
+            }
+            x => unexpected(x),
+        }
+    }
+
+    fn abort(&mut self) {
+        self.plexer_handle.abort();
+    }
+}
+
+fn unexpected<T>(msg: Message<HeaderContent>) -> Result<T, WorkerError> {
+    warn!(?msg, "unexpected chain-sync message");
+    Err(WorkerError::Restart)
+}
+
+fn unexpected_intersection<T>() -> Result<T, WorkerError> {
+    warn!("peer has no intersection with its own chain");
+    Err(WorkerError::Restart)
+}
+
 async fn intersect_from_config(
-    peer: &mut PeerClient,
+    peer: &mut PeerSession,
     intersect: &IntersectConfig,
 ) -> Result<(), WorkerError> {
-    let chainsync = peer.chainsync();
-
     let intersect = match intersect {
         IntersectConfig::Origin => {
             info!("intersecting origin");
-            chainsync.intersect_origin().await.or_restart()?.into()
+            Some(peer.intersect_origin().await?)
         }
         IntersectConfig::Tip => {
             info!("intersecting tip");
-            chainsync.intersect_tip().await.or_restart()?.into()
+            Some(peer.intersect_tip().await?)
         }
         IntersectConfig::Point(..) | IntersectConfig::Breadcrumbs(..) => {
             info!("intersecting specific points");
             let points = intersect.points().unwrap_or_default();
-            let (point, _) = chainsync.find_intersect(points).await.or_restart()?;
+            let (point, _) = peer.find_intersect(points).await?;
             point
         }
     };
@@ -73,132 +288,274 @@ async fn intersect_from_config(
     Ok(())
 }
 
-async fn intersect_from_cursor(peer: &mut PeerClient, cursor: &Cursor) -> Result<(), WorkerError> {
+async fn intersect_from_cursor(peer: &mut PeerSession, cursor: &Cursor) -> Result<(), WorkerError> {
     let points = cursor.clone_state();
 
-    let (intersect, _) = peer
-        .chainsync()
-        .find_intersect(points.into())
-        .await
-        .or_restart()?;
+    let (intersect, _) = peer.find_intersect(points.into()).await?;
 
     info!(?intersect, "intersected");
 
     Ok(())
 }
 
+async fn connect_peer(stage: &Stage, peer_address: &str) -> Result<PeerSession, WorkerError> {
+    debug!(peer = peer_address, "connecting");
+
+    let mut peer_session = PeerSession::connect(peer_address, stage.chain.magic).await?;
+
+    let intersect = if stage.cursor.is_empty() {
+        intersect_from_config(&mut peer_session, &stage.intersect).await
+    } else {
+        intersect_from_cursor(&mut peer_session, &stage.cursor).await
+    };
+
+    if let Err(err) = intersect {
+        peer_session.abort();
+        return Err(err);
+    }
+
+    Ok(peer_session)
+}
+
+/// Work needed to send a batch of chain-sync responses downstream, in order
+#[derive(Debug, PartialEq)]
+enum BatchStep {
+    /// Blocks of consecutive headers, fetched as a single range
+    Fetch(Vec<Point>),
+
+    /// Rollback of the chain to the point
+    Reset(Point),
+}
+
+/// Groups the headers of the batch into block ranges split by the rollbacks
+fn plan_batch(batch: &[NextResponse<Point>]) -> Vec<BatchStep> {
+    let mut steps = Vec::new();
+    let mut headers = Vec::with_capacity(batch.len());
+
+    for next in batch {
+        match next {
+            NextResponse::RollForward(point, _) => headers.push(point.clone()),
+            NextResponse::RollBackward(point, _) => {
+                // headers of the batch past the rollback point are never fetched
+                headers.retain(|x: &Point| x.slot_or_default() <= point.slot_or_default());
+
+                if !headers.is_empty() {
+                    steps.push(BatchStep::Fetch(std::mem::take(&mut headers)));
+                }
+
+                steps.push(BatchStep::Reset(point.clone()));
+            }
+            NextResponse::Await => (),
+        }
+    }
+
+    if !headers.is_empty() {
+        steps.push(BatchStep::Fetch(headers));
+    }
+
+    steps
+}
+
 pub struct Worker {
-    peer_session: PeerClient,
+    finalized: bool,
+    peer_session: PeerSession,
 }
 
 impl Worker {
-    async fn process_next(
+    /// Fetches the blocks of the headers in a single range request and sends
+    /// them downstream in order
+    async fn fetch_blocks(
         &mut self,
         stage: &mut Stage,
-        next: &NextResponse<HeaderContent>,
+        headers: Vec<Point>,
     ) -> Result<(), WorkerError> {
-        match next {
-            NextResponse::RollForward(header, tip) => {
-                let header = to_traverse(header).or_panic()?;
-                let slot = header.slot();
-                let hash = header.hash();
-
-                debug!(slot, %hash, "chain sync roll forward");
+        let (first, last) = match (headers.first(), headers.last()) {
+            (Some(first), Some(last)) => (first.clone(), last.clone()),
+            _ => return Ok(()),
+        };
 
-                let block = self
-                    .peer_session
-                    .blockfetch()
-                    .fetch_single(Point::Specific(slot, hash.to_vec()))
-                    .await
-                    .or_retry()?;
+        debug!(blocks = headers.len(), "fetching block range");
 
-                let evt = ChainEvent::apply(
-                    Point::Specific(slot, hash.to_vec()),
-                    Record::RawBlockPayload(block),
-                );
+        let blocks = self
+            .peer_session
+            .blockfetch
+            .fetch_range((first, last))
+            .await
+            .or_restart()?;
 
-                stage.output.send(evt).await.or_panic()?;
+        if blocks.len() != headers.len() {
+            warn!(
+                expected = headers.len(),
+                received = blocks.len(),
+                "block range doesn't match chain-sync headers"
+            );
 
-                stage.chain_tip.set(tip.0.slot_or_default() as i64);
+            return Err(WorkerError::Restart);
+        }
 
-                Ok(())
+        for (point, block) in headers.into_iter().zip(blocks) {
+            if self.finalized {
+                break;
             }
-            chainsync::NextResponse::RollBackward(point, tip) => {
-                match &point {
-                    Point::Origin => debug!("rollback to origin"),
-                    Point::Specific(slot, _) => debug!(slot, "rollback"),
-                };
 
-                let evt = ChainEvent::reset(point.clone());
+            let evt = ChainEvent::apply(point.clone(), Record::RawBlockPayload(block));
+
+            stage.output.send(evt).await.or_panic()?;
 
-                stage.output.send(evt).await.or_panic()?;
+            stage.block_count += 1;
+
+            if let Some(finalize) = &stage.finalize {
+                if finalize.should_finalize(&point, stage.block_count) {
+                    info!(?point, "finalize condition reached");
+                    self.finalized = true;
+                }
+            }
+        }
+
+        Ok(())
+    }
 
-                stage.chain_tip.set(tip.0.slot_or_default() as i64);
+    async fn process_batch(
+        &mut self,
+        stage: &mut Stage,
+        batch: &[NextResponse<HeaderContent>],
+    ) -> Result<(), WorkerError> {
+        let mut points = Vec::with_capacity(batch.len());
+
+        for next in batch {
+            match next {
+                NextResponse::RollForward(header, tip) => {
+                    let header = to_traverse(header).or_panic()?;
+                    let slot = header.slot();
+                    let hash = header.hash();
+
+                    debug!(slot, %hash, "chain sync roll forward");
+
+                    let point = Point::Specific(slot, hash.to_vec());
+                    points.push(NextResponse::RollForward(point, tip.clone()));
+
+                    stage.chain_tip.set(tip.0.slot_or_default() as i64);
+                }
+                NextResponse::RollBackward(point, tip) => {
+                    match &point {
+                        Point::Origin => debug!("rollback to origin"),
+                        Point::Specific(slot, _) => debug!(slot, "rollback"),
+                    };
+
+                    points.push(NextResponse::RollBackward(point.clone(), tip.clone()));
+
+                    stage.chain_tip.set(tip.0.slot_or_default() as i64);
+                }
+                NextResponse::Await => {
+                    info!("chain-sync reached the tip of the chain");
+                }
+            }
+        }
 
-                Ok(())
+        for step in plan_batch(&points) {
+            if self.finalized {
+                return Ok(());
             }
-            chainsync::NextResponse::Await => {
-                info!("chain-sync reached the tip of the chain");
-                Ok(())
+
+            match step {
+                BatchStep::Fetch(headers) => self.fetch_blocks(stage, headers).await?,
+                BatchStep::Reset(point) => {
+                    let evt = ChainEvent::reset(point);
+                    stage.output.send(evt).await.or_panic()?;
+                }
             }
         }
+
+        Ok(())
     }
 }
 
 #[async_trait::async_trait(?Send)]
 impl gasket::framework::Worker<Stage> for Worker {
     async fn bootstrap(stage: &Stage) -> Result<Self, WorkerError> {
-        debug!("connecting");
+        let peers = &stage.config.peers;
 
-        let peer_address = stage
-            .config
-            .peers
-            .first()
-            .cloned()
-            .ok_or_else(|| Error::config("at least one upstream peer is required"))
-            .or_panic()?;
+        if peers.is_empty() {
+            return Err(Error::config("at least one upstream peer is required")).or_panic();
+        }
 
-        let mut peer_session = PeerClient::connect(&peer_address, stage.chain.magic)
-            .await
-            .or_retry()?;
+        // each (re)bootstrap starts with the peer after the last active one, so a
+        // failing peer rotates the session to the next one in the list
+        let start = stage.next_peer.load(Ordering::Relaxed);
 
-        if stage.cursor.is_empty() {
-            intersect_from_config(&mut peer_session, &stage.intersect).await?;
-        } else {
-            intersect_from_cursor(&mut peer_session, &stage.cursor).await?;
-        }
+        for offset in 0..peers.len() {
+            let index = (start + offset) % peers.len();
+            let peer_address = &peers[index];
 
-        let worker = Self { peer_session };
+            match connect_peer(stage, peer_address).await {
+                Ok(peer_session) => {
+                    info!(peer = peer_address, "connected to upstream peer");
 
-        Ok(worker)
+                    stage.next_peer.store(index + 1, Ordering::Relaxed);
+                    stage.active_peer.set(index as i64);
+
+                    let worker = Self {
+                        finalized: false,
+                        peer_session,
+                    };
+
+                    return Ok(worker);
+                }
+                Err(err) => {
+                    warn!(peer = peer_address, %err, "upstream peer failed, trying next one");
+                }
+            }
+        }
+
+        Err(WorkerError::Retry)
     }
 
     async fn schedule(
         &mut self,
-        _stage: &mut Stage,
-    ) -> Result<WorkSchedule<NextResponse<HeaderContent>>, WorkerError> {
-        let client = self.peer_session.chainsync();
-
-        let next = match client.has_agency() {
-            true => {
-                info!("requesting next block");
-                client.request_next().await.or_restart()?
-            }
-            false => {
+        stage: &mut Stage,
+    ) -> Result<WorkSchedule<Vec<NextResponse<HeaderContent>>>, WorkerError> {
+        if self.finalized {
+            info!("source finalized, no more blocks will be requested");
+            stage.finished.set();
+            return Ok(WorkSchedule::Done);
+        }
+
+        let window = stage.config.fetch_window.unwrap_or(DEFAULT_FETCH_WINDOW);
+        let depth = stage
+            .config
+            .pipeline_depth
+            .unwrap_or(DEFAULT_PIPELINE_DEPTH);
+        let mut batch = Vec::with_capacity(window);
+
+        // collect headers until the window is full, cutting the batch short on
+        // rollbacks and when reaching the tip so that those are handled promptly
+        while batch.len() < window {
+            self.peer_session.request_next(depth).await?;
+
+            if self.peer_session.pipeline.at_tip {
                 info!("awaiting next block (blocking)");
-                client.recv_while_must_reply().await.or_restart()?
             }
-        };
 
-        Ok(WorkSchedule::Unit(next))
+            let next = self.peer_session.recv_next().await?;
+
+            let is_forward = matches!(next, NextResponse::RollForward(..));
+
+            batch.push(next);
+
+            if !is_forward {
+                break;
+            }
+        }
+
+        Ok(WorkSchedule::Unit(batch))
     }
 
     async fn execute(
         &mut self,
-        unit: &NextResponse<HeaderContent>,
+        unit: &Vec<NextResponse<HeaderContent>>,
         stage: &mut Stage,
     ) -> Result<(), WorkerError> {
-        self.process_next(stage, unit).await
+        self.process_batch(stage, unit).await
     }
 
     async fn teardown(&mut self) -> Result<(), WorkerError> {
@@ -211,6 +568,13 @@ impl gasket::framework::Worker<Stage> for Worker {
 #[derive(Deserialize)]
 pub struct Config {
     peers: Vec<String>,
+
+    /// Max number of headers to gather before fetching their blocks as a
+    /// single range
+    fetch_window: Option<usize>,
+
+    /// Max number of chain-sync requests in flight while behind the tip
+    pipeline_depth: Option<usize>,
 }
 
 impl Config {
@@ -220,11 +584,146 @@ impl Config {
             chain: ctx.chain.clone().into(),
             intersect: ctx.intersect.clone(),
             cursor: ctx.cursor.clone(),
+            finalize: ctx.finalize.clone(),
+            block_count: 0,
+            finished: Default::default(),
+            next_peer: Default::default(),
             output: Default::default(),
             ops_count: Default::default(),
             chain_tip: Default::default(),
+            active_peer: Default::default(),
         };
 
         Ok(stage)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn point(slot: u64) -> Point {
+        Point::Specific(slot, vec![slot as u8])
+    }
+
+    fn tip(slot: u64) -> Tip {
+        Tip(point(slot), slot)
+    }
+
+    fn forward(slot: u64) -> NextResponse<Point> {
+        NextResponse::RollForward(point(slot), tip(100))
+    }
+
+    fn backward(slot: u64) -> NextResponse<Point> {
+        NextResponse::RollBackward(point(slot), tip(100))
+    }
+
+    #[test]
+    fn fills_pipeline_up_to_depth() {
+        let mut pipeline = Pipeline::default();
+        assert_eq!(pipeline.requests_to_send(3), 3);
+
+        (0..3).for_each(|_| pipeline.sent());
+        assert_eq!(pipeline.requests_to_send(3), 0);
+
+        pipeline.roll_forward(10, &tip(100)).unwrap();
+        assert_eq!(pipeline.in_flight, 2);
+        assert_eq!(pipeline.requests_to_send(3), 1);
+
+        // a depth of zero still keeps a request going
+        assert_eq!(Pipeline::default().requests_to_send(0), 1);
+    }
+
+    #[test]
+    fn keeps_requests_in_flight_across_rollback() {
+        let mut pipeline = Pipeline::default();
+        (0..3).for_each(|_| pipeline.sent());
+
+        pipeline.roll_forward(10, &tip(100)).unwrap();
+        pipeline.roll_backward().unwrap();
+
+        // the rollback answers a single request, the peer still owes the rest
+        assert_eq!(pipeline.in_flight, 1);
+        assert!(!pipeline.at_tip);
+        assert_eq!(pipeline.requests_to_send(3), 2);
+
+        pipeline.roll_forward(8, &tip(100)).unwrap();
+        assert_eq!(pipeline.in_flight, 0);
+    }
+
+    #[test]
+    fn stops_pipelining_at_tip() {
+        let mut pipeline = Pipeline::default();
+        (0..3).for_each(|_| pipeline.sent());
+
+        pipeline.roll_forward(100, &tip(100)).unwrap();
+        assert!(pipeline.at_tip);
+
+        // the remaining requests are drained before sending a new one
+        assert_eq!(pipeline.requests_to_send(3), 0);
+
+        pipeline.roll_forward(101, &tip(101)).unwrap();
+        pipeline.await_reply();
+        assert_eq!(pipeline.in_flight, 1);
+        assert_eq!(pipeline.requests_to_send(3), 0);
+
+        pipeline.roll_forward(102, &tip(102)).unwrap();
+        assert_eq!(pipeline.requests_to_send(3), 1);
+
+        // falling behind the tip again resumes the pipelining
+        pipeline.sent();
+        pipeline.roll_forward(103, &tip(110)).unwrap();
+        assert!(!pipeline.at_tip);
+        assert_eq!(pipeline.requests_to_send(3), 3);
+    }
+
+    #[test]
+    fn fails_on_unrequested_response() {
+        let mut pipeline = Pipeline::default();
+        assert!(pipeline.roll_forward(10, &tip(100)).is_err());
+        assert!(pipeline.roll_backward().is_err());
+    }
+
+    #[test]
+    fn fetches_consecutive_headers_as_single_range() {
+        let batch = vec![forward(1), forward(2), forward(3)];
+
+        assert_eq!(
+            plan_batch(&batch),
+            vec![BatchStep::Fetch(vec![point(1), point(2), point(3)])]
+        );
+    }
+
+    #[test]
+    fn splits_ranges_at_rollbacks() {
+        let batch = vec![
+            forward(1),
+            forward(2),
+            forward(3),
+            backward(2),
+            forward(3),
+            forward(4),
+        ];
+
+        assert_eq!(
+            plan_batch(&batch),
+            vec![
+                BatchStep::Fetch(vec![point(1), point(2)]),
+                BatchStep::Reset(point(2)),
+                BatchStep::Fetch(vec![point(3), point(4)]),
+            ]
+        );
+    }
+
+    #[test]
+    fn skips_fetch_of_rolled_back_headers() {
+        let batch = vec![forward(5), forward(6), backward(4), NextResponse::Await];
+
+        assert_eq!(plan_batch(&batch), vec![BatchStep::Reset(point(4))]);
+
+        assert_eq!(
+            plan_batch(&[backward(0), backward(1)]),
+            vec![BatchStep::Reset(point(0)), BatchStep::Reset(point(1))]
+        );
+    }
+}
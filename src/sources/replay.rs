@@ -0,0 +1,230 @@
+use std::collections::VecDeque;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+use gasket::framework::*;
+use pallas::ledger::traverse::MultiEraBlock;
+use pallas::network::miniprotocols::Point;
+use serde::Deserialize;
+use tracing::{debug, info};
+
+use crate::crosscut::PointArg;
+use crate::framework::errors::Error;
+use crate::framework::*;
+
+use super::Finished;
+
+#[derive(Stage)]
+#[stage(name = "source-replay", unit = "ChainEvent", worker = "Worker")]
+pub struct Stage {
+    config: Config,
+
+    finalize: Option<FinalizeConfig>,
+
+    block_count: u64,
+
+    pub finished: Finished,
+
+    pub output: SourceOutputPort,
+
+    #[metric]
+    ops_count: gasket::metrics::Counter,
+
+    #[metric]
+    chain_tip: gasket::metrics::Gauge,
+}
+
+fn block_event(cbor: Vec<u8>) -> Result<ChainEvent, Error> {
+    let block = MultiEraBlock::decode(&cbor).map_err(Error::cbor)?;
+    let point = Point::Specific(block.slot(), block.hash().to_vec());
+
+    Ok(ChainEvent::Apply(point, Record::RawBlockPayload(cbor)))
+}
+
+/// Parses a line of a replay file, either a hex-encoded block or a reset
+/// marker such as `reset 1234,<hex-hash>` or `reset origin`
+fn parse_line(line: &str) -> Result<Option<ChainEvent>, Error> {
+    let line = line.trim();
+
+    if line.is_empty() || line.starts_with('#') {
+        return Ok(None);
+    }
+
+    if let Some(marker) = line.strip_prefix("reset") {
+        let point = PointArg::from_str(marker.trim())?.try_into()?;
+        return Ok(Some(ChainEvent::Reset(point)));
+    }
+
+    let cbor = hex::decode(line).map_err(Error::cbor)?;
+
+    block_event(cbor).map(Some)
+}
+
+/// Reads the events of a file, which holds either newline-delimited entries
+/// or a single raw CBOR block
+fn read_file(path: &Path) -> Result<Vec<ChainEvent>, Error> {
+    debug!(?path, "reading replay file");
+
+    let bytes = std::fs::read(path).map_err(Error::source)?;
+
+    match String::from_utf8(bytes) {
+        Ok(text) => text
+            .lines()
+            .filter_map(|line| parse_line(line).transpose())
+            .collect(),
+        Err(err) => Ok(vec![block_event(err.into_bytes())?]),
+    }
+}
+
+fn read_events(path: &Path) -> Result<VecDeque<ChainEvent>, Error> {
+    let mut files = vec![];
+
+    if path.is_dir() {
+        for entry in std::fs::read_dir(path).map_err(Error::source)? {
+            let entry = entry.map_err(Error::source)?.path();
+
+            if entry.is_file() {
+                files.push(entry);
+            }
+        }
+
+        files.sort();
+    } else {
+        files.push(path.to_owned());
+    }
+
+    let mut events = VecDeque::new();
+
+    for file in files {
+        events.extend(read_file(&file)?);
+    }
+
+    Ok(events)
+}
+
+pub struct Worker {
+    events: VecDeque<ChainEvent>,
+    finalized: bool,
+}
+
+#[async_trait::async_trait(?Send)]
+impl gasket::framework::Worker<Stage> for Worker {
+    async fn bootstrap(stage: &Stage) -> Result<Self, WorkerError> {
+        let events = read_events(&stage.config.path).or_panic()?;
+
+        info!(path = ?stage.config.path, events = events.len(), "loaded replay events");
+
+        Ok(Self {
+            events,
+            finalized: false,
+        })
+    }
+
+    async fn schedule(
+        &mut self,
+        stage: &mut Stage,
+    ) -> Result<WorkSchedule<ChainEvent>, WorkerError> {
+        if self.finalized {
+            info!("source finalized, no more blocks will be replayed");
+            stage.finished.set();
+            return Ok(WorkSchedule::Done);
+        }
+
+        match self.events.pop_front() {
+            Some(evt) => Ok(WorkSchedule::Unit(evt)),
+            None => {
+                info!("replay finished");
+                stage.finished.set();
+                Ok(WorkSchedule::Done)
+            }
+        }
+    }
+
+    async fn execute(&mut self, unit: &ChainEvent, stage: &mut Stage) -> Result<(), WorkerError> {
+        let point = unit.point().clone();
+
+        match unit {
+            ChainEvent::Apply(..) => debug!(?point, "replaying block"),
+            ChainEvent::Reset(..) => debug!(?point, "replaying reset"),
+        };
+
+        stage
+            .output
+            .send(gasket::messaging::Message::from(unit.clone()))
+            .await
+            .or_panic()?;
+
+        stage.ops_count.inc(1);
+        stage.chain_tip.set(point.slot_or_default() as i64);
+
+        if let ChainEvent::Apply(..) = unit {
+            stage.block_count += 1;
+
+            if let Some(finalize) = &stage.finalize {
+                if finalize.should_finalize(&point, stage.block_count) {
+                    info!(?point, "finalize condition reached");
+                    self.finalized = true;
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Replays captured blocks, ignoring the intersect config
+///
+/// The path can be a single file or a directory, whose files are read in
+/// name order. Each file holds either a raw CBOR block or newline-delimited
+/// hex-encoded blocks and reset markers.
+#[derive(Deserialize)]
+pub struct Config {
+    path: PathBuf,
+}
+
+impl Config {
+    pub fn bootstrapper(self, ctx: &Context) -> Result<Stage, Error> {
+        let stage = Stage {
+            config: self,
+            finalize: ctx.finalize.clone(),
+            block_count: 0,
+            finished: Default::default(),
+            output: Default::default(),
+            ops_count: Default::default(),
+            chain_tip: Default::default(),
+        };
+
+        Ok(stage)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use pallas::network::miniprotocols::Point;
+
+    use crate::framework::ChainEvent;
+
+    use super::{parse_line, read_file};
+
+    #[test]
+    fn read_hex_block_file() {
+        let events = read_file(Path::new("assets/test.block")).unwrap();
+
+        assert_eq!(events.len(), 1);
+        assert!(matches!(events[0], ChainEvent::Apply(..)));
+    }
+
+    #[test]
+    fn parse_reset_markers() {
+        let evt = parse_line("reset origin").unwrap().unwrap();
+        assert!(matches!(evt, ChainEvent::Reset(Point::Origin)));
+
+        let evt = parse_line("reset 1234,abcd").unwrap().unwrap();
+        assert!(matches!(evt, ChainEvent::Reset(Point::Specific(1234, _))));
+
+        assert!(parse_line("# a comment").unwrap().is_none());
+        assert!(parse_line("").unwrap().is_none());
+    }
+}
@@ -0,0 +1,855 @@
+use elasticsearch::{
+    http::{request::JsonBody, response::Response},
+    indices::IndicesRefreshParts,
+    BulkParts, DeleteByQueryParts, Elasticsearch, GetParts, MgetParts, SearchParts,
+};
+use gasket::framework::*;
+use pallas::network::miniprotocols::Point;
+use serde::Deserialize;
+use serde_json::{json, Value as JsonValue};
+use std::collections::{HashMap, HashSet};
+use tracing::{debug, info, warn};
+
+use crate::{crosscut::PointArg, framework::*};
+
+use super::{
+    journal::{warn_if_exhausted, DEFAULT_JOURNAL_DEPTH},
+    recv_pending, CatchUp,
+};
+
+const DEFAULT_INDEX: &str = "scrolls";
+
+const CURSOR_DOC_ID: &str = "cursor";
+
+/// Blocks applied between two trims of the journal, which holds up to this
+/// many entries past `journal_depth` in between
+const TRIM_INTERVAL: usize = 100;
+
+const SET_ADD: &str = r#"
+    if (ctx._source.members == null) { ctx._source.members = new ArrayList(); }
+    if (!ctx._source.members.contains(params.member)) { ctx._source.members.add(params.member); }
+    ctx._source.key = params.key;
+"#;
+
+const SET_REMOVE: &str = r#"
+    if (ctx._source.members == null) { ctx.op = 'none'; }
+    else { ctx._source.members.removeIf(x -> x == params.member); }
+"#;
+
+// two-phase sets keep their live members next to the tombstones, removed
+// members can't be added back
+const TWO_PHASE_ADD: &str = r#"
+    if (ctx._source.tombstones != null && ctx._source.tombstones.contains(params.member)) {
+        ctx.op = 'none';
+    } else {
+        if (ctx._source.members == null) { ctx._source.members = new ArrayList(); }
+        if (!ctx._source.members.contains(params.member)) { ctx._source.members.add(params.member); }
+        ctx._source.key = params.key;
+    }
+"#;
+
+const TWO_PHASE_REMOVE: &str = r#"
+    if (ctx._source.tombstones == null) { ctx._source.tombstones = new ArrayList(); }
+    if (!ctx._source.tombstones.contains(params.member)) { ctx._source.tombstones.add(params.member); }
+    if (ctx._source.members != null) { ctx._source.members.removeIf(x -> x == params.member); }
+    ctx._source.key = params.key;
+"#;
+
+const SORTED_SET_INCR: &str = r#"
+    if (ctx._source.scores == null) { ctx._source.scores = new ArrayList(); }
+    def entry = null;
+    for (def x : ctx._source.scores) { if (x.member == params.member) { entry = x; } }
+    if (entry == null) {
+        entry = ['member': params.member, 'score': 0L];
+        ctx._source.scores.add(entry);
+    }
+    entry.score += params.delta;
+    ctx._source.scores.removeIf(x -> x.score == 0);
+    ctx._source.key = params.key;
+"#;
+
+const LAST_WRITE: &str = r#"
+    if (ctx._source.slot == null || ctx._source.slot <= params.slot) {
+        ctx._source.key = params.key;
+        ctx._source.value = params.value;
+        ctx._source.slot = params.slot;
+    } else { ctx.op = 'none'; }
+"#;
+
+const COUNTER_INCR: &str = r#"
+    if (ctx._source.count == null) { ctx._source.count = 0L; }
+    ctx._source.count += params.delta;
+    ctx._source.key = params.key;
+"#;
+
+const HASH_SET: &str = r#"
+    if (ctx._source.fields == null) { ctx._source.fields = new ArrayList(); }
+    ctx._source.fields.removeIf(x -> x.member == params.member);
+    ctx._source.fields.add(['member': params.member, 'value': params.value]);
+    ctx._source.key = params.key;
+"#;
+
+const HASH_INCR: &str = r#"
+    if (ctx._source.fields == null) { ctx._source.fields = new ArrayList(); }
+    def entry = null;
+    for (def x : ctx._source.fields) { if (x.member == params.member) { entry = x; } }
+    if (entry == null) {
+        entry = ['member': params.member, 'value': 0L];
+        ctx._source.fields.add(entry);
+    }
+    entry.value += params.delta;
+    ctx._source.key = params.key;
+"#;
+
+const HASH_UNSET: &str = r#"
+    if (ctx._source.fields == null) { ctx.op = 'none'; }
+    else { ctx._source.fields.removeIf(x -> x.member == params.member); }
+"#;
+
+impl From<model::Value> for JsonValue {
+    fn from(other: model::Value) -> JsonValue {
+        match other {
+            model::Value::String(x) => json!(x),
+            model::Value::Cbor(x) => json!(hex::encode(x)),
+            model::Value::BigInt(x) => match i64::try_from(x) {
+                Ok(x) => json!(x),
+                Err(_) => json!(x.to_string()),
+            },
+            model::Value::Json(x) => x,
+        }
+    }
+}
+
+/// Id of the document affected by a command, each key maps to one document
+fn doc_id(command: &model::CRDTCommand) -> String {
+    match command {
+        model::CRDTCommand::SetAdd(key, _)
+        | model::CRDTCommand::SetRemove(key, _)
+        | model::CRDTCommand::SortedSetAdd(key, _, _)
+        | model::CRDTCommand::SortedSetRemove(key, _, _)
+        | model::CRDTCommand::TwoPhaseSetAdd(key, _)
+        | model::CRDTCommand::TwoPhaseSetRemove(key, _)
+        | model::CRDTCommand::GrowOnlySetAdd(key, _)
+        | model::CRDTCommand::LastWriteWins(key, _, _)
+        | model::CRDTCommand::AnyWriteWins(key, _)
+        | model::CRDTCommand::PNCounter(key, _)
+        | model::CRDTCommand::HashCounter(key, _, _)
+        | model::CRDTCommand::HashSetValue(key, _, _)
+        | model::CRDTCommand::HashUnsetKey(key, _) => key.clone(),
+    }
+}
+
+fn scripted_upsert(index: &str, id: &str, script: &str, params: JsonValue) -> [JsonValue; 2] {
+    [
+        json!({ "update": { "_index": index, "_id": id } }),
+        json!({
+            "scripted_upsert": true,
+            "script": { "source": script, "lang": "painless", "params": params },
+            "upsert": {},
+        }),
+    ]
+}
+
+/// Bulk operations that apply a command to its document
+fn command_ops(index: &str, command: &model::CRDTCommand) -> Vec<JsonValue> {
+    let id = doc_id(command);
+
+    let ops = match command {
+        model::CRDTCommand::SetAdd(key, member)
+        | model::CRDTCommand::GrowOnlySetAdd(key, member) => {
+            scripted_upsert(index, &id, SET_ADD, json!({ "key": key, "member": member }))
+        }
+        model::CRDTCommand::TwoPhaseSetAdd(key, member) => scripted_upsert(
+            index,
+            &id,
+            TWO_PHASE_ADD,
+            json!({ "key": key, "member": member }),
+        ),
+        model::CRDTCommand::TwoPhaseSetRemove(key, member) => scripted_upsert(
+            index,
+            &id,
+            TWO_PHASE_REMOVE,
+            json!({ "key": key, "member": member }),
+        ),
+        model::CRDTCommand::SetRemove(key, member) => scripted_upsert(
+            index,
+            &id,
+            SET_REMOVE,
+            json!({ "key": key, "member": member }),
+        ),
+        model::CRDTCommand::SortedSetAdd(key, member, delta)
+        | model::CRDTCommand::SortedSetRemove(key, member, delta) => scripted_upsert(
+            index,
+            &id,
+            SORTED_SET_INCR,
+            json!({ "key": key, "member": member, "delta": delta }),
+        ),
+        model::CRDTCommand::LastWriteWins(key, value, slot) => scripted_upsert(
+            index,
+            &id,
+            LAST_WRITE,
+            json!({ "key": key, "value": JsonValue::from(value.clone()), "slot": slot }),
+        ),
+        model::CRDTCommand::AnyWriteWins(key, value) => [
+            json!({ "index": { "_index": index, "_id": id } }),
+            json!({ "key": key, "value": JsonValue::from(value.clone()) }),
+        ],
+        model::CRDTCommand::PNCounter(key, delta) => scripted_upsert(
+            index,
+            &id,
+            COUNTER_INCR,
+            json!({ "key": key, "delta": delta }),
+        ),
+        model::CRDTCommand::HashSetValue(key, member, value) => scripted_upsert(
+            index,
+            &id,
+            HASH_SET,
+            json!({ "key": key, "member": member, "value": JsonValue::from(value.clone()) }),
+        ),
+        model::CRDTCommand::HashCounter(key, member, delta) => scripted_upsert(
+            index,
+            &id,
+            HASH_INCR,
+            json!({ "key": key, "member": member, "delta": delta }),
+        ),
+        model::CRDTCommand::HashUnsetKey(key, member) => scripted_upsert(
+            index,
+            &id,
+            HASH_UNSET,
+            json!({ "key": key, "member": member }),
+        ),
+    };
+
+    ops.into()
+}
+
+async fn check_response(response: Response) -> Result<JsonValue, Error> {
+    let response = response.error_for_status_code().map_err(Error::storage)?;
+    let body: JsonValue = response.json().await.map_err(Error::storage)?;
+
+    if body["errors"].as_bool().unwrap_or_default() {
+        return Err(Error::storage(format!("bulk request failed: {}", body)));
+    }
+
+    Ok(body)
+}
+
+async fn send_bulk(client: &Elasticsearch, ops: Vec<JsonValue>) -> Result<(), Error> {
+    let body: Vec<JsonBody<JsonValue>> = ops.into_iter().map(JsonBody::from).collect();
+
+    let response = client
+        .bulk(BulkParts::None)
+        .body(body)
+        .send()
+        .await
+        .map_err(Error::storage)?;
+
+    check_response(response).await?;
+
+    Ok(())
+}
+
+/// Id of the journal entry of the compensations applied at a rollback point,
+/// next to the entry of the block at that point
+fn compensation_id(point: &Point) -> String {
+    format!("{}-compensation", point.slot_or_default())
+}
+
+fn cursor_op(journal_index: &str, breadcrumbs: &Breadcrumbs) -> [JsonValue; 2] {
+    [
+        json!({ "index": { "_index": journal_index, "_id": CURSOR_DOC_ID } }),
+        json!({ "breadcrumbs": breadcrumbs }),
+    ]
+}
+
+/// Reads the breadcrumbs persisted along with the journal, `None` if missing
+async fn fetch_breadcrumbs(
+    client: &Elasticsearch,
+    journal_index: &str,
+) -> Result<Option<Breadcrumbs>, Error> {
+    let response = client
+        .get(GetParts::IndexId(journal_index, CURSOR_DOC_ID))
+        .send()
+        .await
+        .map_err(Error::storage)?;
+
+    if response.status_code().as_u16() == 404 {
+        return Ok(None);
+    }
+
+    let response = response.error_for_status_code().map_err(Error::storage)?;
+    let doc: JsonValue = response.json().await.map_err(Error::storage)?;
+
+    serde_json::from_value(doc["_source"]["breadcrumbs"].clone())
+        .map(Some)
+        .map_err(Error::storage)
+}
+
+pub struct Worker {
+    client: Elasticsearch,
+
+    /// Blocks journaled since the journal was last trimmed
+    untrimmed: usize,
+}
+
+impl Worker {
+    /// Reads the current source of each document, `None` if missing
+    async fn fetch_docs(
+        &self,
+        index: &str,
+        ids: Vec<String>,
+    ) -> Result<Vec<(String, Option<JsonValue>)>, Error> {
+        if ids.is_empty() {
+            return Ok(vec![]);
+        }
+
+        let response = self
+            .client
+            .mget(MgetParts::Index(index))
+            .body(json!({ "ids": ids }))
+            .send()
+            .await
+            .map_err(Error::storage)?
+            .error_for_status_code()
+            .map_err(Error::storage)?;
+
+        let body: JsonValue = response.json().await.map_err(Error::storage)?;
+
+        let docs = body["docs"]
+            .as_array()
+            .cloned()
+            .unwrap_or_default()
+            .into_iter()
+            .map(|doc| {
+                let id = doc["_id"].as_str().unwrap_or_default().to_owned();
+
+                match doc["found"].as_bool().unwrap_or_default() {
+                    true => (id, Some(doc["_source"].clone())),
+                    false => (id, None),
+                }
+            })
+            .collect();
+
+        Ok(docs)
+    }
+
+    async fn apply_block(
+        &mut self,
+        config: &Config,
+        cursor: &Cursor,
+        point: &Point,
+        commands: &[model::CRDTCommand],
+    ) -> Result<(), WorkerError> {
+        let index = config.index();
+        let journal_index = config.journal_index();
+
+        // the cursor is saved in the same bulk request as the data, a block up
+        // to its latest point was already committed by a previous attempt
+        // whose reply got lost or by a replay of the source
+        let persisted = fetch_breadcrumbs(&self.client, &journal_index)
+            .await
+            .or_retry()?;
+
+        let persisted: Option<Point> = persisted
+            .and_then(|x| x.into_iter().next())
+            .map(|x| x.try_into())
+            .transpose()
+            .or_panic()?;
+
+        if let Some(persisted) = persisted {
+            if point.slot_or_default() <= persisted.slot_or_default() {
+                warn!(?point, "skipping block already committed");
+                return Ok(());
+            }
+        }
+
+        let mut ids = HashSet::new();
+        let ids: Vec<_> = commands
+            .iter()
+            .map(doc_id)
+            .filter(|x| ids.insert(x.clone()))
+            .collect();
+
+        // pre-images are read before the block is applied so that a rollback
+        // can restore them
+        let undo = self.fetch_docs(&index, ids).await.or_retry()?;
+
+        let undo: Vec<_> = undo
+            .into_iter()
+            .map(|(id, source)| json!({ "id": id, "source": source }))
+            .collect();
+
+        let slot = point.slot_or_default();
+
+        let mut ops = vec![
+            json!({ "index": { "_index": journal_index, "_id": slot.to_string() } }),
+            json!({ "slot": slot, "point": PointArg::from(point.clone()), "undo": undo }),
+        ];
+
+        for command in commands {
+            ops.extend(command_ops(&index, command));
+        }
+
+        ops.extend(cursor_op(
+            &journal_index,
+            &cursor.breadcrumbs_after_apply(point),
+        ));
+
+        // a bulk request isn't atomic, retrying a partially applied block would
+        // apply the increments twice
+        send_bulk(&self.client, ops).await.or_panic()?;
+
+        // trimming takes a search and a delete by query, too much for each block
+        self.untrimmed += 1;
+
+        if self.untrimmed >= TRIM_INTERVAL {
+            self.trim_journal(config).await?;
+            self.untrimmed = 0;
+        }
+
+        Ok(())
+    }
+
+    /// Drops the journal entries older than the latest `journal_depth` blocks
+    async fn trim_journal(&self, config: &Config) -> Result<(), WorkerError> {
+        let journal_index = config.journal_index();
+
+        // entries are keyed by block, the oldest one to keep sits at position
+        // `journal_depth` when sorted from the newest. The cursor doc has no
+        // slot and is left out.
+        let response = self
+            .client
+            .search(SearchParts::Index(&[&journal_index]))
+            .body(json!({
+                "query": { "exists": { "field": "slot" } },
+                "sort": [{ "slot": "desc" }],
+                "from": config.journal_depth().saturating_sub(1),
+                "size": 1,
+                "_source": ["slot"],
+            }))
+            .send()
+            .await
+            .or_retry()?;
+
+        let body = check_response(response).await.or_retry()?;
+        let oldest_kept = body["hits"]["hits"][0]["_source"]["slot"].as_u64();
+
+        let oldest_kept = match oldest_kept {
+            Some(x) => x,
+            None => return Ok(()),
+        };
+
+        self.client
+            .delete_by_query(DeleteByQueryParts::Index(&[&journal_index]))
+            .body(json!({ "query": { "range": { "slot": { "lt": oldest_kept } } } }))
+            .send()
+            .await
+            .or_retry()?;
+
+        Ok(())
+    }
+
+    /// Bulk operations applying the commands that compensate for a rollback,
+    /// along with their journal entry at the rollback point
+    ///
+    /// `restored` holds the documents the rollback restores, which are the
+    /// pre-images of the commands wherever present.
+    async fn compensation_ops(
+        &self,
+        config: &Config,
+        point: &Point,
+        commands: &[model::CRDTCommand],
+        restored: &HashMap<String, JsonValue>,
+    ) -> Result<Vec<JsonValue>, WorkerError> {
+        let index = config.index();
+        let journal_index = config.journal_index();
+        let entry_id = compensation_id(point);
+
+        // a previous compensation at the same point holds older pre-images
+        let previous = self
+            .fetch_docs(&journal_index, vec![entry_id.clone()])
+            .await
+            .or_retry()?
+            .into_iter()
+            .find_map(|(_, x)| x);
+
+        let mut undo = previous
+            .and_then(|x| x["undo"].as_array().cloned())
+            .unwrap_or_default();
+
+        let mut ids: HashSet<_> = undo
+            .iter()
+            .filter_map(|x| x["id"].as_str().map(ToOwned::to_owned))
+            .collect();
+
+        let (known, unknown): (Vec<_>, Vec<_>) = commands
+            .iter()
+            .map(doc_id)
+            .filter(|x| ids.insert(x.clone()))
+            .partition(|x| restored.contains_key(x));
+
+        for id in known {
+            undo.push(json!({ "id": id, "source": restored[&id] }));
+        }
+
+        for (id, source) in self.fetch_docs(&index, unknown).await.or_retry()? {
+            undo.push(json!({ "id": id, "source": source }));
+        }
+
+        let mut ops = vec![
+            json!({ "index": { "_index": journal_index, "_id": entry_id } }),
+            json!({
+                "slot": point.slot_or_default(),
+                "point": PointArg::from(point.clone()),
+                "undo": undo,
+                "compensation": true,
+            }),
+        ];
+
+        for command in commands {
+            ops.extend(command_ops(&index, command));
+        }
+
+        Ok(ops)
+    }
+
+    /// Undoes the blocks after the point, then applies the commands
+    /// compensating for the rollback, if any, in the same bulk request
+    async fn rollback(
+        &self,
+        config: &Config,
+        cursor: &Cursor,
+        point: &Point,
+        compensation: &[model::CRDTCommand],
+    ) -> Result<(), WorkerError> {
+        let index = config.index();
+        let journal_index = config.journal_index();
+
+        // search isn't real-time, recent journal entries need to be visible
+        self.client
+            .indices()
+            .refresh(IndicesRefreshParts::Index(&[&journal_index]))
+            .ignore_unavailable(true)
+            .send()
+            .await
+            .or_retry()?;
+
+        let response = self
+            .client
+            .search(SearchParts::Index(&[&journal_index]))
+            .ignore_unavailable(true)
+            .body(json!({ "size": 0, "aggs": { "oldest": { "min": { "field": "slot" } } } }))
+            .send()
+            .await
+            .or_retry()?;
+
+        let body = check_response(response).await.or_retry()?;
+        let oldest = body["aggregations"]["oldest"]["value"].as_f64();
+
+        warn_if_exhausted(oldest.map(|x| x as u64), cursor, point);
+
+        let response = self
+            .client
+            .search(SearchParts::Index(&[&journal_index]))
+            .ignore_unavailable(true)
+            .body(json!({
+                "query": { "range": { "slot": { "gt": point.slot_or_default() } } },
+                // compensations are undone before the block at their point
+                "sort": [
+                    { "slot": "desc" },
+                    {
+                        "compensation": {
+                            "order": "desc",
+                            "missing": "_last",
+                            "unmapped_type": "boolean",
+                        }
+                    },
+                ],
+                "size": 10000,
+            }))
+            .send()
+            .await
+            .or_retry()?;
+
+        let body = check_response(response).await.or_retry()?;
+        let hits = body["hits"]["hits"].as_array().cloned().unwrap_or_default();
+
+        let mut ops = vec![];
+
+        // documents as they are once every block is undone
+        let mut restored = HashMap::new();
+
+        for hit in hits {
+            let entry = &hit["_source"];
+
+            debug!(point = %entry["point"], "undoing block");
+
+            for undo in entry["undo"].as_array().cloned().unwrap_or_default() {
+                let id = &undo["id"];
+
+                if let Some(id) = id.as_str() {
+                    restored.insert(id.to_owned(), undo["source"].clone());
+                }
+
+                match &undo["source"] {
+                    JsonValue::Null => {
+                        ops.push(json!({ "delete": { "_index": index, "_id": id } }));
+                    }
+                    source => {
+                        ops.push(json!({ "index": { "_index": index, "_id": id } }));
+                        ops.push(source.clone());
+                    }
+                }
+            }
+
+            ops.push(json!({ "delete": { "_index": journal_index, "_id": hit["_id"] } }));
+        }
+
+        if !compensation.is_empty() {
+            let compensation = self
+                .compensation_ops(config, point, compensation, &restored)
+                .await?;
+
+            ops.extend(compensation);
+        }
+
+        ops.extend(cursor_op(
+            &journal_index,
+            &cursor.breadcrumbs_after_rollback(point),
+        ));
+
+        send_bulk(&self.client, ops).await.or_panic()?;
+
+        info!(?point, "rollback applied");
+
+        Ok(())
+    }
+}
+
+fn build_client(config: &Config) -> Result<Elasticsearch, Error> {
+    let url = elasticsearch::http::Url::parse(&config.connection_url).map_err(Error::config)?;
+
+    let pool = elasticsearch::http::transport::SingleNodeConnectionPool::new(url);
+
+    let transport = elasticsearch::http::transport::TransportBuilder::new(pool);
+
+    let transport = match (&config.username, &config.password) {
+        (Some(username), Some(password)) => transport.auth(
+            elasticsearch::auth::Credentials::Basic(username.clone(), password.clone()),
+        ),
+        _ => transport,
+    };
+
+    let transport = transport
+        .cert_validation(elasticsearch::cert::CertificateValidation::None)
+        .build()
+        .map_err(Error::storage)?;
+
+    Ok(Elasticsearch::new(transport))
+}
+
+#[async_trait::async_trait(?Send)]
+impl gasket::framework::Worker<Stage> for Worker {
+    async fn bootstrap(stage: &Stage) -> Result<Self, WorkerError> {
+        let client = build_client(&stage.config).or_panic()?;
+
+        Ok(Self {
+            client,
+            untrimmed: 0,
+        })
+    }
+
+    async fn schedule(
+        &mut self,
+        stage: &mut Stage,
+    ) -> Result<WorkSchedule<ChainEvent>, WorkerError> {
+        recv_pending(&mut stage.input, &mut stage.catch_up).await
+    }
+
+    async fn execute(&mut self, unit: &ChainEvent, stage: &mut Stage) -> Result<(), WorkerError> {
+        match unit {
+            ChainEvent::Apply(point, record) => match record {
+                Record::CRDTCommand(commands) => {
+                    self.apply_block(&stage.config, &stage.cursor, point, commands)
+                        .await?;
+                    stage.cursor.add_breadcrumb(point.clone());
+                }
+                Record::Compensation(commands) => {
+                    self.rollback(&stage.config, &stage.cursor, point, commands)
+                        .await?;
+                    stage.cursor.rollback(point);
+                }
+                _ => {
+                    Err(Error::message("elastic storage only accepts CRDT commands")).or_panic()?
+                }
+            },
+            ChainEvent::Reset(point) => {
+                self.rollback(&stage.config, &stage.cursor, point, &[])
+                    .await?;
+                stage.cursor.rollback(point);
+            }
+        }
+
+        let point = unit.point();
+
+        stage.ops_count.inc(1);
+        stage.latest_block.set(point.slot_or_default() as i64);
+
+        Ok(())
+    }
+}
+
+#[derive(Stage)]
+#[stage(name = "elastic", unit = "ChainEvent", worker = "Worker")]
+pub struct Stage {
+    config: Config,
+    cursor: Cursor,
+    catch_up: CatchUp,
+
+    pub input: StorageInputPort,
+
+    #[metric]
+    ops_count: gasket::metrics::Counter,
+
+    #[metric]
+    latest_block: gasket::metrics::Gauge,
+}
+
+#[derive(Deserialize, Clone)]
+pub struct Config {
+    pub connection_url: String,
+    pub username: Option<String>,
+    pub password: Option<String>,
+
+    /// Index holding one document per CRDT key
+    pub index: Option<String>,
+
+    /// Min number of blocks that can be undone by a rollback, the journal is
+    /// trimmed down to it every 100 blocks
+    pub journal_depth: Option<usize>,
+}
+
+impl Config {
+    fn index(&self) -> String {
+        self.index
+            .clone()
+            .unwrap_or_else(|| DEFAULT_INDEX.to_owned())
+    }
+
+    /// Index keeping the undo data of recent blocks and the cursor
+    fn journal_index(&self) -> String {
+        format!("{}-journal", self.index())
+    }
+
+    fn journal_depth(&self) -> usize {
+        self.journal_depth.unwrap_or(DEFAULT_JOURNAL_DEPTH)
+    }
+
+    pub fn load_cursor(&self) -> Result<Cursor, Error> {
+        let client = build_client(self)?;
+
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()
+            .map_err(Error::storage)?;
+
+        let breadcrumbs = runtime.block_on(fetch_breadcrumbs(&client, &self.journal_index()))?;
+
+        match breadcrumbs {
+            Some(x) => Cursor::from_breadcrumbs(x),
+            None => Ok(Cursor::new(Default::default())),
+        }
+    }
+
+    pub fn bootstrapper(self, ctx: &Context) -> Result<Stage, Error> {
+        let stage = Stage {
+            config: self,
+            cursor: ctx.cursor.clone(),
+            catch_up: CatchUp::new(&ctx.cursor),
+            ops_count: Default::default(),
+            latest_block: Default::default(),
+            input: Default::default(),
+        };
+
+        Ok(stage)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::framework::model::{CRDTCommand, Value};
+
+    #[test]
+    fn upserts_commands_into_their_key_document() {
+        let ops = command_ops("scrolls", &CRDTCommand::PNCounter("c.1".into(), -3));
+
+        assert_eq!(
+            ops[0],
+            json!({ "update": { "_index": "scrolls", "_id": "c.1" } })
+        );
+        assert_eq!(ops[1]["scripted_upsert"], json!(true));
+        assert_eq!(ops[1]["script"]["source"], json!(COUNTER_INCR));
+        assert_eq!(
+            ops[1]["script"]["params"],
+            json!({ "key": "c.1", "delta": -3 })
+        );
+    }
+
+    #[test]
+    fn routes_two_phase_commands_to_the_same_document() {
+        let add = command_ops(
+            "scrolls",
+            &CRDTCommand::TwoPhaseSetAdd("s.1".into(), "a".into()),
+        );
+        let remove = command_ops(
+            "scrolls",
+            &CRDTCommand::TwoPhaseSetRemove("s.1".into(), "a".into()),
+        );
+
+        assert_eq!(add[0], remove[0]);
+        assert_eq!(add[1]["script"]["source"], json!(TWO_PHASE_ADD));
+        assert_eq!(remove[1]["script"]["source"], json!(TWO_PHASE_REMOVE));
+    }
+
+    #[test]
+    fn overwrites_any_write_wins_documents() {
+        let ops = command_ops(
+            "scrolls",
+            &CRDTCommand::AnyWriteWins("k.1".into(), Value::String("v".into())),
+        );
+
+        assert_eq!(
+            ops,
+            vec![
+                json!({ "index": { "_index": "scrolls", "_id": "k.1" } }),
+                json!({ "key": "k.1", "value": "v" }),
+            ]
+        );
+    }
+
+    #[test]
+    fn stores_big_ints_beyond_i64_as_strings() {
+        let small = JsonValue::from(Value::BigInt(i64::MIN as i128));
+        let big = JsonValue::from(Value::BigInt(i64::MAX as i128 + 1));
+
+        assert_eq!(small, json!(i64::MIN));
+        assert_eq!(big, json!("9223372036854775808"));
+    }
+
+    #[test]
+    fn writes_cursor_into_journal_index() {
+        let breadcrumbs: Breadcrumbs = vec![Point::Specific(42, vec![0xab; 32]).into()];
+
+        let [action, doc] = cursor_op("scrolls-journal", &breadcrumbs);
+
+        assert_eq!(
+            action,
+            json!({ "index": { "_index": "scrolls-journal", "_id": CURSOR_DOC_ID } })
+        );
+
+        let restored: Breadcrumbs = serde_json::from_value(doc["breadcrumbs"].clone()).unwrap();
+        let restored: Vec<_> = restored.iter().map(ToString::to_string).collect();
+        assert_eq!(restored, vec!["42,".to_owned() + &"ab".repeat(32)]);
+    }
+}
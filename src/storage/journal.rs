@@ -0,0 +1,135 @@
+//! Undo journal shared by the storage backends
+//!
+//! Before a block is applied, backends capture the pre-image of every slot
+//! its commands touch. The pre-images are persisted next to the data as one
+//! journal entry per block, and restoring them undoes the block on rollback.
+
+use pallas::network::miniprotocols::Point;
+use serde::{Deserialize, Serialize};
+use std::{collections::HashSet, hash::Hash};
+use tracing::warn;
+
+use crate::{crosscut::PointArg, framework::*};
+
+// matches the security parameter (k) of mainnet
+pub const DEFAULT_JOURNAL_DEPTH: usize = 2160;
+
+/// Text representation of a value, for backends that store values as text
+pub fn value_text(value: &model::Value) -> String {
+    match value {
+        model::Value::String(x) => x.clone(),
+        model::Value::BigInt(x) => x.to_string(),
+        model::Value::Cbor(x) => hex::encode(x),
+        model::Value::Json(x) => x.to_string(),
+    }
+}
+
+/// Row held by a table of the SQL backends before a block touched it
+///
+/// Restoring every pre-image captured for a block is the inverse of all the
+/// CRDT commands executed for that block.
+#[derive(Debug, Serialize, Deserialize)]
+pub enum UndoOp {
+    SetMember(String, String, bool),
+    SortedSetMember(String, String, Option<i64>),
+    Counter(String, Option<i64>),
+    Register(String, Option<(String, Option<i64>)>),
+    HashMember(String, String, Option<String>),
+}
+
+impl UndoOp {
+    /// Row touched by the command, its pre-image is filled once read
+    pub fn probe(command: &model::CRDTCommand) -> Self {
+        match command {
+            model::CRDTCommand::GrowOnlySetAdd(key, member)
+            | model::CRDTCommand::TwoPhaseSetAdd(key, member)
+            | model::CRDTCommand::SetAdd(key, member)
+            | model::CRDTCommand::SetRemove(key, member) => {
+                UndoOp::SetMember(key.clone(), member.clone(), false)
+            }
+            model::CRDTCommand::TwoPhaseSetRemove(key, member) => {
+                UndoOp::SetMember(format!("{}.ts", key), member.clone(), false)
+            }
+            model::CRDTCommand::SortedSetAdd(key, member, _)
+            | model::CRDTCommand::SortedSetRemove(key, member, _) => {
+                UndoOp::SortedSetMember(key.clone(), member.clone(), None)
+            }
+            model::CRDTCommand::PNCounter(key, _) => UndoOp::Counter(key.clone(), None),
+            model::CRDTCommand::AnyWriteWins(key, _)
+            | model::CRDTCommand::LastWriteWins(key, _, _) => UndoOp::Register(key.clone(), None),
+            model::CRDTCommand::HashSetValue(key, member, _)
+            | model::CRDTCommand::HashCounter(key, member, _)
+            | model::CRDTCommand::HashUnsetKey(key, member) => {
+                UndoOp::HashMember(key.clone(), member.clone(), None)
+            }
+        }
+    }
+
+    /// Identifies the row this pre-image belongs to
+    pub fn target(&self) -> (&'static str, &str, &str) {
+        match self {
+            UndoOp::SetMember(key, member, _) => ("sets", key, member),
+            UndoOp::SortedSetMember(key, member, _) => ("sorted_sets", key, member),
+            UndoOp::Counter(key, _) => ("counters", key, ""),
+            UndoOp::Register(key, _) => ("registers", key, ""),
+            UndoOp::HashMember(key, member, _) => ("hashes", key, member),
+        }
+    }
+}
+
+/// Rows touched by the commands of a block, each probed once
+pub fn probe_rows(commands: &[model::CRDTCommand]) -> Vec<UndoOp> {
+    first_captures(commands.iter().map(UndoOp::probe), |x| {
+        let (table, key, member) = x.target();
+        (table, key.to_owned(), member.to_owned())
+    })
+}
+
+/// Keeps the first pre-image of each target
+///
+/// Pre-images are read before the block is applied, so later captures of the
+/// same target are redundant.
+pub fn first_captures<T, K, F>(ops: impl IntoIterator<Item = T>, target: F) -> Vec<T>
+where
+    K: Eq + Hash,
+    F: Fn(&T) -> K,
+{
+    let mut seen = HashSet::new();
+
+    ops.into_iter().filter(|x| seen.insert(target(x))).collect()
+}
+
+/// Undo data for a single applied block, stored as JSON in the journal
+#[derive(Debug, Serialize, Deserialize)]
+pub struct JournalEntry<T> {
+    pub point: PointArg,
+    pub undo: Vec<T>,
+}
+
+impl<T> JournalEntry<T> {
+    pub fn new(point: &Point, undo: Vec<T>) -> Self {
+        Self {
+            point: point.clone().into(),
+            undo,
+        }
+    }
+}
+
+/// Warns when a rollback reaches past the oldest journal entry
+///
+/// `oldest` is the slot of the oldest entry before the rollback, the blocks
+/// that preceded it have no undo data. With an empty journal, any block
+/// applied after the rollback point can't be undone.
+pub fn warn_if_exhausted(oldest: Option<u64>, cursor: &Cursor, point: &Point) {
+    let latest = match oldest {
+        Some(x) => Some(x),
+        None => cursor.latest_known_point().map(|x| x.slot_or_default()),
+    };
+
+    if latest.unwrap_or_default() > point.slot_or_default() {
+        warn!(
+            ?point,
+            "undo journal exhausted before reaching rollback point"
+        );
+    }
+}
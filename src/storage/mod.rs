@@ -1,12 +1,170 @@
-use gasket::{messaging::RecvPort, runtime::Tether};
+use gasket::{
+    framework::{WorkSchedule, WorkerError},
+    messaging::RecvPort,
+    runtime::Tether,
+};
+use pallas::network::miniprotocols::Point;
 use serde::Deserialize;
+use std::collections::VecDeque;
+use tracing::debug;
 
 use crate::framework::{errors::Error, *};
 
+pub mod journal;
 pub mod redis;
 
+#[cfg(any(feature = "postgres", feature = "sqlite"))]
+pub mod sql;
+
+#[cfg(feature = "postgres")]
+pub mod postgres;
+
+#[cfg(feature = "sqlite")]
+pub mod sqlite;
+
+#[cfg(feature = "elastic")]
+pub mod elastic;
+
+/// Tracks a backend whose cursor is ahead of the point the pipeline resumed
+/// from, which happens when storages with different progress share a source
+///
+/// Events are matched by hash against the breadcrumbs the backend persisted.
+/// Those on the persisted chain are skipped, the first one that isn't ends
+/// the tracking. If the stream forked away from the persisted chain, a reset
+/// to the last common point is delivered first so that the backend undoes
+/// the orphaned blocks through its journal.
+pub struct CatchUp {
+    /// Persisted chain, latest first; empty once the stream caught up
+    persisted: VecDeque<Point>,
+    /// Latest point of the stream found on the persisted chain
+    common: Option<Point>,
+    /// Event held back while the reset to the common point is delivered
+    pending: Option<ChainEvent>,
+}
+
+impl CatchUp {
+    pub fn new(cursor: &Cursor) -> Self {
+        Self {
+            persisted: cursor.clone_state(),
+            common: None,
+            pending: None,
+        }
+    }
+
+    /// Tells if the point belongs to the persisted chain, points older than
+    /// the oldest breadcrumb are assumed to belong to it
+    fn is_on_chain(&self, point: &Point) -> bool {
+        match self.persisted.back() {
+            Some(oldest) if point.slot_or_default() < oldest.slot_or_default() => true,
+            Some(_) => self.persisted.contains(point),
+            None => false,
+        }
+    }
+
+    /// Point the backend has to roll back to before applying a block that
+    /// isn't on the persisted chain
+    fn fork_point(&self, point: &Point) -> Option<Point> {
+        let slot = point.slot_or_default();
+
+        self.common.clone().or_else(|| {
+            self.persisted
+                .iter()
+                .find(|x| x.slot_or_default() < slot)
+                .cloned()
+        })
+    }
+
+    /// Next event the backend has to process out of the received one, `None`
+    /// if it was already persisted
+    fn filter(&mut self, unit: ChainEvent) -> Option<ChainEvent> {
+        let head = match self.persisted.front() {
+            Some(x) => x.clone(),
+            None => return Some(unit),
+        };
+
+        // a compensation carries its own rollback, which has to run even when
+        // the point is on the persisted chain
+        if let ChainEvent::Apply(_, Record::Compensation(_)) = &unit {
+            self.persisted.clear();
+            return Some(unit);
+        }
+
+        if self.is_on_chain(unit.point()) {
+            if *unit.point() == head {
+                self.persisted.clear();
+            }
+
+            self.common = Some(unit.point().clone());
+            return None;
+        }
+
+        let fork = self.fork_point(unit.point());
+        self.persisted.clear();
+
+        match (unit, fork) {
+            // the stream left the persisted chain, the blocks applied after
+            // the fork point are undone before moving on
+            (unit @ ChainEvent::Apply(..), Some(fork)) if fork != head => {
+                self.pending = Some(unit);
+                Some(ChainEvent::Reset(fork))
+            }
+            // resets go through the journal rollback as usual
+            (unit, _) => Some(unit),
+        }
+    }
+}
+/// Latest point of a cursor persisted as JSON breadcrumbs
+///
+/// Backends write the cursor in the same transaction as the data of each
+/// block, so this is the last point whose data was committed.
+pub fn persisted_point(breadcrumbs: Option<&str>) -> Result<Option<Point>, Error> {
+    let breadcrumbs: Breadcrumbs = match breadcrumbs {
+        Some(x) => serde_json::from_str(x).map_err(Error::storage)?,
+        None => return Ok(None),
+    };
+
+    breadcrumbs
+        .into_iter()
+        .next()
+        .map(|x| x.try_into())
+        .transpose()
+}
+
+/// Waits for the next event that the backend hasn't persisted yet
+pub async fn recv_pending(
+    input: &mut StorageInputPort,
+    catch_up: &mut CatchUp,
+) -> Result<WorkSchedule<ChainEvent>, WorkerError> {
+    if let Some(x) = catch_up.pending.take() {
+        return Ok(WorkSchedule::Unit(x));
+    }
+
+    loop {
+        match recv_or_done(input).await? {
+            WorkSchedule::Unit(x) => {
+                let point = x.point().clone();
+
+                match catch_up.filter(x) {
+                    Some(x) => return Ok(WorkSchedule::Unit(x)),
+                    None => debug!(?point, "skipping event already persisted"),
+                }
+            }
+            x => return Ok(x),
+        }
+    }
+}
+
 pub enum Bootstrapper {
-    Redis(redis::Stage),
+    Redis(Box<redis::Stage>),
+
+    #[cfg(feature = "postgres")]
+    Postgres(postgres::Stage),
+
+    #[cfg(feature = "sqlite")]
+    Sqlite(sqlite::Stage),
+
+    #[cfg(feature = "elastic")]
+    Elastic(elastic::Stage),
 }
 
 impl StageBootstrapper for Bootstrapper {
@@ -17,12 +175,30 @@ impl StageBootstrapper for Bootstrapper {
     fn connect_input(&mut self, adapter: InputAdapter) {
         match self {
             Bootstrapper::Redis(p) => p.input.connect(adapter),
+
+            #[cfg(feature = "postgres")]
+            Bootstrapper::Postgres(p) => p.input.connect(adapter),
+
+            #[cfg(feature = "sqlite")]
+            Bootstrapper::Sqlite(p) => p.input.connect(adapter),
+
+            #[cfg(feature = "elastic")]
+            Bootstrapper::Elastic(p) => p.input.connect(adapter),
         }
     }
 
     fn spawn(self, policy: gasket::runtime::Policy) -> Tether {
         match self {
-            Bootstrapper::Redis(s) => gasket::runtime::spawn_stage(s, policy),
+            Bootstrapper::Redis(s) => gasket::runtime::spawn_stage(*s, policy),
+
+            #[cfg(feature = "postgres")]
+            Bootstrapper::Postgres(s) => gasket::runtime::spawn_stage(s, policy),
+
+            #[cfg(feature = "sqlite")]
+            Bootstrapper::Sqlite(s) => gasket::runtime::spawn_stage(s, policy),
+
+            #[cfg(feature = "elastic")]
+            Bootstrapper::Elastic(s) => gasket::runtime::spawn_stage(s, policy),
         }
     }
 }
@@ -31,12 +207,141 @@ impl StageBootstrapper for Bootstrapper {
 #[serde(tag = "type")]
 pub enum Config {
     Redis(redis::Config),
+
+    #[cfg(feature = "postgres")]
+    Postgres(postgres::Config),
+
+    #[cfg(feature = "sqlite")]
+    Sqlite(sqlite::Config),
+
+    #[cfg(feature = "elastic")]
+    Elastic(elastic::Config),
 }
 
 impl Config {
     pub fn bootstrapper(self, ctx: &Context) -> Result<Bootstrapper, Error> {
         match self {
-            Config::Redis(c) => Ok(Bootstrapper::Redis(c.bootstrapper(ctx)?)),
+            Config::Redis(c) => Ok(Bootstrapper::Redis(Box::new(c.bootstrapper(ctx)?))),
+
+            #[cfg(feature = "postgres")]
+            Config::Postgres(c) => Ok(Bootstrapper::Postgres(c.bootstrapper(ctx)?)),
+
+            #[cfg(feature = "sqlite")]
+            Config::Sqlite(c) => Ok(Bootstrapper::Sqlite(c.bootstrapper(ctx)?)),
+
+            #[cfg(feature = "elastic")]
+            Config::Elastic(c) => Ok(Bootstrapper::Elastic(c.bootstrapper(ctx)?)),
+        }
+    }
+
+    /// Loads the cursor persisted by the storage backend during a previous run
+    pub fn load_cursor(&self) -> Result<Cursor, Error> {
+        match self {
+            Config::Redis(c) => c.load_cursor(),
+
+            #[cfg(feature = "postgres")]
+            Config::Postgres(c) => c.load_cursor(),
+
+            #[cfg(feature = "sqlite")]
+            Config::Sqlite(c) => c.load_cursor(),
+
+            #[cfg(feature = "elastic")]
+            Config::Elastic(c) => c.load_cursor(),
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use pallas::network::miniprotocols::Point;
+
+    use super::CatchUp;
+    use crate::framework::{ChainEvent, Cursor, Record};
+
+    fn point(slot: u64, fork: u8) -> Point {
+        Point::Specific(slot, vec![fork; 32])
+    }
+
+    fn apply(slot: u64, fork: u8) -> ChainEvent {
+        ChainEvent::Apply(point(slot, fork), Record::CRDTCommand(vec![]))
+    }
+
+    /// Backend that persisted slots 10 to 14 of fork 0
+    fn catch_up() -> CatchUp {
+        let state = (10..15).rev().map(|x| point(x, 0)).collect();
+        CatchUp::new(&Cursor::new(state))
+    }
+
+    fn delivered(catch_up: &mut CatchUp, events: Vec<ChainEvent>) -> Vec<(bool, Point)> {
+        let mut out = vec![];
+
+        for event in events {
+            let next = catch_up.filter(event);
+            out.extend(next.into_iter().chain(catch_up.pending.take()));
+        }
+
+        out.into_iter()
+            .map(|x| (matches!(x, ChainEvent::Apply(..)), x.point().clone()))
+            .collect()
+    }
+
+    #[test]
+    fn skips_persisted_chain() {
+        let events = vec![
+            ChainEvent::Reset(point(8, 0)),
+            apply(9, 0),
+            apply(10, 0),
+            apply(14, 0),
+            apply(15, 0),
+        ];
+
+        let out = delivered(&mut catch_up(), events);
+        assert_eq!(out, vec![(true, point(15, 0))]);
+    }
+
+    #[test]
+    fn rolls_back_to_fork_point() {
+        let events = vec![
+            ChainEvent::Reset(point(10, 0)),
+            apply(11, 0),
+            apply(12, 1),
+            apply(13, 1),
+        ];
+
+        let out = delivered(&mut catch_up(), events);
+
+        assert_eq!(
+            out,
+            vec![
+                (false, point(11, 0)),
+                (true, point(12, 1)),
+                (true, point(13, 1)),
+            ]
+        );
+    }
+
+    #[test]
+    fn passes_resets_off_persisted_chain() {
+        let events = vec![ChainEvent::Reset(point(12, 1)), apply(13, 1)];
+
+        let out = delivered(&mut catch_up(), events);
+        assert_eq!(out, vec![(false, point(12, 1)), (true, point(13, 1))]);
+    }
+
+    #[test]
+    fn passes_compensation_on_persisted_chain() {
+        let compensation = ChainEvent::Apply(point(12, 0), Record::Compensation(vec![]));
+        let events = vec![apply(11, 0), compensation, apply(13, 1)];
+
+        let out = delivered(&mut catch_up(), events);
+        assert_eq!(out, vec![(true, point(12, 0)), (true, point(13, 1))]);
+    }
+
+    #[test]
+    fn passes_everything_without_cursor() {
+        let mut catch_up = CatchUp::new(&Cursor::new(Default::default()));
+
+        let out = delivered(&mut catch_up, vec![apply(1, 0)]);
+        assert_eq!(out, vec![(true, point(1, 0))]);
+    }
+}
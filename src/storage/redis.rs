@@ -1,146 +1,1253 @@
 use gasket::framework::*;
+use pallas::{ledger::traverse::wellknown::GenesisValues, network::miniprotocols::Point};
 use r2d2_redis::{
     r2d2::{self, Pool},
-    redis::{self, Commands, ToRedisArgs},
+    redis::{
+        self, Commands, Connection, ConnectionAddr, ConnectionInfo, IntoConnectionInfo, ToRedisArgs,
+    },
     RedisConnectionManager,
 };
-use serde::Deserialize;
-use std::ops::DerefMut;
-use tracing::debug;
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::{HashMap, HashSet},
+    ops::DerefMut,
+    time::Duration,
+};
+use tokio::time::Instant;
+use tracing::{debug, info, warn};
+
+use crate::{
+    crosscut::time::{is_far_from_tip, DEFAULT_TIP_DISTANCE},
+    framework::*,
+};
+
+use super::{
+    journal::{first_captures, warn_if_exhausted, JournalEntry, DEFAULT_JOURNAL_DEPTH},
+    persisted_point, recv_pending, CatchUp,
+};
+
+const DEFAULT_JOURNAL_KEY: &str = "_scrolls.journal";
+
+const DEFAULT_CURSOR_KEY: &str = "_scrolls.cursor";
+
+const DEFAULT_BATCH_BLOCKS: usize = 100;
+
+const DEFAULT_BATCH_COMMANDS: usize = 50_000;
+
+const DEFAULT_BATCH_DELAY_MS: u64 = 5000;
+
+// removes a member from either a plain hash or a RedisJSON document
+const HASH_UNSET_SCRIPT: &str = r#"
+if redis.call('TYPE', KEYS[1]).ok == 'ReJSON-RL' then
+    return redis.call('JSON.DEL', KEYS[1], ARGV[2])
+end
+return redis.call('HDEL', KEYS[1], ARGV[1])
+"#;
+
+// adds a member to a two-phase set unless it was already removed, keeping
+// the optional live view in sync
+const TWO_PHASE_ADD_SCRIPT: &str = r#"
+if redis.call('SISMEMBER', KEYS[2], ARGV[1]) == 1 then
+    return 0
+end
+redis.call('SADD', KEYS[1], ARGV[1])
+if KEYS[3] then
+    redis.call('SADD', KEYS[3], ARGV[1])
+end
+return 1
+"#;
+
+/// How `Value::Json` payloads are written to Redis
+#[derive(Deserialize, Clone, Copy, Default, PartialEq, Eq)]
+pub enum JsonMode {
+    /// Serialized as a plain string value
+    #[default]
+    String,
+
+    /// Stored as native documents through the RedisJSON module
+    RedisJson,
+}
+
+/// JSONPath of a top-level member of a RedisJSON document
+fn json_member_path(member: &str) -> String {
+    format!("$[{}]", serde_json::Value::from(member))
+}
+
+/// Asks each sentinel in turn for the address of the current master
+fn query_sentinels(config: &SentinelConfig) -> Result<(String, u16), Error> {
+    for node in config.nodes.iter() {
+        let master = redis::Client::open(node.as_str())
+            .and_then(|client| client.get_connection())
+            .and_then(|mut conn| {
+                redis::cmd("SENTINEL")
+                    .arg("get-master-addr-by-name")
+                    .arg(&config.master_name)
+                    .query::<Option<(String, u16)>>(&mut conn)
+            });
+
+        match master {
+            Ok(Some(addr)) => return Ok(addr),
+            Ok(None) => warn!(node, "sentinel doesn't monitor the master"),
+            Err(err) => warn!(node, %err, "can't query sentinel"),
+        }
+    }
+
+    Err(Error::storage(format!(
+        "no sentinel could resolve master {}",
+        config.master_name
+    )))
+}
+
+/// Finds the master serving a hash slot within a `CLUSTER SLOTS` reply
+fn find_slot_master(ranges: &[redis::Value], slot: i64) -> Option<(String, u16)> {
+    ranges.iter().find_map(|range| {
+        let items = match range {
+            redis::Value::Bulk(x) if x.len() >= 3 => x,
+            _ => return None,
+        };
+
+        let start: i64 = redis::from_redis_value(&items[0]).ok()?;
+        let end: i64 = redis::from_redis_value(&items[1]).ok()?;
+
+        if slot < start || slot > end {
+            return None;
+        }
+
+        match &items[2] {
+            redis::Value::Bulk(node) if node.len() >= 2 => Some((
+                redis::from_redis_value(&node[0]).ok()?,
+                redis::from_redis_value(&node[1]).ok()?,
+            )),
+            _ => None,
+        }
+    })
+}
+
+/// Asks each seed node of the cluster for the master serving the hash tag
+fn query_cluster(seeds: &[String], hash_tag: &str) -> Result<(String, u16), Error> {
+    for seed in seeds {
+        let master = redis::Client::open(seed.as_str())
+            .and_then(|client| client.get_connection())
+            .and_then(|mut conn| {
+                let slot: i64 = redis::cmd("CLUSTER")
+                    .arg("KEYSLOT")
+                    .arg(hash_tag)
+                    .query(&mut conn)?;
+
+                let ranges: Vec<redis::Value> =
+                    redis::cmd("CLUSTER").arg("SLOTS").query(&mut conn)?;
+
+                Ok(find_slot_master(&ranges, slot))
+            });
+
+        match master {
+            Ok(Some(addr)) => return Ok(addr),
+            Ok(None) => warn!(seed, hash_tag, "cluster slot has no master"),
+            Err(err) => warn!(seed, %err, "can't query cluster node"),
+        }
+    }
+
+    Err(Error::storage(format!(
+        "no cluster node could resolve hash tag {}",
+        hash_tag
+    )))
+}
+
+/// Restarts the worker when a cluster node redirects a write, so that the
+/// node serving the hash tag is resolved again, other errors are retried
+fn or_redirect<T>(result: redis::RedisResult<T>) -> Result<T, WorkerError> {
+    match result {
+        Err(err) if matches!(err.kind(), redis::ErrorKind::Moved | redis::ErrorKind::Ask) => {
+            warn!(%err, "hash tag slot served by another cluster node");
+            Err(WorkerError::Restart)
+        }
+        x => x.or_retry(),
+    }
+}
+
+/// Prepends a hash tag to every key of the command, pinning it to one slot
+fn tag_command(tag: &str, command: &model::CRDTCommand) -> model::CRDTCommand {
+    use model::CRDTCommand::*;
+
+    let k = |key: &str| format!("{{{}}}{}", tag, key);
+
+    match command.clone() {
+        SetAdd(key, member) => SetAdd(k(&key), member),
+        SetRemove(key, member) => SetRemove(k(&key), member),
+        SortedSetAdd(key, member, delta) => SortedSetAdd(k(&key), member, delta),
+        SortedSetRemove(key, member, delta) => SortedSetRemove(k(&key), member, delta),
+        TwoPhaseSetAdd(key, member) => TwoPhaseSetAdd(k(&key), member),
+        TwoPhaseSetRemove(key, member) => TwoPhaseSetRemove(k(&key), member),
+        GrowOnlySetAdd(key, member) => GrowOnlySetAdd(k(&key), member),
+        LastWriteWins(key, value, ts) => LastWriteWins(k(&key), value, ts),
+        AnyWriteWins(key, value) => AnyWriteWins(k(&key), value),
+        PNCounter(key, delta) => PNCounter(k(&key), delta),
+        HashCounter(key, member, delta) => HashCounter(k(&key), member, delta),
+        HashSetValue(key, member, value) => HashSetValue(k(&key), member, value),
+        HashUnsetKey(key, member) => HashUnsetKey(k(&key), member),
+    }
+}
+
+/// Point of a serialized journal entry
+fn entry_point(entry: &str) -> Result<Point, Error> {
+    let entry: JournalEntry<UndoOp> = serde_json::from_str(entry).map_err(Error::storage)?;
+    entry.point.try_into()
+}
+
+/// Integer held by a slot after an increment, `None` if it isn't one
+fn increased(value: &Option<Vec<u8>>, delta: i64) -> Option<Vec<u8>> {
+    let value: i64 = match value {
+        Some(x) => std::str::from_utf8(x).ok()?.parse().ok()?,
+        None => 0,
+    };
+
+    let value = value.checked_add(delta)?;
+
+    Some(value.to_string().into_bytes())
+}
+
+/// Value held by a Redis slot before a block touched it
+///
+/// Restoring every pre-image captured for a block is the inverse of all the
+/// CRDT commands executed for that block.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+enum UndoOp {
+    SetMember(String, String, bool),
+    SortedSetMember(String, Vec<u8>, Option<f64>),
+    Key(String, Option<Vec<u8>>),
+    HashMember(String, String, Option<Vec<u8>>),
+    Dump(String, Option<Vec<u8>>),
+}
+
+impl UndoOp {
+    /// Slot touched by the command, its pre-image is filled once read
+    fn probe(command: &model::CRDTCommand, json_mode: JsonMode) -> Self {
+        if json_mode == JsonMode::RedisJson {
+            // module types can't be read with plain commands, the whole key is
+            // dumped instead
+            match command {
+                model::CRDTCommand::AnyWriteWins(key, model::Value::Json(_))
+                | model::CRDTCommand::HashSetValue(key, _, model::Value::Json(_))
+                | model::CRDTCommand::HashUnsetKey(key, _) => {
+                    return UndoOp::Dump(key.clone(), None);
+                }
+                _ => (),
+            };
+        }
+
+        match command {
+            model::CRDTCommand::GrowOnlySetAdd(key, member)
+            | model::CRDTCommand::TwoPhaseSetAdd(key, member)
+            | model::CRDTCommand::SetAdd(key, member)
+            | model::CRDTCommand::SetRemove(key, member) => {
+                UndoOp::SetMember(key.clone(), member.clone(), false)
+            }
+            model::CRDTCommand::TwoPhaseSetRemove(key, member) => {
+                UndoOp::SetMember(format!("{}.ts", key), member.clone(), false)
+            }
+            model::CRDTCommand::LastWriteWins(key, value, _) => {
+                UndoOp::SortedSetMember(key.clone(), value.to_redis_args().concat(), None)
+            }
+            model::CRDTCommand::SortedSetAdd(key, member, _)
+            | model::CRDTCommand::SortedSetRemove(key, member, _) => {
+                UndoOp::SortedSetMember(key.clone(), member.as_bytes().to_vec(), None)
+            }
+            model::CRDTCommand::AnyWriteWins(key, _) | model::CRDTCommand::PNCounter(key, _) => {
+                UndoOp::Key(key.clone(), None)
+            }
+            model::CRDTCommand::HashSetValue(key, member, _)
+            | model::CRDTCommand::HashCounter(key, member, _)
+            | model::CRDTCommand::HashUnsetKey(key, member) => {
+                UndoOp::HashMember(key.clone(), member.clone(), None)
+            }
+        }
+    }
+
+    /// Queues the read of the current value of the slot
+    fn read(&self, pipe: &mut redis::Pipeline) {
+        match self {
+            UndoOp::SetMember(key, member, _) => pipe.sismember(key, member),
+            UndoOp::SortedSetMember(key, member, _) => pipe.zscore(key, member.as_slice()),
+            UndoOp::Key(key, _) => pipe.get(key),
+            UndoOp::HashMember(key, member, _) => pipe.hget(key, member),
+            UndoOp::Dump(key, _) => pipe.cmd("DUMP").arg(key),
+        };
+    }
+
+    /// Fills the pre-image with the reply of its read
+    fn fill(self, reply: &redis::Value) -> redis::RedisResult<Self> {
+        let op = match self {
+            UndoOp::SetMember(key, member, _) => {
+                UndoOp::SetMember(key, member, redis::from_redis_value(reply)?)
+            }
+            UndoOp::SortedSetMember(key, member, _) => {
+                UndoOp::SortedSetMember(key, member, redis::from_redis_value(reply)?)
+            }
+            UndoOp::Key(key, _) => UndoOp::Key(key, redis::from_redis_value(reply)?),
+            UndoOp::HashMember(key, member, _) => {
+                UndoOp::HashMember(key, member, redis::from_redis_value(reply)?)
+            }
+            UndoOp::Dump(key, _) => UndoOp::Dump(key, redis::from_redis_value(reply)?),
+        };
+
+        Ok(op)
+    }
+
+    /// Identifies the Redis slot this pre-image belongs to
+    fn target(&self) -> (&str, &[u8]) {
+        match self {
+            UndoOp::SetMember(key, member, _) => (key, member.as_bytes()),
+            UndoOp::SortedSetMember(key, member, _) => (key, member),
+            UndoOp::Key(key, _) => (key, &[]),
+            UndoOp::HashMember(key, member, _) => (key, member.as_bytes()),
+            UndoOp::Dump(key, _) => (key, &[]),
+        }
+    }
+
+    /// Pre-image of the slot once the command runs, `None` when it can't be
+    /// told without reading it back
+    ///
+    /// `tombstoned` tells if the member of a two-phase addition was removed.
+    fn after(&self, command: &model::CRDTCommand, tombstoned: bool) -> Option<Self> {
+        let op = match (self, command) {
+            (
+                UndoOp::SetMember(slot, member, present),
+                model::CRDTCommand::TwoPhaseSetAdd(key, _),
+            ) => {
+                // tombstones aren't touched by additions
+                let present = match *slot == format!("{}.ts", key) {
+                    true => *present,
+                    false => *present || !tombstoned,
+                };
+
+                UndoOp::SetMember(slot.clone(), member.clone(), present)
+            }
+            (UndoOp::SetMember(slot, member, _), model::CRDTCommand::TwoPhaseSetRemove(key, _)) => {
+                // either the tombstone or the live view
+                let present = *slot == format!("{}.ts", key);
+                UndoOp::SetMember(slot.clone(), member.clone(), present)
+            }
+            (
+                UndoOp::SetMember(key, member, _),
+                model::CRDTCommand::GrowOnlySetAdd(..) | model::CRDTCommand::SetAdd(..),
+            ) => UndoOp::SetMember(key.clone(), member.clone(), true),
+            (UndoOp::SetMember(key, member, _), model::CRDTCommand::SetRemove(..)) => {
+                UndoOp::SetMember(key.clone(), member.clone(), false)
+            }
+            (
+                UndoOp::SortedSetMember(key, member, _),
+                model::CRDTCommand::LastWriteWins(_, _, slot),
+            ) => UndoOp::SortedSetMember(key.clone(), member.clone(), Some(*slot as f64)),
+            (
+                UndoOp::SortedSetMember(key, member, score),
+                model::CRDTCommand::SortedSetAdd(_, _, delta),
+            ) => UndoOp::SortedSetMember(
+                key.clone(),
+                member.clone(),
+                Some(score.unwrap_or_default() + *delta as f64),
+            ),
+            (
+                UndoOp::SortedSetMember(key, member, score),
+                model::CRDTCommand::SortedSetRemove(_, _, delta),
+            ) => {
+                let score = Some(score.unwrap_or_default() + *delta as f64);
+                UndoOp::SortedSetMember(key.clone(), member.clone(), score.filter(|x| *x != 0.0))
+            }
+            (UndoOp::Key(key, _), model::CRDTCommand::AnyWriteWins(_, value)) => {
+                UndoOp::Key(key.clone(), Some(value.to_redis_args().concat()))
+            }
+            (UndoOp::Key(key, value), model::CRDTCommand::PNCounter(_, delta)) => {
+                UndoOp::Key(key.clone(), Some(increased(value, *delta)?))
+            }
+            (UndoOp::HashMember(key, member, _), model::CRDTCommand::HashSetValue(_, _, value)) => {
+                UndoOp::HashMember(
+                    key.clone(),
+                    member.clone(),
+                    Some(value.to_redis_args().concat()),
+                )
+            }
+            (
+                UndoOp::HashMember(key, member, value),
+                model::CRDTCommand::HashCounter(_, _, delta),
+            ) => UndoOp::HashMember(key.clone(), member.clone(), Some(increased(value, *delta)?)),
+            (UndoOp::HashMember(key, member, _), model::CRDTCommand::HashUnsetKey(..)) => {
+                UndoOp::HashMember(key.clone(), member.clone(), None)
+            }
+            _ => return None,
+        };
+
+        Some(op)
+    }
+
+    fn restore(&self, pipe: &mut redis::Pipeline) {
+        match self {
+            UndoOp::SetMember(key, member, true) => pipe.sadd(key, member),
+            UndoOp::SetMember(key, member, false) => pipe.srem(key, member),
+            UndoOp::SortedSetMember(key, member, Some(score)) => {
+                pipe.zadd(key, member.as_slice(), *score)
+            }
+            UndoOp::SortedSetMember(key, member, None) => pipe.zrem(key, member.as_slice()),
+            UndoOp::Key(key, Some(value)) => pipe.set(key, value.as_slice()),
+            UndoOp::Key(key, None) => pipe.del(key),
+            UndoOp::HashMember(key, member, Some(value)) => {
+                pipe.hset(key, member, value.as_slice())
+            }
+            UndoOp::HashMember(key, member, None) => pipe.hdel(key, member),
+            UndoOp::Dump(key, Some(dump)) => pipe
+                .cmd("RESTORE")
+                .arg(key)
+                .arg(0)
+                .arg(dump.as_slice())
+                .arg("REPLACE"),
+            UndoOp::Dump(key, None) => pipe.del(key),
+        }
+        .ignore();
+    }
+}
+
+/// Pre-images of the slots touched by a batch, by key and member
+type Images = HashMap<String, HashMap<Vec<u8>, Option<UndoOp>>>;
+
+/// Redis slots touched by each CRDT command, which depend on the config
+#[derive(Clone, Copy, Default)]
+struct Layout {
+    json_mode: JsonMode,
+    live_two_phase_sets: bool,
+}
+
+impl Layout {
+    /// Slots touched by the command, plus the tombstone that decides a
+    /// two-phase addition
+    fn probes(&self, command: &model::CRDTCommand) -> Vec<UndoOp> {
+        let mut probes = vec![UndoOp::probe(command, self.json_mode)];
+
+        if let model::CRDTCommand::TwoPhaseSetAdd(key, member) = command {
+            probes.push(UndoOp::SetMember(
+                format!("{}.ts", key),
+                member.clone(),
+                false,
+            ));
+        }
+
+        if let model::CRDTCommand::TwoPhaseSetAdd(key, member)
+        | model::CRDTCommand::TwoPhaseSetRemove(key, member) = command
+        {
+            if self.live_two_phase_sets {
+                probes.push(UndoOp::SetMember(
+                    format!("{}.live", key),
+                    member.clone(),
+                    false,
+                ));
+            }
+        }
+
+        probes
+    }
+
+    /// Moves the pre-images of the slots touched by the command past it
+    fn advance(&self, images: &mut Images, command: &model::CRDTCommand) {
+        let tombstoned = match command {
+            model::CRDTCommand::TwoPhaseSetAdd(key, member) => {
+                match image(images, &format!("{}.ts", key), member.as_bytes()) {
+                    Some(UndoOp::SetMember(_, _, x)) => Some(*x),
+                    _ => None,
+                }
+            }
+            _ => Some(false),
+        };
+
+        for probe in self.probes(command) {
+            let (key, member) = probe.target();
+
+            let next = match (image(images, key, member), tombstoned) {
+                (Some(x), Some(tombstoned)) => x.after(command, tombstoned),
+                _ => None,
+            };
+
+            images
+                .entry(key.to_owned())
+                .or_default()
+                .insert(member.to_vec(), next);
+        }
+
+        // the dangling scores of every member go along with the decremented one
+        if let model::CRDTCommand::SortedSetRemove(key, _, _) = command {
+            for x in images.get_mut(key).into_iter().flat_map(|x| x.values_mut()) {
+                if let Some(UndoOp::SortedSetMember(_, _, score)) = x {
+                    if *score == Some(0.0) {
+                        *score = None;
+                    }
+                }
+            }
+        }
+    }
+
+    /// Splits the undo data of consecutive blocks into one entry per block
+    ///
+    /// The pre-images read before the first block are moved forward through
+    /// the commands of each block. Entries stop at the first block with a
+    /// pre-image that can't be derived that way, that block and the ones
+    /// after it need a new read.
+    fn block_entries(
+        &self,
+        blocks: &[(Point, usize)],
+        commands: &[model::CRDTCommand],
+        read: Vec<UndoOp>,
+    ) -> Vec<JournalEntry<UndoOp>> {
+        let mut images = Images::new();
+
+        for op in read {
+            let (key, member) = op.target();
+            let (key, member) = (key.to_owned(), member.to_vec());
+            images.entry(key).or_default().insert(member, Some(op));
+        }
+
+        let mut entries = vec![];
+        let mut commands = commands;
+
+        for (point, count) in blocks {
+            let (block, rest) = commands.split_at(*count);
+            commands = rest;
+
+            let probes = first_captures(block.iter().flat_map(|x| self.probes(x)), slot_of);
+
+            let undo: Option<Vec<_>> = probes
+                .iter()
+                .map(|x| {
+                    let (key, member) = x.target();
+                    image(&images, key, member).cloned()
+                })
+                .collect();
+
+            let undo = match undo {
+                Some(x) => x,
+                None => break,
+            };
+
+            for command in block {
+                self.advance(&mut images, command);
+            }
+
+            entries.push(JournalEntry::new(point, undo));
+        }
+
+        entries
+    }
+
+    /// Queues the writes of the commands, each with its reply ignored
+    fn queue_commands(&self, pipe: &mut redis::Pipeline, commands: &[model::CRDTCommand]) {
+        for command in commands {
+            match command {
+                model::CRDTCommand::GrowOnlySetAdd(key, value) => {
+                    pipe.sadd(key, value);
+                }
+                model::CRDTCommand::TwoPhaseSetAdd(key, value) => {
+                    debug!(key, value, "adding to 2-phase");
+
+                    let mut keys = vec![key.clone(), format!("{}.ts", key)];
+
+                    if self.live_two_phase_sets {
+                        keys.push(format!("{}.live", key));
+                    }
+
+                    pipe.cmd("EVAL")
+                        .arg(TWO_PHASE_ADD_SCRIPT)
+                        .arg(keys.len())
+                        .arg(keys)
+                        .arg(value);
+                }
+                model::CRDTCommand::TwoPhaseSetRemove(key, value) => {
+                    debug!(key, value, "removing from 2-phase");
+
+                    pipe.sadd(format!("{}.ts", key), value);
+
+                    if self.live_two_phase_sets {
+                        pipe.ignore().srem(format!("{}.live", key), value);
+                    }
+                }
+                model::CRDTCommand::SetAdd(key, value) => {
+                    debug!(key, value, "adding");
+
+                    pipe.sadd(key, value);
+                }
+                model::CRDTCommand::SetRemove(key, value) => {
+                    debug!(key, value, "removing");
+
+                    pipe.srem(key, value);
+                }
+                model::CRDTCommand::LastWriteWins(key, value, slot) => {
+                    debug!(key, slot, "last write");
+
+                    pipe.zadd(key, value.clone(), *slot);
+                }
+                model::CRDTCommand::SortedSetAdd(key, value, delta) => {
+                    debug!(key, value, delta, "sorted set add");
+
+                    pipe.zincr(key, value, *delta);
+                }
+                model::CRDTCommand::SortedSetRemove(key, value, delta) => {
+                    debug!(key, value, delta, "sorted set remove");
+
+                    pipe.zincr(key, value, *delta).ignore();
+
+                    // removal of dangling scores  (aka garage collection)
+                    pipe.zrembyscore(key, 0, 0);
+                }
+                model::CRDTCommand::AnyWriteWins(key, value) => {
+                    debug!(key, "overwrite");
+
+                    match (self.json_mode, value) {
+                        (JsonMode::RedisJson, model::Value::Json(x)) => {
+                            pipe.cmd("JSON.SET").arg(key).arg("$").arg(x.to_string());
+                        }
+                        _ => {
+                            pipe.set(key, value.clone());
+                        }
+                    }
+                }
+                model::CRDTCommand::PNCounter(key, value) => {
+                    debug!(key, value, "increasing counter");
+
+                    pipe.incr(key, *value);
+                }
+                model::CRDTCommand::HashSetValue(key, member, value) => {
+                    debug!(key, member, "setting hash");
+
+                    match (self.json_mode, value) {
+                        (JsonMode::RedisJson, model::Value::Json(x)) => {
+                            let patch = serde_json::json!({ member: x });
+
+                            pipe.cmd("JSON.MERGE")
+                                .arg(key)
+                                .arg("$")
+                                .arg(patch.to_string());
+                        }
+                        _ => {
+                            pipe.hset(key, member, value.clone());
+                        }
+                    }
+                }
+                model::CRDTCommand::HashCounter(key, member, delta) => {
+                    debug!(key, member, delta, "increasing hash");
+
+                    pipe.hincr(key, member, *delta);
+                }
+                model::CRDTCommand::HashUnsetKey(key, member) => {
+                    debug!(key, member, "deleting hash");
+
+                    match self.json_mode {
+                        JsonMode::RedisJson => {
+                            pipe.cmd("EVAL")
+                                .arg(HASH_UNSET_SCRIPT)
+                                .arg(1)
+                                .arg(key)
+                                .arg(member)
+                                .arg(json_member_path(member));
+                        }
+                        JsonMode::String => {
+                            pipe.hdel(key, member);
+                        }
+                    }
+                }
+            }
+
+            pipe.ignore();
+        }
+    }
+}
+
+fn slot_of(op: &UndoOp) -> (String, Vec<u8>) {
+    let (key, member) = op.target();
+    (key.to_owned(), member.to_vec())
+}
+
+/// Tells if pre-images and probes share a key dumped as a whole by either
+fn overlaps_dump(undo: &[UndoOp], probes: &[UndoOp]) -> bool {
+    let dumped = |ops: &[UndoOp]| -> HashSet<String> {
+        ops.iter()
+            .filter(|x| matches!(x, UndoOp::Dump(..)))
+            .map(|x| x.target().0.to_owned())
+            .collect()
+    };
+
+    let keys = |ops: &[UndoOp]| -> HashSet<String> {
+        ops.iter().map(|x| x.target().0.to_owned()).collect()
+    };
+
+    !dumped(undo).is_disjoint(&keys(probes)) || !dumped(probes).is_disjoint(&keys(undo))
+}
+
+fn image<'a>(images: &'a Images, key: &str, member: &[u8]) -> Option<&'a UndoOp> {
+    images.get(key)?.get(member)?.as_ref()
+}
+
+/// Blocks waiting to be committed within the same transaction
+#[derive(Default)]
+struct Batch {
+    /// Point of each block along with its number of commands
+    blocks: Vec<(Point, usize)>,
+    commands: Vec<model::CRDTCommand>,
+    deadline: Option<Instant>,
+
+    /// Keys touched by the blocks, and the ones dumped as a whole
+    keys: HashSet<String>,
+    dumped: HashSet<String>,
+}
+
+impl Batch {
+    /// Removes the blocks at or before the given point, returning their points
+    fn drain_until(&mut self, point: &Point) -> Vec<Point> {
+        let count = self
+            .blocks
+            .iter()
+            .take_while(|(x, _)| x.slot_or_default() <= point.slot_or_default())
+            .count();
+
+        self.drain(count).into_iter().map(|(x, _)| x).collect()
+    }
+
+    /// Removes the first blocks along with their commands
+    fn drain(&mut self, count: usize) -> Vec<(Point, usize)> {
+        let drained: Vec<_> = self.blocks.drain(..count).collect();
+
+        let commands: usize = drained.iter().map(|(_, x)| x).sum();
+        self.commands.drain(..commands);
+
+        drained
+    }
+
+    /// Tells if the slots of a block overlap a key dumped as a whole by
+    /// another block of the batch
+    ///
+    /// Dumps can't be moved forward through commands, so the pre-images of
+    /// such a block can only be read once the batch is committed.
+    fn overlaps_dump(&self, probes: &[UndoOp]) -> bool {
+        probes.iter().any(|x| {
+            let (key, _) = x.target();
+            let dump = matches!(x, UndoOp::Dump(..));
+
+            self.dumped.contains(key) || (dump && self.keys.contains(key))
+        })
+    }
+}
+
+/// Batching limits, resolved from the config
+struct BatchLimits {
+    max_blocks: usize,
+    max_commands: usize,
+    max_delay: Duration,
+    tip_distance: u64,
+}
+
+/// Either a chain event or the expiration of the pending batch
+pub enum Unit {
+    Event(ChainEvent),
+    Flush,
+}
+
+fn event_unit(schedule: WorkSchedule<ChainEvent>) -> WorkSchedule<Unit> {
+    match schedule {
+        WorkSchedule::Unit(x) => WorkSchedule::Unit(Unit::Event(x)),
+        WorkSchedule::Idle => WorkSchedule::Idle,
+        WorkSchedule::Done => WorkSchedule::Done,
+    }
+}
+
+pub struct Worker {
+    pool: Pool<RedisConnectionManager>,
+    journal_key: String,
+    journal_depth: usize,
+    cursor_key: String,
+    layout: Layout,
+    hash_tag: Option<String>,
+    batch_limits: Option<BatchLimits>,
+}
+
+impl Worker {
+    /// Reads the pre-images of the slots touched by the commands
+    fn read_images(
+        &self,
+        conn: &mut Connection,
+        commands: &[model::CRDTCommand],
+    ) -> Result<Vec<UndoOp>, Error> {
+        let probes = commands.iter().flat_map(|x| self.layout.probes(x));
+        let probes = first_captures(probes, slot_of);
+
+        if probes.is_empty() {
+            return Ok(probes);
+        }
+
+        let mut pipe = redis::pipe();
+
+        for op in probes.iter() {
+            op.read(&mut pipe);
+        }
+
+        let replies: Vec<redis::Value> = pipe.query(conn).map_err(Error::storage)?;
+
+        probes
+            .into_iter()
+            .zip(replies.iter())
+            .map(|(op, reply)| op.fill(reply).map_err(Error::storage))
+            .collect()
+    }
+
+    /// Tells if the block can wait in the batch instead of being committed
+    /// right away
+    ///
+    /// Blocks close to the tip are committed one by one so that they're
+    /// visible as soon as they arrive.
+    fn is_batched(&self, stage: &Stage, point: &Point) -> bool {
+        match &self.batch_limits {
+            Some(limits) => is_far_from_tip(&stage.chain, point, limits.tip_distance),
+            None => false,
+        }
+    }
+
+    fn is_full(&self, batch: &Batch) -> bool {
+        match &self.batch_limits {
+            Some(limits) => {
+                batch.blocks.len() >= limits.max_blocks
+                    || batch.commands.len() >= limits.max_commands
+            }
+            None => true,
+        }
+    }
+
+    /// Commands of a block with their keys pinned to the hash tag, if any
+    fn tag_commands(&self, commands: &[model::CRDTCommand]) -> Vec<model::CRDTCommand> {
+        match &self.hash_tag {
+            Some(tag) => commands.iter().map(|x| tag_command(tag, x)).collect(),
+            None => commands.to_vec(),
+        }
+    }
+
+    fn push_block(
+        &self,
+        stage: &mut Stage,
+        point: &Point,
+        commands: Vec<model::CRDTCommand>,
+        probes: &[UndoOp],
+    ) {
+        let batch = &mut stage.batch;
+
+        if batch.deadline.is_none() {
+            let delay = self
+                .batch_limits
+                .as_ref()
+                .map(|x| x.max_delay)
+                .unwrap_or_default();
+
+            batch.deadline = Some(Instant::now() + delay);
+        }
+
+        batch.blocks.push((point.clone(), commands.len()));
+        batch.commands.extend(commands);
+
+        for op in probes {
+            let (key, _) = op.target();
+
+            if let UndoOp::Dump(..) = op {
+                batch.dumped.insert(key.to_owned());
+            }
+
+            batch.keys.insert(key.to_owned());
+        }
+    }
+
+    /// Commits the pending blocks in pipelined transactions, along with the
+    /// undo data of each block and the resulting cursor
+    fn commit(&self, conn: &mut Connection, stage: &mut Stage) -> Result<(), WorkerError> {
+        if stage.batch.blocks.is_empty() {
+            return Ok(());
+        }
+
+        // the last applied point is persisted in the same transaction as the
+        // data, blocks up to it were already committed by a previous attempt
+        // whose reply got lost or by a replay of the source
+        let persisted: Option<String> = conn.get(&self.cursor_key).or_restart()?;
+
+        if let Some(persisted) = persisted_point(persisted.as_deref()).or_panic()? {
+            for point in stage.batch.drain_until(&persisted) {
+                warn!(?point, "skipping block already committed");
+                stage.cursor.add_breadcrumb(point);
+            }
+        }
+
+        while !stage.batch.blocks.is_empty() {
+            self.commit_run(conn, stage)?;
+        }
+
+        stage.batch = Default::default();
+
+        Ok(())
+    }
+
+    /// Commits the longest run of pending blocks whose pre-images can be
+    /// derived from a single read, within one pipelined transaction
+    fn commit_run(&self, conn: &mut Connection, stage: &mut Stage) -> Result<(), WorkerError> {
+        let batch = &stage.batch;
+
+        let read = self.read_images(conn, &batch.commands).or_restart()?;
+
+        let entries = self
+            .layout
+            .block_entries(&batch.blocks, &batch.commands, read);
+
+        // the pre-images of the first block are always read
+        if entries.is_empty() {
+            return Err(Error::message("can't capture undo data of block")).or_panic();
+        }
+
+        let run = entries.len();
+        let commands: usize = batch.blocks[..run].iter().map(|(_, x)| x).sum();
+
+        let cursor = Cursor::new(stage.cursor.clone_state());
+
+        for (point, _) in batch.blocks[..run].iter() {
+            cursor.add_breadcrumb(point.clone());
+        }
+
+        let breadcrumbs = serde_json::to_string(&cursor.breadcrumbs()).or_panic()?;
+
+        let mut pipe = redis::pipe();
+        pipe.atomic();
+
+        self.layout
+            .queue_commands(&mut pipe, &batch.commands[..commands]);
+
+        // one entry per block, so that rollbacks can land within the run
+        for entry in entries {
+            let entry = serde_json::to_string(&entry).or_panic()?;
+            pipe.lpush(&self.journal_key, entry).ignore();
+        }
+
+        pipe.ltrim(&self.journal_key, 0, self.journal_depth as isize - 1)
+            .ignore()
+            .set(&self.cursor_key, breadcrumbs)
+            .ignore();
+
+        or_redirect(pipe.query::<()>(conn))?;
+
+        let blocks = stage.batch.drain(run);
+
+        let latest = blocks.last().map(|(x, _)| x.slot_or_default());
+        debug!(blocks = blocks.len(), ?latest, "batch committed");
+
+        for (point, _) in blocks {
+            stage.cursor.add_breadcrumb(point);
+        }
+
+        stage.latest_block.set(latest.unwrap_or_default() as i64);
+
+        Ok(())
+    }
+
+    /// Restores the pre-images of the head entry of the journal and pops it,
+    /// along with the cursor it leaves on top
+    fn undo_entry(
+        &self,
+        conn: &mut Connection,
+        entry: &JournalEntry<UndoOp>,
+        breadcrumbs: &Breadcrumbs,
+    ) -> Result<(), WorkerError> {
+        let breadcrumbs = serde_json::to_string(breadcrumbs).or_panic()?;
+
+        let mut pipe = redis::pipe();
+        pipe.atomic();
+
+        for op in entry.undo.iter() {
+            op.restore(&mut pipe);
+        }
+
+        pipe.lpop(&self.journal_key)
+            .ignore()
+            .set(&self.cursor_key, &breadcrumbs)
+            .ignore();
+
+        or_redirect(pipe.query::<()>(conn))
+    }
+
+    /// Undoes the blocks after the point, then applies the commands
+    /// compensating for the rollback, if any
+    ///
+    /// Each undo step persists the cursor of the block it leaves on top, in
+    /// case the rollback is interrupted. The compensation goes in the same
+    /// transaction as the last step, so that it's never lost once the cursor
+    /// reaches the rollback point.
+    fn rollback(
+        &self,
+        conn: &mut Connection,
+        cursor: &Cursor,
+        point: &Point,
+        compensation: &[model::CRDTCommand],
+    ) -> Result<(), WorkerError> {
+        let oldest: Option<String> = conn.lindex(&self.journal_key, -1).or_restart()?;
+
+        let oldest = match oldest {
+            Some(x) => Some(entry_point(&x).or_panic()?.slot_or_default()),
+            None => None,
+        };
+
+        warn_if_exhausted(oldest, cursor, point);
+
+        let mut last = None;
+
+        loop {
+            // the head entry, along with the one that becomes the head once
+            // the block is undone
+            let head: Vec<String> = conn.lrange(&self.journal_key, 0, 1).or_restart()?;
 
-use crate::framework::*;
+            let entry: JournalEntry<UndoOp> = match head.first() {
+                Some(x) => serde_json::from_str(x).or_panic()?,
+                None => break,
+            };
 
-pub struct Worker {
-    pool: Pool<RedisConnectionManager>,
-}
+            let applied: Point = entry.point.clone().try_into().or_panic()?;
 
-#[async_trait::async_trait(?Send)]
-impl gasket::framework::Worker<Stage> for Worker {
-    async fn bootstrap(stage: &Stage) -> Result<Self, WorkerError> {
-        let manager =
-            RedisConnectionManager::new(stage.config.url.clone()).or_panic()?;
-        let pool = r2d2::Pool::builder().build(manager).or_panic()?;
+            if applied.slot_or_default() <= point.slot_or_default() {
+                break;
+            }
 
-        Ok(Self { pool })
-    }
+            let previous = match head.get(1) {
+                Some(x) => Some(entry_point(x).or_panic()?),
+                None => None,
+            };
 
-    async fn schedule(
-        &mut self,
-        stage: &mut Stage,
-    ) -> Result<WorkSchedule<ChainEvent>, WorkerError> {
-        let msg = stage.input.recv().await.or_panic()?;
-        Ok(WorkSchedule::Unit(msg.payload))
+            let previous = previous.filter(|x| x.slot_or_default() > point.slot_or_default());
+
+            if previous.is_none() && !compensation.is_empty() {
+                last = Some(entry);
+                break;
+            }
+
+            debug!(?applied, "undoing block");
+
+            let previous = previous.unwrap_or_else(|| point.clone());
+            self.undo_entry(conn, &entry, &cursor.breadcrumbs_after_rollback(&previous))?;
+        }
+
+        if compensation.is_empty() {
+            // the rollback point might precede every journal entry, cursor
+            // still needs to reflect it
+            let breadcrumbs = cursor.breadcrumbs_after_rollback(point);
+            let breadcrumbs = serde_json::to_string(&breadcrumbs).or_panic()?;
+
+            conn.set::<_, _, ()>(&self.cursor_key, &breadcrumbs)
+                .or_restart()?;
+        } else {
+            self.compensate(conn, cursor, point, last, compensation)?;
+        }
+
+        info!(?point, "rollback applied");
+
+        Ok(())
     }
 
-    async fn execute(&mut self, unit: &ChainEvent, stage: &mut Stage) -> Result<(), WorkerError> {
-        let point = unit.point().clone();
-        let record = unit.record().cloned();
+    /// Applies the commands compensating for a rollback, journaled at the
+    /// rollback point, within the transaction that undoes the last block
+    fn compensate(
+        &self,
+        conn: &mut Connection,
+        cursor: &Cursor,
+        point: &Point,
+        last: Option<JournalEntry<UndoOp>>,
+        commands: &[model::CRDTCommand],
+    ) -> Result<(), WorkerError> {
+        let breadcrumbs = cursor.breadcrumbs_after_rollback(point);
 
-        if record.is_none() {
-            return Ok(());
+        let probes: Vec<_> = commands
+            .iter()
+            .flat_map(|x| self.layout.probes(x))
+            .collect();
+
+        // the slots of a dumped key can't be told apart, such a block is
+        // undone on its own before reading the pre-images
+        let last = match last {
+            Some(entry) if overlaps_dump(&entry.undo, &probes) => {
+                self.undo_entry(conn, &entry, &breadcrumbs)?;
+                None
+            }
+            x => x,
+        };
+
+        let mut read = self.read_images(conn, commands).or_restart()?;
+
+        // slots restored by the last block hold its pre-image once undone
+        if let Some(entry) = &last {
+            for op in read.iter_mut() {
+                let restored = entry.undo.iter().find(|x| slot_of(x) == slot_of(op));
+
+                if let Some(restored) = restored {
+                    *op = restored.clone();
+                }
+            }
         }
 
-        let record = record.unwrap();
+        let entry = self
+            .layout
+            .block_entries(&[(point.clone(), commands.len())], commands, read)
+            .pop()
+            .ok_or_else(|| Error::message("can't capture undo data of compensation"))
+            .or_panic()?;
 
-        match record {
-            Record::CRDTCommand(commands) => {
-                let mut conn = self.pool.get().or_restart()?;
+        let entry = serde_json::to_string(&entry).or_panic()?;
+        let breadcrumbs = serde_json::to_string(&breadcrumbs).or_panic()?;
 
-                redis::cmd("MULTI").query(conn.deref_mut()).or_retry()?;
+        let mut pipe = redis::pipe();
+        pipe.atomic();
 
-                // TODO: add to parallel persist
-                for command in commands {
-                    match command {
-                        model::CRDTCommand::GrowOnlySetAdd(key, value) => {
-                            conn.deref_mut().sadd(key, value).or_restart()?;
-                        }
-                        model::CRDTCommand::TwoPhaseSetAdd(key, value) => {
-                            debug!(key, value, "adding to 2-phase");
+        if let Some(last) = &last {
+            debug!(point = ?last.point, "undoing block");
 
-                            conn.deref_mut().sadd(key, value).or_restart()?;
-                        }
-                        model::CRDTCommand::TwoPhaseSetRemove(key, value) => {
-                            debug!(key, value, "removing from 2-phase");
+            for op in last.undo.iter() {
+                op.restore(&mut pipe);
+            }
 
-                            conn.deref_mut()
-                                .sadd(format!("{}.ts", key), value)
-                                .or_restart()?;
-                        }
-                        model::CRDTCommand::SetAdd(key, value) => {
-                            debug!(key, value, "adding");
+            pipe.lpop(&self.journal_key).ignore();
+        }
 
-                            conn.deref_mut().sadd(key, value).or_restart()?;
-                        }
-                        model::CRDTCommand::SetRemove(key, value) => {
-                            debug!(key, value, "removing");
+        self.layout.queue_commands(&mut pipe, commands);
 
-                            conn.deref_mut().srem(key, value).or_restart()?;
-                        }
-                        model::CRDTCommand::LastWriteWins(key, value, slot) => {
-                            debug!(key, slot, "last write");
+        pipe.lpush(&self.journal_key, entry)
+            .ignore()
+            .ltrim(&self.journal_key, 0, self.journal_depth as isize - 1)
+            .ignore()
+            .set(&self.cursor_key, breadcrumbs)
+            .ignore();
 
-                            conn.deref_mut().zadd(key, value, slot).or_restart()?;
-                        }
-                        model::CRDTCommand::SortedSetAdd(key, value, delta) => {
-                            debug!(key, value, delta, "sorted set add");
+        or_redirect(pipe.query::<()>(conn))
+    }
+}
 
-                            conn.deref_mut().zincr(key, value, delta).or_restart()?;
-                        }
-                        model::CRDTCommand::SortedSetRemove(key, value, delta) => {
-                            debug!(key, value, delta, "sorted set remove");
+#[async_trait::async_trait(?Send)]
+impl gasket::framework::Worker<Stage> for Worker {
+    async fn bootstrap(stage: &Stage) -> Result<Self, WorkerError> {
+        // the target node is resolved again each time the worker restarts,
+        // which follows failovers and cluster resharding
+        let info = stage.config.connection_info().or_retry()?;
+        let manager = RedisConnectionManager::new(info).or_panic()?;
+        let pool = r2d2::Pool::builder().build(manager).or_panic()?;
 
-                            conn.deref_mut().zincr(&key, value, delta).or_restart()?;
+        let journal_key = stage.config.tag_key(
+            stage
+                .config
+                .journal_key
+                .as_deref()
+                .unwrap_or(DEFAULT_JOURNAL_KEY),
+        );
 
-                            // removal of dangling scores  (aka garage collection)
-                            conn.deref_mut().zrembyscore(&key, 0, 0).or_restart()?;
-                        }
-                        model::CRDTCommand::AnyWriteWins(key, value) => {
-                            debug!(key, "overwrite");
+        let journal_depth = stage.config.journal_depth.unwrap_or(DEFAULT_JOURNAL_DEPTH);
 
-                            conn.deref_mut().set(key, value).or_restart()?;
-                        }
-                        model::CRDTCommand::PNCounter(key, value) => {
-                            debug!(key, value, "increasing counter");
+        let cursor_key = stage.config.cursor_key();
 
-                            conn.deref_mut().incr(key, value).or_restart()?;
-                        }
-                        model::CRDTCommand::HashSetValue(key, member, value) => {
-                            debug!(key, member, "setting hash");
+        let layout = Layout {
+            json_mode: stage.config.json_mode.unwrap_or_default(),
+            live_two_phase_sets: stage.config.live_two_phase_sets.unwrap_or_default(),
+        };
 
-                            conn.deref_mut().hset(key, member, value).or_restart()?;
-                        }
-                        model::CRDTCommand::HashCounter(key, member, delta) => {
-                            debug!(key, member, delta, "increasing hash");
+        let hash_tag = stage.config.hash_tag();
 
-                            conn.deref_mut().hincr(key, member, delta).or_restart()?;
-                        }
-                        model::CRDTCommand::HashUnsetKey(key, member) => {
-                            debug!(key, member, "deleting hash");
+        let batch_limits = stage.config.batch.as_ref().map(BatchConfig::limits);
 
-                            conn.deref_mut().hdel(member, key).or_restart()?;
-                        }
+        Ok(Self {
+            pool,
+            journal_key,
+            journal_depth,
+            cursor_key,
+            layout,
+            hash_tag,
+            batch_limits,
+        })
+    }
+
+    async fn schedule(&mut self, stage: &mut Stage) -> Result<WorkSchedule<Unit>, WorkerError> {
+        let deadline = match stage.batch.deadline {
+            Some(x) => x,
+            None => {
+                return recv_pending(&mut stage.input, &mut stage.catch_up)
+                    .await
+                    .map(event_unit)
+            }
+        };
+
+        let next = tokio::time::timeout_at(
+            deadline,
+            recv_pending(&mut stage.input, &mut stage.catch_up),
+        )
+        .await;
+
+        match next {
+            // the pending blocks are committed before the stage finishes
+            Ok(Ok(WorkSchedule::Done)) | Err(_) => Ok(WorkSchedule::Unit(Unit::Flush)),
+            Ok(x) => x.map(event_unit),
+        }
+    }
+
+    async fn execute(&mut self, unit: &Unit, stage: &mut Stage) -> Result<(), WorkerError> {
+        let mut conn = self.pool.get().or_restart()?;
+
+        let event = match unit {
+            Unit::Flush => return self.commit(conn.deref_mut(), stage),
+            Unit::Event(x) => x,
+        };
+
+        match event {
+            ChainEvent::Apply(point, record) => match record {
+                Record::CRDTCommand(commands) => {
+                    let commands = self.tag_commands(commands);
+
+                    let probes: Vec<_> = commands
+                        .iter()
+                        .flat_map(|x| self.layout.probes(x))
+                        .collect();
+
+                    let batched = self.is_batched(stage, point);
+
+                    // a block committed on its own gets its own transaction,
+                    // and so does one that depends on a dump of the batch
+                    if !batched || stage.batch.overlaps_dump(&probes) {
+                        self.commit(conn.deref_mut(), stage)?;
+                    }
+
+                    self.push_block(stage, point, commands, &probes);
+
+                    if !batched || self.is_full(&stage.batch) {
+                        self.commit(conn.deref_mut(), stage)?;
                     }
                 }
+                Record::Compensation(commands) => {
+                    let commands = self.tag_commands(commands);
 
-                // TODO: check if its necessary to set cursor
-                redis::cmd("EXEC").query(conn.deref_mut()).or_retry()?;
+                    self.commit(conn.deref_mut(), stage)?;
+                    self.rollback(conn.deref_mut(), &stage.cursor, point, &commands)?;
+                    stage.cursor.rollback(point);
+                    stage.latest_block.set(point.slot_or_default() as i64);
+                }
+                _ => Err(Error::message("redis storage only accepts CRDT commands")).or_panic()?,
+            },
+            ChainEvent::Reset(point) => {
+                self.commit(conn.deref_mut(), stage)?;
+                self.rollback(conn.deref_mut(), &stage.cursor, point, &[])?;
+                stage.cursor.rollback(point);
+                stage.latest_block.set(point.slot_or_default() as i64);
             }
-            _ => todo!(),
         }
 
         stage.ops_count.inc(1);
-        stage.latest_block.set(point.slot_or_default() as i64);
-        stage.cursor.add_breadcrumb(point.clone());
 
         Ok(())
     }
 }
 
 #[derive(Stage)]
-#[stage(name = "redis", unit = "ChainEvent", worker = "Worker")]
+#[stage(name = "redis", unit = "Unit", worker = "Worker")]
 pub struct Stage {
     config: Config,
+    chain: GenesisValues,
     cursor: Cursor,
+    catch_up: CatchUp,
+    batch: Batch,
 
     pub input: StorageInputPort,
 
@@ -151,16 +1258,187 @@ pub struct Stage {
     latest_block: gasket::metrics::Gauge,
 }
 
+/// Commits several consecutive blocks within a single pipelined transaction
+///
+/// Blocks are committed once any of the limits is reached, and one at a time
+/// when close to the tip of the chain.
+#[derive(Deserialize)]
+pub struct BatchConfig {
+    /// Max number of blocks per transaction, defaults to 100
+    pub max_blocks: Option<usize>,
+
+    /// Max number of CRDT commands per transaction, defaults to 50000
+    pub max_commands: Option<usize>,
+
+    /// Max milliseconds a block waits for its transaction, defaults to 5000
+    pub max_delay_ms: Option<u64>,
+
+    /// Distance in slots to the wall-clock tip under which blocks aren't
+    /// batched, defaults to 129600 (the stability window of mainnet)
+    pub tip_distance: Option<u64>,
+}
+
+impl BatchConfig {
+    fn limits(&self) -> BatchLimits {
+        BatchLimits {
+            max_blocks: self.max_blocks.unwrap_or(DEFAULT_BATCH_BLOCKS),
+            max_commands: self.max_commands.unwrap_or(DEFAULT_BATCH_COMMANDS),
+            max_delay: Duration::from_millis(self.max_delay_ms.unwrap_or(DEFAULT_BATCH_DELAY_MS)),
+            tip_distance: self.tip_distance.unwrap_or(DEFAULT_TIP_DISTANCE),
+        }
+    }
+}
+
+/// Discovers the master of a replica set through Redis Sentinel
+#[derive(Deserialize)]
+pub struct SentinelConfig {
+    /// Name of the master monitored by the sentinels
+    pub master_name: String,
+
+    /// Addresses of the sentinels, queried in order
+    pub nodes: Vec<String>,
+}
+
+/// Targets a Redis Cluster in single-shard mode
+///
+/// Every key is prefixed with the same hash tag so that all of them live in
+/// a single hash slot, which lets the node serving it commit each block
+/// atomically within MULTI / EXEC. The whole dataset is kept by the shard that
+/// owns that slot, the cluster provides failover and read replicas but
+/// doesn't spread the writes. A write redirected with MOVED or ASK restarts
+/// the stage, which resolves the node again; while the slot is migrating, the
+/// stage keeps restarting until the migration completes.
+///
+/// The tag renames every key, `<key>` is stored as `{<hash_tag>}<key>`, so it
+/// has no default and has to be set explicitly.
+#[derive(Deserialize)]
+pub struct ClusterConfig {
+    /// Hash tag prepended to every key, including the cursor and the journal,
+    /// readers have to query the keys with the tag
+    pub hash_tag: String,
+
+    /// Seed nodes queried before the one in `url`
+    pub nodes: Option<Vec<String>>,
+}
+
 #[derive(Default, Deserialize)]
 pub struct Config {
+    /// Server to connect to, or the credentials and database to use when the
+    /// server is resolved through sentinels or a cluster
     pub url: String,
+
+    /// Key of the Redis list that keeps the undo data of recent blocks
+    pub journal_key: Option<String>,
+
+    /// Max number of blocks that can be undone by a rollback
+    pub journal_depth: Option<usize>,
+
+    /// Key where the cursor breadcrumbs are persisted
+    pub cursor_key: Option<String>,
+
+    /// How JSON values are stored, defaults to serialized strings
+    pub json_mode: Option<JsonMode>,
+
+    /// Resolves the master through Redis Sentinel
+    pub sentinel: Option<SentinelConfig>,
+
+    /// Connects to the cluster node serving the hash tag, which is prepended
+    /// to every key
+    pub cluster: Option<ClusterConfig>,
+
+    /// Commits several blocks per transaction, by default each block is
+    /// committed on its own
+    pub batch: Option<BatchConfig>,
+
+    /// Keeps a `<key>.live` set with the members of each two-phase set that
+    /// haven't been removed, next to the `<key>` and `<key>.ts` sets
+    pub live_two_phase_sets: Option<bool>,
 }
 
 impl Config {
+    fn hash_tag(&self) -> Option<String> {
+        self.cluster.as_ref().map(|x| x.hash_tag.clone())
+    }
+
+    fn tag_key(&self, key: &str) -> String {
+        match self.hash_tag() {
+            Some(tag) => format!("{{{}}}{}", tag, key),
+            None => key.to_owned(),
+        }
+    }
+
+    fn cursor_key(&self) -> String {
+        self.tag_key(self.cursor_key.as_deref().unwrap_or(DEFAULT_CURSOR_KEY))
+    }
+
+    /// Resolves the node that should receive the writes
+    fn connection_info(&self) -> Result<ConnectionInfo, Error> {
+        let mut info = self
+            .url
+            .as_str()
+            .into_connection_info()
+            .map_err(Error::config)?;
+
+        let (host, port) = match (&self.sentinel, &self.cluster) {
+            (Some(_), Some(_)) => {
+                return Err(Error::config(
+                    "redis sentinel and cluster modes can't be used together",
+                ))
+            }
+            (Some(sentinel), None) => query_sentinels(sentinel)?,
+            (None, Some(cluster)) => {
+                // redis only hashes the text between the first pair of braces
+                let tag = &cluster.hash_tag;
+                if tag.is_empty() || tag.contains(['{', '}']) {
+                    return Err(Error::config(
+                        "redis cluster hash_tag can't be empty or contain braces",
+                    ));
+                }
+
+                let mut seeds = cluster.nodes.clone().unwrap_or_default();
+                seeds.push(self.url.clone());
+
+                query_cluster(&seeds, &self.hash_tag().unwrap_or_default())?
+            }
+            (None, None) => return Ok(info),
+        };
+
+        debug!(host, port, "resolved redis node");
+
+        info.addr = match *info.addr {
+            ConnectionAddr::TcpTls { insecure, .. } => Box::new(ConnectionAddr::TcpTls {
+                host,
+                port,
+                insecure,
+            }),
+            _ => Box::new(ConnectionAddr::Tcp(host, port)),
+        };
+
+        Ok(info)
+    }
+
+    pub fn load_cursor(&self) -> Result<Cursor, Error> {
+        let client = redis::Client::open(self.connection_info()?).map_err(Error::storage)?;
+        let mut conn = client.get_connection().map_err(Error::storage)?;
+
+        let breadcrumbs: Option<String> = conn.get(self.cursor_key()).map_err(Error::storage)?;
+
+        match breadcrumbs {
+            Some(x) => {
+                let breadcrumbs = serde_json::from_str(&x).map_err(Error::storage)?;
+                Cursor::from_breadcrumbs(breadcrumbs)
+            }
+            None => Ok(Cursor::new(Default::default())),
+        }
+    }
+
     pub fn bootstrapper(self, ctx: &Context) -> Result<Stage, Error> {
         let stage = Stage {
             config: self,
+            chain: ctx.chain.clone().into(),
             cursor: ctx.cursor.clone(),
+            catch_up: CatchUp::new(&ctx.cursor),
+            batch: Default::default(),
             ops_count: Default::default(),
             latest_block: Default::default(),
             input: Default::default(),
@@ -179,7 +1457,498 @@ impl ToRedisArgs for model::Value {
             model::Value::String(x) => x.write_redis_args(out),
             model::Value::BigInt(x) => x.to_string().write_redis_args(out),
             model::Value::Cbor(x) => x.write_redis_args(out),
-            model::Value::Json(x) => todo!("{}", x),
+            model::Value::Json(x) => x.to_string().write_redis_args(out),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use gasket::framework::{Worker as _, WorkerError};
+    use pallas::{ledger::traverse::wellknown::GenesisValues, network::miniprotocols::Point};
+    use r2d2_redis::redis::{self, Arg, Commands, ErrorKind, RedisError, Value};
+
+    use super::{
+        find_slot_master, or_redirect, overlaps_dump, CatchUp, Config, JsonMode, Layout, Stage,
+        UndoOp, Unit, Worker,
+    };
+    use crate::framework::{
+        model::{self, CRDTCommand},
+        ChainEvent, Cursor, Record,
+    };
+
+    const JSON_LAYOUT: Layout = Layout {
+        json_mode: JsonMode::RedisJson,
+        live_two_phase_sets: false,
+    };
+
+    fn point(slot: u64) -> Point {
+        Point::Specific(slot, vec![slot as u8; 32])
+    }
+
+    /// Undo data of each block, given one block per command
+    fn entries(layout: Layout, commands: Vec<CRDTCommand>, read: Vec<UndoOp>) -> Vec<Vec<UndoOp>> {
+        let blocks: Vec<_> = (0..commands.len() as u64).map(|x| (point(x), 1)).collect();
+
+        layout
+            .block_entries(&blocks, &commands, read)
+            .into_iter()
+            .map(|x| x.undo)
+            .collect()
+    }
+
+    /// Arguments of each Redis command queued for the CRDT commands
+    fn queued(layout: Layout, commands: &[CRDTCommand]) -> Vec<Vec<String>> {
+        let mut pipe = redis::pipe();
+        layout.queue_commands(&mut pipe, commands);
+
+        pipe.cmd_iter()
+            .map(|cmd| {
+                cmd.args_iter()
+                    .map(|arg| match arg {
+                        Arg::Simple(x) => String::from_utf8_lossy(x).into_owned(),
+                        Arg::Cursor => "<cursor>".to_owned(),
+                    })
+                    .collect()
+            })
+            .collect()
+    }
+
+    fn words(items: &[&str]) -> Vec<String> {
+        items.iter().map(|x| x.to_string()).collect()
+    }
+
+    /// Stage and worker on the test server, with every key under the prefix
+    /// cleared, `None` if `SCROLLS_TEST_REDIS_URL` is unset
+    fn test_worker(prefix: &str) -> Option<(Stage, Worker)> {
+        let url = std::env::var("SCROLLS_TEST_REDIS_URL").ok()?;
+
+        let config: Config = serde_json::from_value(serde_json::json!({
+            "url": url,
+            "journal_key": format!("{}.journal", prefix),
+            "cursor_key": format!("{}.cursor", prefix),
+        }))
+        .unwrap();
+
+        let cursor = Cursor::new(Default::default());
+
+        let stage = Stage {
+            config,
+            chain: GenesisValues::mainnet(),
+            catch_up: CatchUp::new(&cursor),
+            cursor,
+            batch: Default::default(),
+            input: Default::default(),
+            ops_count: Default::default(),
+            latest_block: Default::default(),
+        };
+
+        let worker = block_on(Worker::bootstrap(&stage)).unwrap();
+
+        let mut conn = worker.pool.get().unwrap();
+        let keys: Vec<String> = conn.keys(format!("{}.*", prefix)).unwrap();
+
+        if !keys.is_empty() {
+            conn.del::<_, ()>(keys).unwrap();
+        }
+
+        Some((stage, worker))
+    }
+
+    fn block_on<T>(future: impl std::future::Future<Output = T>) -> T {
+        tokio::runtime::Runtime::new().unwrap().block_on(future)
+    }
+
+    fn execute(worker: &mut Worker, stage: &mut Stage, event: ChainEvent) {
+        block_on(worker.execute(&Unit::Event(event), stage)).unwrap();
+    }
+
+    /// Connection to the server in `SCROLLS_TEST_REDIS_URL`, `None` if unset
+    fn test_connection() -> Option<redis::Connection> {
+        let url = std::env::var("SCROLLS_TEST_REDIS_URL").ok()?;
+        let client = redis::Client::open(url.as_str()).unwrap();
+
+        Some(client.get_connection().unwrap())
+    }
+
+    fn node(host: &str, port: i64) -> Value {
+        Value::Bulk(vec![
+            Value::Data(host.as_bytes().to_vec()),
+            Value::Int(port),
+            Value::Data(b"id".to_vec()),
+        ])
+    }
+
+    #[test]
+    fn finds_master_of_slot() {
+        let ranges = vec![
+            Value::Bulk(vec![
+                Value::Int(0),
+                Value::Int(5460),
+                node("10.0.0.1", 7000),
+            ]),
+            Value::Bulk(vec![
+                Value::Int(5461),
+                Value::Int(16383),
+                node("10.0.0.2", 7001),
+                node("10.0.0.3", 7002),
+            ]),
+        ];
+
+        let master = find_slot_master(&ranges, 5461);
+        assert_eq!(master, Some(("10.0.0.2".to_owned(), 7001)));
+
+        assert_eq!(find_slot_master(&ranges[..1], 9000), None);
+    }
+
+    #[test]
+    fn restarts_on_redirected_write() {
+        let moved = RedisError::from((ErrorKind::Moved, "moved"));
+        let ask = RedisError::from((ErrorKind::Ask, "ask"));
+        let io = RedisError::from((ErrorKind::IoError, "io"));
+
+        assert!(matches!(
+            or_redirect::<()>(Err(moved)),
+            Err(WorkerError::Restart)
+        ));
+        assert!(matches!(
+            or_redirect::<()>(Err(ask)),
+            Err(WorkerError::Restart)
+        ));
+        assert!(matches!(
+            or_redirect::<()>(Err(io)),
+            Err(WorkerError::Retry)
+        ));
+    }
+
+    #[test]
+    fn requires_explicit_hash_tag_in_cluster_mode() {
+        let config = |cluster| {
+            serde_json::from_value::<Config>(serde_json::json!({
+                "url": "redis://127.0.0.1:6379",
+                "cluster": cluster,
+            }))
+        };
+
+        assert!(config(serde_json::json!({})).is_err());
+
+        for tag in ["", "a{b", "a}b"] {
+            let config = config(serde_json::json!({ "hash_tag": tag })).unwrap();
+            assert!(config.connection_info().is_err());
+        }
+
+        let config = config(serde_json::json!({ "hash_tag": "scrolls" })).unwrap();
+        assert_eq!(config.cursor_key(), "{scrolls}_scrolls.cursor");
+    }
+
+    #[test]
+    fn derives_counter_per_block() {
+        let commands = vec![
+            CRDTCommand::PNCounter("c".into(), 1),
+            CRDTCommand::PNCounter("c".into(), -3),
+            CRDTCommand::PNCounter("c".into(), 1),
+        ];
+
+        let read = vec![UndoOp::Key("c".into(), Some(b"5".to_vec()))];
+
+        let expected: Vec<_> = ["5", "6", "3"]
+            .iter()
+            .map(|x| vec![UndoOp::Key("c".into(), Some(x.as_bytes().to_vec()))])
+            .collect();
+
+        assert_eq!(entries(Layout::default(), commands, read), expected);
+    }
+
+    #[test]
+    fn derives_two_phase_set_per_block() {
+        let layout = Layout {
+            live_two_phase_sets: true,
+            ..Default::default()
+        };
+
+        let commands = vec![
+            CRDTCommand::TwoPhaseSetAdd("s".into(), "m".into()),
+            CRDTCommand::TwoPhaseSetRemove("s".into(), "m".into()),
+            CRDTCommand::TwoPhaseSetAdd("s".into(), "m".into()),
+        ];
+
+        let member = |key: &str, present| UndoOp::SetMember(key.into(), "m".into(), present);
+
+        let read = vec![
+            member("s", false),
+            member("s.ts", false),
+            member("s.live", false),
+        ];
+
+        let expected = vec![
+            vec![
+                member("s", false),
+                member("s.ts", false),
+                member("s.live", false),
+            ],
+            vec![member("s.ts", false), member("s.live", true)],
+            vec![
+                member("s", true),
+                member("s.ts", true),
+                member("s.live", false),
+            ],
+        ];
+
+        assert_eq!(entries(layout, commands, read), expected);
+    }
+
+    #[test]
+    fn derives_dangling_scores_per_block() {
+        let commands = vec![
+            CRDTCommand::SortedSetRemove("z".into(), "b".into(), -1),
+            CRDTCommand::SortedSetAdd("z".into(), "a".into(), 1),
+        ];
+
+        let score = |member: &str, x| UndoOp::SortedSetMember("z".into(), member.into(), x);
+
+        let read = vec![score("b", Some(1.0)), score("a", Some(0.0))];
+
+        // the removal collects every zero score of the key
+        let expected = vec![vec![score("b", Some(1.0))], vec![score("a", None)]];
+
+        assert_eq!(entries(Layout::default(), commands, read), expected);
+    }
+
+    #[test]
+    fn stops_at_underivable_pre_image() {
+        let layout = Layout {
+            json_mode: JsonMode::RedisJson,
+            ..Default::default()
+        };
+
+        let doc = || model::Value::Json(serde_json::json!({ "a": 1 }));
+
+        let commands = vec![
+            CRDTCommand::AnyWriteWins("k".into(), doc()),
+            CRDTCommand::AnyWriteWins("k".into(), doc()),
+        ];
+
+        let read = vec![UndoOp::Dump("k".into(), None)];
+
+        let expected = vec![vec![UndoOp::Dump("k".into(), None)]];
+
+        assert_eq!(entries(layout, commands, read), expected);
+    }
+
+    #[test]
+    fn queues_json_per_mode() {
+        let json = model::Value::Json(serde_json::json!({ "a": 1 }));
+
+        let commands = vec![
+            CRDTCommand::AnyWriteWins("k".into(), json.clone()),
+            CRDTCommand::HashSetValue("h".into(), "m".into(), json),
+            CRDTCommand::HashSetValue("h".into(), "n".into(), model::Value::BigInt(1)),
+            CRDTCommand::HashUnsetKey("h".into(), "m".into()),
+        ];
+
+        assert_eq!(
+            queued(Layout::default(), &commands),
+            vec![
+                words(&["SET", "k", r#"{"a":1}"#]),
+                words(&["HSET", "h", "m", r#"{"a":1}"#]),
+                words(&["HSET", "h", "n", "1"]),
+                words(&["HDEL", "h", "m"]),
+            ]
+        );
+
+        let queued = queued(JSON_LAYOUT, &commands);
+
+        assert_eq!(queued[0], words(&["JSON.SET", "k", "$", r#"{"a":1}"#]));
+        assert_eq!(
+            queued[1],
+            words(&["JSON.MERGE", "h", "$", r#"{"m":{"a":1}}"#])
+        );
+        assert_eq!(queued[2], words(&["HSET", "h", "n", "1"]));
+        assert_eq!(queued[3][0], "EVAL");
+        assert_eq!(queued[3][2..], words(&["1", "h", "m", r#"$["m"]"#]));
+    }
+
+    #[test]
+    fn dumps_json_keys_in_redis_json_mode() {
+        let json = model::Value::Json(serde_json::json!(1));
+        let command = CRDTCommand::HashSetValue("h".into(), "m".into(), json);
+
+        assert_eq!(
+            Layout::default().probes(&command),
+            vec![UndoOp::HashMember("h".into(), "m".into(), None)]
+        );
+
+        assert_eq!(
+            JSON_LAYOUT.probes(&command),
+            vec![UndoOp::Dump("h".into(), None)]
+        );
+
+        // plain values keep their own slot, even in RedisJSON mode
+        let command = CRDTCommand::AnyWriteWins("k".into(), model::Value::BigInt(1));
+
+        assert_eq!(
+            JSON_LAYOUT.probes(&command),
+            vec![UndoOp::Key("k".into(), None)]
+        );
+
+        let mut pipe = redis::pipe();
+        UndoOp::Dump("h".into(), Some(b"dump".to_vec())).restore(&mut pipe);
+        UndoOp::Dump("k".into(), None).restore(&mut pipe);
+
+        let restored: Vec<Vec<u8>> = pipe
+            .cmd_iter()
+            .flat_map(|x| x.args_iter())
+            .filter_map(|x| match x {
+                Arg::Simple(x) => Some(x.to_vec()),
+                Arg::Cursor => None,
+            })
+            .collect();
+
+        let expected: Vec<&[u8]> = vec![b"RESTORE", b"h", b"0", b"dump", b"REPLACE", b"DEL", b"k"];
+        assert_eq!(restored, expected);
+    }
+
+    #[test]
+    fn unsets_plain_hash_member_in_redis_json_mode() {
+        let mut conn = match test_connection() {
+            Some(x) => x,
+            None => return,
+        };
+
+        let key = "_scrolls_test.hash";
+        conn.del::<_, ()>(key).unwrap();
+
+        let commands = vec![
+            CRDTCommand::HashSetValue(key.into(), "a".into(), model::Value::BigInt(1)),
+            CRDTCommand::HashSetValue(key.into(), "b".into(), model::Value::BigInt(2)),
+            CRDTCommand::HashUnsetKey(key.into(), "a".into()),
+        ];
+
+        let mut pipe = redis::pipe();
+        JSON_LAYOUT.queue_commands(&mut pipe, &commands);
+        pipe.query::<()>(&mut conn).unwrap();
+
+        let members: Vec<String> = conn.hkeys(key).unwrap();
+        assert_eq!(members, vec!["b"]);
+    }
+
+    #[test]
+    fn queues_two_phase_commands() {
+        let commands = vec![
+            CRDTCommand::TwoPhaseSetRemove("s".into(), "a".into()),
+            CRDTCommand::TwoPhaseSetAdd("s".into(), "a".into()),
+        ];
+
+        let plain = queued(Layout::default(), &commands);
+
+        assert_eq!(plain[0], words(&["SADD", "s.ts", "a"]));
+        assert_eq!(plain[1][0], "EVAL");
+        assert_eq!(plain[1][2..], words(&["2", "s", "s.ts", "a"]));
+
+        let layout = Layout {
+            live_two_phase_sets: true,
+            ..Default::default()
+        };
+
+        let live = queued(layout, &commands);
+
+        assert_eq!(live[0], words(&["SADD", "s.ts", "a"]));
+        assert_eq!(live[1], words(&["SREM", "s.live", "a"]));
+        assert_eq!(live[2][2..], words(&["3", "s", "s.ts", "s.live", "a"]));
+    }
+
+    #[test]
+    fn ignores_two_phase_add_after_remove() {
+        let mut conn = match test_connection() {
+            Some(x) => x,
+            None => return,
+        };
+
+        let keys = [
+            "_scrolls_test.2p",
+            "_scrolls_test.2p.ts",
+            "_scrolls_test.2p.live",
+        ];
+        conn.del::<_, ()>(&keys[..]).unwrap();
+
+        let layout = Layout {
+            live_two_phase_sets: true,
+            ..Default::default()
+        };
+
+        let commands = vec![
+            CRDTCommand::TwoPhaseSetAdd(keys[0].into(), "a".into()),
+            CRDTCommand::TwoPhaseSetRemove(keys[0].into(), "a".into()),
+            CRDTCommand::TwoPhaseSetAdd(keys[0].into(), "a".into()),
+            CRDTCommand::TwoPhaseSetRemove(keys[0].into(), "b".into()),
+            CRDTCommand::TwoPhaseSetAdd(keys[0].into(), "b".into()),
+            CRDTCommand::TwoPhaseSetAdd(keys[0].into(), "c".into()),
+        ];
+
+        let mut pipe = redis::pipe();
+        pipe.atomic();
+        layout.queue_commands(&mut pipe, &commands);
+        pipe.query::<()>(&mut conn).unwrap();
+
+        let mut members = |key: &str| {
+            let mut x: Vec<String> = conn.smembers(key).unwrap();
+            x.sort();
+            x
+        };
+
+        assert_eq!(members(keys[0]), vec!["a", "c"]);
+        assert_eq!(members(keys[1]), vec!["a", "b"]);
+        assert_eq!(members(keys[2]), vec!["c"]);
+    }
+
+    #[test]
+    fn detects_dumps_overlapping_slots() {
+        let dump = UndoOp::Dump("h".into(), None);
+        let member = UndoOp::HashMember("h".into(), "a".into(), None);
+        let other = UndoOp::HashMember("g".into(), "a".into(), None);
+
+        assert!(overlaps_dump(&[dump.clone()], &[member.clone()]));
+        assert!(overlaps_dump(&[member.clone()], &[dump.clone()]));
+        assert!(!overlaps_dump(&[dump], &[other.clone()]));
+        assert!(!overlaps_dump(&[member], &[other]));
+    }
+
+    #[test]
+    fn journals_compensation_at_rollback_point() {
+        let (mut stage, mut worker) = match test_worker("_scrolls_test.compensation") {
+            Some(x) => x,
+            None => return,
+        };
+
+        let counter = "_scrolls_test.compensation.c";
+        let rollbacks = "_scrolls_test.compensation.rollbacks";
+
+        for (slot, delta) in [(1, 1), (2, 2)] {
+            let commands = vec![CRDTCommand::PNCounter(counter.into(), delta)];
+            let event = ChainEvent::Apply(point(slot), Record::CRDTCommand(commands));
+            execute(&mut worker, &mut stage, event);
         }
+
+        let compensation = vec![
+            CRDTCommand::PNCounter(counter.into(), 10),
+            CRDTCommand::PNCounter(rollbacks.into(), 1),
+        ];
+
+        let event = ChainEvent::Apply(point(1), Record::Compensation(compensation));
+        execute(&mut worker, &mut stage, event);
+
+        let values = |worker: &Worker| -> (Option<i64>, Option<i64>) {
+            let mut conn = worker.pool.get().unwrap();
+            (conn.get(counter).unwrap(), conn.get(rollbacks).unwrap())
+        };
+
+        assert_eq!(values(&worker), (Some(11), Some(1)));
+
+        // the compensation belongs to the rollback point, which is kept
+        execute(&mut worker, &mut stage, ChainEvent::Reset(point(1)));
+        assert_eq!(values(&worker), (Some(11), Some(1)));
+
+        execute(&mut worker, &mut stage, ChainEvent::Reset(point(0)));
+        assert_eq!(values(&worker), (None, None));
     }
 }
@@ -0,0 +1,341 @@
+//! Statements shared by the SQL backends
+//!
+//! Postgres and SQLite keep the CRDTs in the same generic tables, so each
+//! command, pre-image read and restore maps to the same statements. They're
+//! written with `$N` placeholders and only differ by dialect in the
+//! placeholder style, the schema prefix of the tables and the check for
+//! integer text.
+
+use crate::framework::*;
+
+use super::journal::{value_text, UndoOp};
+
+/// Parameter bound to a statement
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SqlValue {
+    Text(String),
+    Int(i64),
+    NullableInt(Option<i64>),
+}
+
+/// Statement along with its parameters, in placeholder order
+#[derive(Debug)]
+pub struct Statement {
+    pub sql: String,
+    pub params: Vec<SqlValue>,
+
+    /// Error raised when the statement doesn't change any row
+    pub unchanged_error: Option<String>,
+}
+
+pub enum Dialect {
+    /// `$N` placeholders, tables live in the given schema
+    Postgres(String),
+
+    /// `?N` placeholders, tables live in the main database
+    Sqlite,
+}
+
+impl Dialect {
+    fn table(&self, name: &str) -> String {
+        match self {
+            Dialect::Postgres(schema) => format!("{}.{}", schema, name),
+            Dialect::Sqlite => name.to_owned(),
+        }
+    }
+
+    /// Condition that holds when the text column is an integer, which is what
+    /// the casts of the hash counters expect
+    fn is_integer(&self, column: &str) -> String {
+        match self {
+            Dialect::Postgres(_) => format!("{} ~ '^-?[0-9]+$'", column),
+            // the cast would silently turn anything else into zero
+            Dialect::Sqlite => format!("CAST(CAST({0} AS INTEGER) AS TEXT) = {0}", column),
+        }
+    }
+
+    fn statement(&self, sql: String, params: Vec<SqlValue>) -> Statement {
+        let sql = match self {
+            Dialect::Postgres(_) => sql,
+            Dialect::Sqlite => sql.replace('$', "?"),
+        };
+
+        Statement {
+            sql,
+            params,
+            unchanged_error: None,
+        }
+    }
+
+    /// Reads the row targeted by the op, its pre-image
+    pub fn probe(&self, op: &UndoOp) -> Statement {
+        let (table, key, member) = op.target();
+
+        let (columns, filter, params) = match op {
+            UndoOp::SetMember(..) => ("1", "key = $1 AND member = $2", vec![key, member]),
+            UndoOp::SortedSetMember(..) => ("score", "key = $1 AND member = $2", vec![key, member]),
+            UndoOp::Counter(..) => ("value", "key = $1", vec![key]),
+            UndoOp::Register(..) => ("value, slot", "key = $1", vec![key]),
+            UndoOp::HashMember(..) => ("value", "key = $1 AND member = $2", vec![key, member]),
+        };
+
+        self.statement(
+            format!(
+                "SELECT {} FROM {} WHERE {}",
+                columns,
+                self.table(table),
+                filter
+            ),
+            params.into_iter().map(text).collect(),
+        )
+    }
+
+    /// Puts back the row as captured by the op
+    pub fn restore(&self, op: &UndoOp) -> Statement {
+        let (table, key, member) = op.target();
+        let table = self.table(table);
+
+        let delete = |filter: &str, params| {
+            self.statement(format!("DELETE FROM {} WHERE {}", table, filter), params)
+        };
+
+        match op {
+            UndoOp::SetMember(key, member, true) => self.statement(
+                format!(
+                    "INSERT INTO {} VALUES ($1, $2) ON CONFLICT DO NOTHING",
+                    table
+                ),
+                vec![text(key), text(member)],
+            ),
+            UndoOp::SortedSetMember(_, _, Some(score)) => self.statement(
+                format!(
+                    "INSERT INTO {} VALUES ($1, $2, $3)
+                    ON CONFLICT (key, member) DO UPDATE SET score = excluded.score",
+                    table
+                ),
+                vec![text(key), text(member), SqlValue::Int(*score)],
+            ),
+            UndoOp::Counter(_, Some(value)) => self.statement(
+                format!(
+                    "INSERT INTO {} VALUES ($1, $2)
+                    ON CONFLICT (key) DO UPDATE SET value = excluded.value",
+                    table
+                ),
+                vec![text(key), SqlValue::Int(*value)],
+            ),
+            UndoOp::Register(_, Some((value, slot))) => self.statement(
+                format!(
+                    "INSERT INTO {} VALUES ($1, $2, $3)
+                    ON CONFLICT (key) DO UPDATE SET value = excluded.value, slot = excluded.slot",
+                    table
+                ),
+                vec![text(key), text(value), SqlValue::NullableInt(*slot)],
+            ),
+            UndoOp::HashMember(_, _, Some(value)) => self.statement(
+                format!(
+                    "INSERT INTO {} VALUES ($1, $2, $3)
+                    ON CONFLICT (key, member) DO UPDATE SET value = excluded.value",
+                    table
+                ),
+                vec![text(key), text(member), text(value)],
+            ),
+            UndoOp::Counter(..) | UndoOp::Register(..) => delete("key = $1", vec![text(key)]),
+            UndoOp::SetMember(..) | UndoOp::SortedSetMember(..) | UndoOp::HashMember(..) => {
+                delete("key = $1 AND member = $2", vec![text(key), text(member)])
+            }
+        }
+    }
+
+    /// Statements executing the command, in order
+    pub fn apply(&self, command: &model::CRDTCommand) -> Vec<Statement> {
+        let sets = self.table("sets");
+
+        match command {
+            model::CRDTCommand::GrowOnlySetAdd(key, member)
+            | model::CRDTCommand::SetAdd(key, member) => vec![self.statement(
+                format!(
+                    "INSERT INTO {} VALUES ($1, $2) ON CONFLICT DO NOTHING",
+                    sets
+                ),
+                vec![text(key), text(member)],
+            )],
+            model::CRDTCommand::TwoPhaseSetAdd(key, member) => vec![self.statement(
+                // members can't be added back once removed
+                format!(
+                    "INSERT INTO {0} SELECT CAST($1 AS TEXT), CAST($2 AS TEXT)
+                    WHERE NOT EXISTS (SELECT 1 FROM {0} WHERE key = $3 AND member = $2)
+                    ON CONFLICT DO NOTHING",
+                    sets
+                ),
+                vec![
+                    text(key),
+                    text(member),
+                    SqlValue::Text(format!("{}.ts", key)),
+                ],
+            )],
+            model::CRDTCommand::TwoPhaseSetRemove(key, member) => vec![self.statement(
+                format!(
+                    "INSERT INTO {} VALUES ($1, $2) ON CONFLICT DO NOTHING",
+                    sets
+                ),
+                vec![SqlValue::Text(format!("{}.ts", key)), text(member)],
+            )],
+            model::CRDTCommand::SetRemove(key, member) => vec![self.statement(
+                format!("DELETE FROM {} WHERE key = $1 AND member = $2", sets),
+                vec![text(key), text(member)],
+            )],
+            model::CRDTCommand::SortedSetAdd(key, member, delta)
+            | model::CRDTCommand::SortedSetRemove(key, member, delta) => {
+                let table = self.table("sorted_sets");
+
+                vec![
+                    self.statement(
+                        format!(
+                            "INSERT INTO {} VALUES ($1, $2, $3)
+                            ON CONFLICT (key, member) DO UPDATE
+                            SET score = sorted_sets.score + excluded.score",
+                            table
+                        ),
+                        vec![text(key), text(member), SqlValue::Int(*delta)],
+                    ),
+                    // removal of dangling scores (aka garbage collection)
+                    self.statement(
+                        format!(
+                            "DELETE FROM {} WHERE key = $1 AND member = $2 AND score = 0",
+                            table
+                        ),
+                        vec![text(key), text(member)],
+                    ),
+                ]
+            }
+            model::CRDTCommand::LastWriteWins(key, value, slot) => vec![self.statement(
+                format!(
+                    "INSERT INTO {} VALUES ($1, $2, $3)
+                    ON CONFLICT (key) DO UPDATE
+                    SET value = excluded.value, slot = excluded.slot
+                    WHERE registers.slot IS NULL OR registers.slot <= excluded.slot",
+                    self.table("registers")
+                ),
+                vec![
+                    text(key),
+                    SqlValue::Text(value_text(value)),
+                    SqlValue::Int(*slot as i64),
+                ],
+            )],
+            model::CRDTCommand::AnyWriteWins(key, value) => vec![self.statement(
+                format!(
+                    "INSERT INTO {} VALUES ($1, $2, NULL)
+                    ON CONFLICT (key) DO UPDATE SET value = excluded.value, slot = NULL",
+                    self.table("registers")
+                ),
+                vec![text(key), SqlValue::Text(value_text(value))],
+            )],
+            model::CRDTCommand::PNCounter(key, delta) => vec![self.statement(
+                format!(
+                    "INSERT INTO {} VALUES ($1, $2)
+                    ON CONFLICT (key) DO UPDATE SET value = counters.value + excluded.value",
+                    self.table("counters")
+                ),
+                vec![text(key), SqlValue::Int(*delta)],
+            )],
+            model::CRDTCommand::HashSetValue(key, member, value) => vec![self.statement(
+                format!(
+                    "INSERT INTO {} VALUES ($1, $2, $3)
+                    ON CONFLICT (key, member) DO UPDATE SET value = excluded.value",
+                    self.table("hashes")
+                ),
+                vec![text(key), text(member), SqlValue::Text(value_text(value))],
+            )],
+            model::CRDTCommand::HashCounter(key, member, delta) => {
+                // values set by other commands might not be integers, the
+                // cast is only attempted on the ones that are
+                let statement = self.statement(
+                    format!(
+                        "INSERT INTO {} VALUES ($1, $2, $3)
+                        ON CONFLICT (key, member) DO UPDATE
+                        SET value = CAST(
+                            CAST(hashes.value AS BIGINT) + CAST(excluded.value AS BIGINT) AS TEXT
+                        )
+                        WHERE {}",
+                        self.table("hashes"),
+                        self.is_integer("hashes.value")
+                    ),
+                    vec![text(key), text(member), SqlValue::Text(delta.to_string())],
+                );
+
+                vec![Statement {
+                    unchanged_error: Some(format!(
+                        "can't increase non-integer value of hash {key}, member {member}"
+                    )),
+                    ..statement
+                }]
+            }
+            model::CRDTCommand::HashUnsetKey(key, member) => vec![self.statement(
+                format!(
+                    "DELETE FROM {} WHERE key = $1 AND member = $2",
+                    self.table("hashes")
+                ),
+                vec![text(key), text(member)],
+            )],
+        }
+    }
+}
+
+fn text(value: &str) -> SqlValue {
+    SqlValue::Text(value.to_owned())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Dialect, SqlValue};
+    use crate::framework::model::CRDTCommand;
+    use crate::storage::journal::UndoOp;
+
+    #[test]
+    fn renders_placeholders_and_tables_per_dialect() {
+        let command = CRDTCommand::TwoPhaseSetAdd("s".into(), "a".into());
+
+        let postgres = Dialect::Postgres("scrolls".into()).apply(&command);
+        let sqlite = Dialect::Sqlite.apply(&command);
+
+        assert!(postgres[0].sql.contains("INTO scrolls.sets"));
+        assert!(postgres[0].sql.contains("key = $3 AND member = $2"));
+
+        assert!(sqlite[0].sql.contains("INTO sets"));
+        assert!(sqlite[0].sql.contains("key = ?3 AND member = ?2"));
+        assert!(!sqlite[0].sql.contains('$'));
+
+        assert_eq!(postgres[0].params, sqlite[0].params);
+        assert_eq!(postgres[0].params[2], SqlValue::Text("s.ts".into()));
+    }
+
+    #[test]
+    fn fails_hash_counter_on_unchanged_row() {
+        let command = CRDTCommand::HashCounter("h".into(), "n".into(), 1);
+
+        for dialect in [Dialect::Postgres("scrolls".into()), Dialect::Sqlite] {
+            let statements = dialect.apply(&command);
+
+            assert_eq!(statements.len(), 1);
+            assert!(statements[0].unchanged_error.is_some());
+        }
+    }
+
+    #[test]
+    fn restores_or_deletes_rows() {
+        let dialect = Dialect::Sqlite;
+
+        let restore = dialect.restore(&UndoOp::Register("r".into(), Some(("v".into(), None))));
+        assert!(restore.sql.starts_with("INSERT INTO registers"));
+        assert_eq!(restore.params[2], SqlValue::NullableInt(None));
+
+        let restore = dialect.restore(&UndoOp::Register("r".into(), None));
+        assert_eq!(restore.sql, "DELETE FROM registers WHERE key = ?1");
+
+        let restore = dialect.restore(&UndoOp::SetMember("s".into(), "a".into(), false));
+        assert_eq!(
+            restore.sql,
+            "DELETE FROM sets WHERE key = ?1 AND member = ?2"
+        );
+    }
+}
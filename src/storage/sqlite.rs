@@ -0,0 +1,674 @@
+use gasket::framework::*;
+use pallas::network::miniprotocols::Point;
+use rusqlite::{params, Connection, OptionalExtension, ToSql};
+use serde::Deserialize;
+use std::path::PathBuf;
+use tracing::{debug, info, warn};
+
+use crate::framework::*;
+
+use super::{
+    journal::{probe_rows, warn_if_exhausted, JournalEntry, UndoOp, DEFAULT_JOURNAL_DEPTH},
+    persisted_point, recv_pending,
+    sql::{Dialect, SqlValue},
+    CatchUp,
+};
+
+/// Opens the database file, creating the generic tables that hold every type
+/// of CRDT if needed
+fn open_db(path: &PathBuf) -> Result<Connection, Error> {
+    let conn = Connection::open(path).map_err(Error::storage)?;
+
+    // WAL allows external tools to query the file while the pipeline writes
+    conn.execute_batch(
+        r#"
+        PRAGMA journal_mode = WAL;
+
+        CREATE TABLE IF NOT EXISTS sets (
+            key TEXT NOT NULL,
+            member TEXT NOT NULL,
+            PRIMARY KEY (key, member)
+        );
+
+        CREATE TABLE IF NOT EXISTS sorted_sets (
+            key TEXT NOT NULL,
+            member TEXT NOT NULL,
+            score INTEGER NOT NULL,
+            PRIMARY KEY (key, member)
+        );
+
+        CREATE TABLE IF NOT EXISTS counters (
+            key TEXT PRIMARY KEY,
+            value INTEGER NOT NULL
+        );
+
+        CREATE TABLE IF NOT EXISTS registers (
+            key TEXT PRIMARY KEY,
+            value TEXT NOT NULL,
+            slot INTEGER
+        );
+
+        CREATE TABLE IF NOT EXISTS hashes (
+            key TEXT NOT NULL,
+            member TEXT NOT NULL,
+            value TEXT NOT NULL,
+            PRIMARY KEY (key, member)
+        );
+
+        CREATE TABLE IF NOT EXISTS _journal (
+            seq INTEGER PRIMARY KEY AUTOINCREMENT,
+            slot INTEGER NOT NULL,
+            entry TEXT NOT NULL
+        );
+
+        CREATE TABLE IF NOT EXISTS _cursor (
+            id INTEGER PRIMARY KEY,
+            breadcrumbs TEXT NOT NULL
+        );
+
+        -- members of every set, minus the tombstones of two-phase sets
+        CREATE VIEW IF NOT EXISTS live_sets AS
+            SELECT s.key, s.member FROM sets s
+            WHERE NOT EXISTS (
+                SELECT 1 FROM sets t
+                WHERE t.key = s.key || '.ts' AND t.member = s.member
+            );
+        "#,
+    )
+    .map_err(Error::storage)?;
+
+    Ok(conn)
+}
+
+/// Parameters of a shared statement, as expected by the driver
+fn sql_params(params: &[SqlValue]) -> Vec<&dyn ToSql> {
+    params
+        .iter()
+        .map(|x| match x {
+            SqlValue::Text(x) => x as &dyn ToSql,
+            SqlValue::Int(x) => x,
+            SqlValue::NullableInt(x) => x,
+        })
+        .collect()
+}
+
+/// Reads the pre-image of the row probed by the op
+fn read_pre_image(conn: &Connection, op: UndoOp) -> rusqlite::Result<UndoOp> {
+    let probe = Dialect::Sqlite.probe(&op);
+
+    let mut stmt = conn.prepare_cached(&probe.sql)?;
+    let mut rows = stmt.query(sql_params(&probe.params).as_slice())?;
+    let row = rows.next()?;
+
+    let op = match op {
+        UndoOp::SetMember(key, member, _) => UndoOp::SetMember(key, member, row.is_some()),
+        UndoOp::SortedSetMember(key, member, _) => {
+            UndoOp::SortedSetMember(key, member, row.map(|x| x.get(0)).transpose()?)
+        }
+        UndoOp::Counter(key, _) => UndoOp::Counter(key, row.map(|x| x.get(0)).transpose()?),
+        UndoOp::Register(key, _) => {
+            let value = row
+                .map(|x| -> rusqlite::Result<_> { Ok((x.get(0)?, x.get(1)?)) })
+                .transpose()?;
+
+            UndoOp::Register(key, value)
+        }
+        UndoOp::HashMember(key, member, _) => {
+            UndoOp::HashMember(key, member, row.map(|x| x.get(0)).transpose()?)
+        }
+    };
+
+    Ok(op)
+}
+
+fn restore_pre_image(conn: &Connection, op: &UndoOp) -> rusqlite::Result<usize> {
+    let restore = Dialect::Sqlite.restore(op);
+    conn.execute(&restore.sql, sql_params(&restore.params).as_slice())
+}
+
+fn capture_undo(
+    conn: &Connection,
+    commands: &[model::CRDTCommand],
+) -> rusqlite::Result<Vec<UndoOp>> {
+    probe_rows(commands)
+        .into_iter()
+        .map(|op| read_pre_image(conn, op))
+        .collect()
+}
+
+fn apply_command(conn: &Connection, command: &model::CRDTCommand) -> Result<(), WorkerError> {
+    debug!(?command, "applying");
+
+    for statement in Dialect::Sqlite.apply(command) {
+        let changed = conn
+            .execute(&statement.sql, sql_params(&statement.params).as_slice())
+            .or_restart()?;
+
+        if let (0, Some(err)) = (changed, statement.unchanged_error) {
+            return Err(Error::storage(err)).or_panic();
+        }
+    }
+
+    Ok(())
+}
+
+/// Applies the commands along with a journal entry holding their undo data
+fn apply_journaled(
+    conn: &Connection,
+    point: &Point,
+    commands: &[model::CRDTCommand],
+) -> Result<(), WorkerError> {
+    let undo = capture_undo(conn, commands).or_restart()?;
+
+    let entry = JournalEntry::new(point, undo);
+
+    let entry = serde_json::to_string(&entry).or_panic()?;
+
+    for command in commands {
+        apply_command(conn, command)?;
+    }
+
+    conn.execute(
+        "INSERT INTO _journal (slot, entry) VALUES (?1, ?2)",
+        params![point.slot_or_default() as i64, entry],
+    )
+    .or_restart()?;
+
+    Ok(())
+}
+
+fn save_cursor(conn: &Connection, breadcrumbs: &Breadcrumbs) -> Result<(), WorkerError> {
+    let breadcrumbs = serde_json::to_string(breadcrumbs).or_panic()?;
+
+    conn.execute(
+        "INSERT OR REPLACE INTO _cursor VALUES (0, ?1)",
+        params![breadcrumbs],
+    )
+    .or_restart()?;
+
+    Ok(())
+}
+
+pub struct Worker {
+    conn: Connection,
+    journal_depth: usize,
+}
+
+impl Worker {
+    fn apply_block(
+        &mut self,
+        cursor: &Cursor,
+        point: &Point,
+        commands: &[model::CRDTCommand],
+    ) -> Result<(), WorkerError> {
+        let tx = self.conn.transaction().or_restart()?;
+
+        // the cursor is saved in the same transaction as the data, a block up
+        // to its latest point was already committed by a previous attempt
+        // whose reply got lost or by a replay of the source
+        let persisted: Option<String> = tx
+            .query_row("SELECT breadcrumbs FROM _cursor WHERE id = 0", [], |row| {
+                row.get(0)
+            })
+            .optional()
+            .or_restart()?;
+
+        if let Some(persisted) = persisted_point(persisted.as_deref()).or_panic()? {
+            if point.slot_or_default() <= persisted.slot_or_default() {
+                warn!(?point, "skipping block already committed");
+                return Ok(());
+            }
+        }
+
+        apply_journaled(&tx, point, commands)?;
+
+        tx.execute(
+            "DELETE FROM _journal WHERE seq <= (SELECT MAX(seq) FROM _journal) - ?1",
+            params![self.journal_depth as i64],
+        )
+        .or_restart()?;
+
+        save_cursor(&tx, &cursor.breadcrumbs_after_apply(point))?;
+
+        tx.commit().or_restart()?;
+
+        Ok(())
+    }
+
+    /// Undoes the blocks after the point, then applies the commands
+    /// compensating for the rollback, if any, in the same transaction
+    fn rollback(
+        &mut self,
+        cursor: &Cursor,
+        point: &Point,
+        compensation: &[model::CRDTCommand],
+    ) -> Result<(), WorkerError> {
+        let tx = self.conn.transaction().or_restart()?;
+
+        let oldest: Option<i64> = tx
+            .query_row(
+                "SELECT slot FROM _journal ORDER BY seq LIMIT 1",
+                [],
+                |row| row.get(0),
+            )
+            .optional()
+            .or_restart()?;
+
+        warn_if_exhausted(oldest.map(|x| x as u64), cursor, point);
+
+        let entries = tx
+            .prepare("SELECT seq, entry FROM _journal WHERE slot > ?1 ORDER BY seq DESC")
+            .and_then(|mut stmt| {
+                stmt.query_map(params![point.slot_or_default() as i64], |row| {
+                    Ok((row.get::<_, i64>(0)?, row.get::<_, String>(1)?))
+                })?
+                .collect::<rusqlite::Result<Vec<_>>>()
+            })
+            .or_restart()?;
+
+        for (seq, entry) in entries {
+            let entry: JournalEntry<UndoOp> = serde_json::from_str(&entry).or_panic()?;
+
+            debug!(point = ?entry.point, "undoing block");
+
+            for op in entry.undo.iter() {
+                restore_pre_image(&tx, op).or_restart()?;
+            }
+
+            tx.execute("DELETE FROM _journal WHERE seq = ?1", params![seq])
+                .or_restart()?;
+        }
+
+        // journaled at the rollback point, a deeper rollback undoes them
+        if !compensation.is_empty() {
+            apply_journaled(&tx, point, compensation)?;
+        }
+
+        save_cursor(&tx, &cursor.breadcrumbs_after_rollback(point))?;
+
+        tx.commit().or_restart()?;
+
+        info!(?point, "rollback applied");
+
+        Ok(())
+    }
+}
+
+#[async_trait::async_trait(?Send)]
+impl gasket::framework::Worker<Stage> for Worker {
+    async fn bootstrap(stage: &Stage) -> Result<Self, WorkerError> {
+        let conn = open_db(&stage.config.path).or_panic()?;
+
+        let journal_depth = stage.config.journal_depth.unwrap_or(DEFAULT_JOURNAL_DEPTH);
+
+        Ok(Self {
+            conn,
+            journal_depth,
+        })
+    }
+
+    async fn schedule(
+        &mut self,
+        stage: &mut Stage,
+    ) -> Result<WorkSchedule<ChainEvent>, WorkerError> {
+        recv_pending(&mut stage.input, &mut stage.catch_up).await
+    }
+
+    async fn execute(&mut self, unit: &ChainEvent, stage: &mut Stage) -> Result<(), WorkerError> {
+        match unit {
+            ChainEvent::Apply(point, record) => match record {
+                Record::CRDTCommand(commands) => {
+                    self.apply_block(&stage.cursor, point, commands)?;
+                    stage.cursor.add_breadcrumb(point.clone());
+                }
+                Record::Compensation(commands) => {
+                    self.rollback(&stage.cursor, point, commands)?;
+                    stage.cursor.rollback(point);
+                }
+                _ => Err(Error::message("sqlite storage only accepts CRDT commands")).or_panic()?,
+            },
+            ChainEvent::Reset(point) => {
+                self.rollback(&stage.cursor, point, &[])?;
+                stage.cursor.rollback(point);
+            }
+        }
+
+        let point = unit.point();
+
+        stage.ops_count.inc(1);
+        stage.latest_block.set(point.slot_or_default() as i64);
+
+        Ok(())
+    }
+}
+
+#[derive(Stage)]
+#[stage(name = "sqlite", unit = "ChainEvent", worker = "Worker")]
+pub struct Stage {
+    config: Config,
+    cursor: Cursor,
+    catch_up: CatchUp,
+
+    pub input: StorageInputPort,
+
+    #[metric]
+    ops_count: gasket::metrics::Counter,
+
+    #[metric]
+    latest_block: gasket::metrics::Gauge,
+}
+
+#[derive(Default, Deserialize)]
+pub struct Config {
+    /// Path of the database file, created if it doesn't exist
+    pub path: PathBuf,
+
+    /// Max number of blocks that can be undone by a rollback
+    pub journal_depth: Option<usize>,
+}
+
+impl Config {
+    pub fn load_cursor(&self) -> Result<Cursor, Error> {
+        let conn = open_db(&self.path)?;
+
+        let breadcrumbs: Option<String> = conn
+            .query_row("SELECT breadcrumbs FROM _cursor WHERE id = 0", [], |row| {
+                row.get(0)
+            })
+            .optional()
+            .map_err(Error::storage)?;
+
+        match breadcrumbs {
+            Some(x) => {
+                let breadcrumbs = serde_json::from_str(&x).map_err(Error::storage)?;
+                Cursor::from_breadcrumbs(breadcrumbs)
+            }
+            None => Ok(Cursor::new(Default::default())),
+        }
+    }
+
+    pub fn bootstrapper(self, ctx: &Context) -> Result<Stage, Error> {
+        let stage = Stage {
+            config: self,
+            cursor: ctx.cursor.clone(),
+            catch_up: CatchUp::new(&ctx.cursor),
+            ops_count: Default::default(),
+            latest_block: Default::default(),
+            input: Default::default(),
+        };
+
+        Ok(stage)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use gasket::framework::WorkerError;
+    use pallas::network::miniprotocols::Point;
+
+    use super::{open_db, Worker};
+    use crate::framework::{
+        model::{CRDTCommand, Value},
+        Cursor,
+    };
+
+    fn point(slot: u64) -> Point {
+        Point::Specific(slot, vec![slot as u8; 32])
+    }
+
+    fn worker() -> Worker {
+        Worker {
+            conn: open_db(&":memory:".into()).unwrap(),
+            journal_depth: 10,
+        }
+    }
+
+    fn apply(worker: &mut Worker, cursor: &Cursor, slot: u64, commands: Vec<CRDTCommand>) {
+        worker.apply_block(cursor, &point(slot), &commands).unwrap();
+        cursor.add_breadcrumb(point(slot));
+    }
+
+    fn rollback(worker: &mut Worker, cursor: &Cursor, slot: u64) {
+        worker.rollback(cursor, &point(slot), &[]).unwrap();
+        cursor.rollback(&point(slot));
+    }
+
+    /// Every row of the data tables, as text
+    fn dump(worker: &Worker) -> Vec<String> {
+        let sql = "SELECT 'set', key, member FROM sets
+            UNION ALL SELECT 'sorted', key, member || '=' || score FROM sorted_sets
+            UNION ALL SELECT 'counter', key, CAST(value AS TEXT) FROM counters
+            UNION ALL SELECT 'register', key, value || '@' || IFNULL(slot, '') FROM registers
+            UNION ALL SELECT 'hash', key, member || '=' || value FROM hashes
+            ORDER BY 1, 2, 3";
+
+        let mut stmt = worker.conn.prepare(sql).unwrap();
+
+        stmt.query_map([], |row| {
+            Ok(format!(
+                "{} {} {}",
+                row.get::<_, String>(0)?,
+                row.get::<_, String>(1)?,
+                row.get::<_, String>(2)?
+            ))
+        })
+        .unwrap()
+        .collect::<Result<_, _>>()
+        .unwrap()
+    }
+
+    /// Members of the `s` set that weren't removed
+    fn live(worker: &Worker) -> Vec<String> {
+        let mut stmt = worker
+            .conn
+            .prepare("SELECT member FROM live_sets WHERE key = 's' ORDER BY member")
+            .unwrap();
+
+        stmt.query_map([], |row| row.get(0))
+            .unwrap()
+            .collect::<Result<_, _>>()
+            .unwrap()
+    }
+
+    fn text(value: &str) -> Value {
+        Value::String(value.to_owned())
+    }
+
+    #[test]
+    fn rollback_restores_state() {
+        let mut worker = worker();
+        let cursor = Cursor::new(Default::default());
+
+        apply(
+            &mut worker,
+            &cursor,
+            1,
+            vec![
+                CRDTCommand::SetAdd("s".into(), "a".into()),
+                CRDTCommand::SortedSetAdd("z".into(), "a".into(), 2),
+                CRDTCommand::PNCounter("c".into(), 5),
+                CRDTCommand::LastWriteWins("r".into(), text("old"), 1),
+                CRDTCommand::HashSetValue("h".into(), "a".into(), text("x")),
+                CRDTCommand::HashCounter("h".into(), "n".into(), 1),
+            ],
+        );
+
+        let before = dump(&worker);
+
+        apply(
+            &mut worker,
+            &cursor,
+            2,
+            vec![
+                CRDTCommand::SetRemove("s".into(), "a".into()),
+                CRDTCommand::SetAdd("s".into(), "b".into()),
+                CRDTCommand::SortedSetRemove("z".into(), "a".into(), -2),
+                CRDTCommand::SortedSetAdd("z".into(), "b".into(), 1),
+                CRDTCommand::PNCounter("c".into(), -3),
+                CRDTCommand::PNCounter("d".into(), 1),
+                CRDTCommand::LastWriteWins("r".into(), text("new"), 2),
+                CRDTCommand::AnyWriteWins("w".into(), text("any")),
+                CRDTCommand::HashUnsetKey("h".into(), "a".into()),
+                CRDTCommand::HashCounter("h".into(), "n".into(), 2),
+            ],
+        );
+
+        apply(
+            &mut worker,
+            &cursor,
+            3,
+            vec![CRDTCommand::PNCounter("c".into(), 10)],
+        );
+
+        assert_ne!(dump(&worker), before);
+
+        rollback(&mut worker, &cursor, 1);
+
+        assert_eq!(dump(&worker), before);
+        assert_eq!(cursor.latest_known_point(), Some(point(1)));
+    }
+
+    #[test]
+    fn fails_hash_counter_on_non_integer_value() {
+        let mut worker = worker();
+        let cursor = Cursor::new(Default::default());
+
+        apply(
+            &mut worker,
+            &cursor,
+            1,
+            vec![
+                CRDTCommand::HashSetValue("h".into(), "n".into(), text("41")),
+                CRDTCommand::HashCounter("h".into(), "n".into(), 1),
+                CRDTCommand::HashSetValue("h".into(), "x".into(), text("abc")),
+            ],
+        );
+
+        assert_eq!(dump(&worker), vec!["hash h n=42", "hash h x=abc"]);
+
+        let commands = vec![CRDTCommand::HashCounter("h".into(), "x".into(), 1)];
+        let result = worker.apply_block(&cursor, &point(2), &commands);
+
+        assert!(matches!(result, Err(WorkerError::Panic)));
+    }
+
+    #[test]
+    fn skips_persisted_blocks() {
+        let mut worker = worker();
+        let cursor = Cursor::new(Default::default());
+
+        apply(
+            &mut worker,
+            &cursor,
+            2,
+            vec![CRDTCommand::PNCounter("c".into(), 1)],
+        );
+
+        // a replay after a restart, with a cursor that lags behind
+        let stale = Cursor::new(Default::default());
+
+        for slot in [1, 2] {
+            apply(
+                &mut worker,
+                &stale,
+                slot,
+                vec![CRDTCommand::PNCounter("c".into(), 1)],
+            );
+        }
+
+        assert_eq!(dump(&worker), vec!["counter c 1"]);
+
+        apply(
+            &mut worker,
+            &stale,
+            3,
+            vec![CRDTCommand::PNCounter("c".into(), 1)],
+        );
+
+        assert_eq!(dump(&worker), vec!["counter c 2"]);
+    }
+
+    #[test]
+    fn journals_compensation_at_rollback_point() {
+        let mut worker = worker();
+        let cursor = Cursor::new(Default::default());
+
+        apply(
+            &mut worker,
+            &cursor,
+            1,
+            vec![CRDTCommand::PNCounter("c".into(), 1)],
+        );
+
+        let before = dump(&worker);
+
+        apply(
+            &mut worker,
+            &cursor,
+            2,
+            vec![CRDTCommand::PNCounter("c".into(), 2)],
+        );
+
+        let compensation = vec![
+            CRDTCommand::PNCounter("c".into(), 10),
+            CRDTCommand::PNCounter("rollbacks".into(), 1),
+        ];
+
+        worker.rollback(&cursor, &point(1), &compensation).unwrap();
+        cursor.rollback(&point(1));
+
+        assert_eq!(dump(&worker), vec!["counter c 11", "counter rollbacks 1"]);
+
+        // the compensation belongs to the rollback point, which is kept
+        rollback(&mut worker, &cursor, 1);
+        assert_eq!(dump(&worker), vec!["counter c 11", "counter rollbacks 1"]);
+
+        rollback(&mut worker, &cursor, 0);
+        assert_eq!(dump(&worker), Vec::<String>::new());
+
+        apply(
+            &mut worker,
+            &cursor,
+            1,
+            vec![CRDTCommand::PNCounter("c".into(), 1)],
+        );
+
+        assert_eq!(dump(&worker), before);
+    }
+
+    #[test]
+    fn ignores_two_phase_add_after_remove() {
+        let mut worker = worker();
+        let cursor = Cursor::new(Default::default());
+
+        apply(
+            &mut worker,
+            &cursor,
+            1,
+            vec![CRDTCommand::TwoPhaseSetAdd("s".into(), "a".into())],
+        );
+
+        apply(
+            &mut worker,
+            &cursor,
+            2,
+            vec![
+                CRDTCommand::TwoPhaseSetRemove("s".into(), "a".into()),
+                CRDTCommand::TwoPhaseSetAdd("s".into(), "a".into()),
+                CRDTCommand::TwoPhaseSetRemove("s".into(), "b".into()),
+                CRDTCommand::TwoPhaseSetAdd("s".into(), "b".into()),
+                CRDTCommand::TwoPhaseSetAdd("s".into(), "c".into()),
+            ],
+        );
+
+        assert_eq!(live(&worker), vec!["c"]);
+        assert_eq!(
+            dump(&worker),
+            vec!["set s a", "set s c", "set s.ts a", "set s.ts b"]
+        );
+
+        rollback(&mut worker, &cursor, 1);
+
+        assert_eq!(live(&worker), vec!["a"]);
+    }
+}